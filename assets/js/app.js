@@ -42,6 +42,19 @@ window.showToast = function (msg, type) {
   }, 3000);
 };
 
+// Spam-check token for comment/guestbook forms; the server uses its
+// timestamp to tell hurried bots from people
+window.loadFormToken = function (form) {
+  return fetch("/api/form-token")
+    .then(function (resp) {
+      return resp.json();
+    })
+    .then(function (data) {
+      form.dataset.formToken = data.token;
+    })
+    .catch(function () {});
+};
+
 // Highlight active nav link
 (function () {
   var path = window.location.pathname;
@@ -108,3 +121,32 @@ document.addEventListener("DOMContentLoaded", function () {
     }
   }
 });
+
+// Behaviour for markup that cannot carry inline handlers under the
+// Content-Security-Policy
+document.addEventListener("submit", function (e) {
+  var message = e.target.dataset && e.target.dataset.confirm;
+  if (message && !confirm(message)) e.preventDefault();
+});
+
+document.addEventListener("click", function (e) {
+  var back = e.target.closest && e.target.closest("[data-history-back]");
+  if (back && window.history.length > 1) {
+    e.preventDefault();
+    window.history.back();
+  }
+});
+
+document.querySelectorAll("link[data-async-style]").forEach(function (link) {
+  link.media = "all";
+});
+
+function applyProgressWidths(root) {
+  root.querySelectorAll("[data-progress]").forEach(function (bar) {
+    bar.style.width = bar.dataset.progress + "%";
+  });
+}
+applyProgressWidths(document);
+document.addEventListener("htmx:afterSwap", function (e) {
+  applyProgressWidths(e.target);
+});
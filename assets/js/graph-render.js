@@ -8,6 +8,29 @@
   var _i18nEl = document.getElementById("i18n-data");
   var _i18n = _i18nEl ? JSON.parse(_i18nEl.textContent) : {};
 
+  // Self-hosted library locations and SRI hashes (from #vendor-data)
+  var _vendorEl = document.getElementById("vendor-data");
+  var _vendor = _vendorEl ? JSON.parse(_vendorEl.textContent) : {};
+
+  function loadVendorScript(name, label, cb) {
+    var asset = _vendor[name];
+    if (!asset || !asset.src) {
+      console.error("Failed to load " + label);
+      return;
+    }
+    var s = document.createElement("script");
+    s.src = asset.src;
+    if (asset.integrity) {
+      s.integrity = asset.integrity;
+      s.crossOrigin = "anonymous";
+    }
+    s.onload = cb;
+    s.onerror = function () {
+      console.error("Failed to load " + label);
+    };
+    document.head.appendChild(s);
+  }
+
   // ── Pastel palettes (light / dark) ──
 
   var PALETTE_LIGHT = {
@@ -173,14 +196,7 @@
 
   function loadFP(cb) {
     if (typeof functionPlot !== "undefined") return cb();
-    var s = document.createElement("script");
-    s.src =
-      "https://cdn.jsdelivr.net/npm/function-plot@1/dist/function-plot.js";
-    s.onload = cb;
-    s.onerror = function () {
-      console.error("Failed to load function-plot");
-    };
-    document.head.appendChild(s);
+    loadVendorScript("function-plot", "function-plot", cb);
   }
 
   function mkSvgIcon(pathD, extra) {
@@ -421,13 +437,7 @@
 
   function loadCJ(cb) {
     if (typeof Chart !== "undefined") return cb();
-    var s = document.createElement("script");
-    s.src = "https://cdn.jsdelivr.net/npm/chart.js@4/dist/chart.umd.min.js";
-    s.onload = cb;
-    s.onerror = function () {
-      console.error("Failed to load Chart.js");
-    };
-    document.head.appendChild(s);
+    loadVendorScript("chart-js", "Chart.js", cb);
   }
 
   function renderChart(el) {
@@ -618,13 +628,7 @@
 
   function loadPlotly(cb) {
     if (typeof Plotly !== "undefined") return cb();
-    var s = document.createElement("script");
-    s.src = "https://cdn.jsdelivr.net/npm/plotly.js-dist-min@2/plotly.min.js";
-    s.onload = cb;
-    s.onerror = function () {
-      console.error("Failed to load Plotly.js");
-    };
-    document.head.appendChild(s);
+    loadVendorScript("plotly", "Plotly.js", cb);
   }
 
   // DSL parser for plot3d — supports array keys (vec, dataset)
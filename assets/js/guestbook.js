@@ -2,60 +2,85 @@ var i18nEl = document.getElementById("i18n-data");
 var i18n = i18nEl ? JSON.parse(i18nEl.textContent) : {};
 
 document.addEventListener("DOMContentLoaded", function () {
+  var guestbookForm = document.getElementById("guestbook-form");
+  loadFormToken(guestbookForm);
+
   // Guestbook form submission
-  document
-    .getElementById("guestbook-form")
-    .addEventListener("submit", async function (e) {
-      e.preventDefault();
+  guestbookForm.addEventListener("submit", async function (e) {
+    e.preventDefault();
 
-      var author = document.getElementById("author").value;
-      var content = document.getElementById("content").value;
-      var password = document.getElementById("password").value;
+    var author = document.getElementById("author").value;
+    var content = document.getElementById("content").value;
+    var password = document.getElementById("password").value;
+    var website = document.getElementById("website").value.trim();
 
-      if (!author.trim() || !content.trim()) {
-        alert(
-          i18n.guestbook_enter_both || "Please enter both name and message.",
-        );
-        return;
-      }
+    if (!author.trim() || !content.trim()) {
+      alert(
+        i18n.guestbook_enter_both || "Please enter both name and message.",
+      );
+      return;
+    }
 
-      try {
-        var response = await fetch("/api/guestbook", {
-          method: "POST",
-          headers: {
-            "Content-Type": "application/json",
-          },
-          body: JSON.stringify({
-            author: author,
-            content: content,
-            password: password || null,
-          }),
-        });
+    try {
+      var response = await fetch("/api/guestbook", {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+        },
+        body: JSON.stringify({
+          author: author,
+          content: content,
+          password: password || null,
+          website: website || null,
+          email: document.getElementById("email").value,
+          form_token: guestbookForm.dataset.formToken || null,
+        }),
+      });
 
-        if (response.ok) {
-          document.getElementById("author").value = "";
-          document.getElementById("content").value = "";
-          document.getElementById("password").value = "";
-          location.reload();
-        } else {
-          alert(
-            i18n.guestbook_failed || "Failed to post entry. Please try again.",
+      if (response.status === 429) {
+        showToast(
+          i18n.rate_limit || "Too many requests. Please wait a moment.",
+          "warn",
+        );
+      } else if (response.ok) {
+        document.getElementById("author").value = "";
+        document.getElementById("content").value = "";
+        document.getElementById("password").value = "";
+        document.getElementById("website").value = "";
+        var result = await response.json();
+        if (result.data.is_spam) {
+          showToast(
+            i18n.moderation_held ||
+              "Thanks! Your message will appear once it has been reviewed.",
+            "info",
           );
+          loadFormToken(guestbookForm);
+          return;
         }
-      } catch (error) {
-        console.error("Error:", error);
-        alert(i18n.comments_error || "An error occurred. Please try again.");
+        location.reload();
+      } else if (response.status === 400 && website) {
+        alert(
+          i18n.guestbook_invalid_website ||
+            "Please enter a website address starting with http:// or https://.",
+        );
+      } else {
+        alert(
+          i18n.guestbook_failed || "Failed to post entry. Please try again.",
+        );
       }
-    });
+    } catch (error) {
+      console.error("Error:", error);
+      alert(i18n.comments_error || "An error occurred. Please try again.");
+    }
+  });
 
   // Edit entry functionality
   document.querySelectorAll(".edit-btn").forEach(function (btn) {
     btn.addEventListener("click", async function () {
       var entryId = this.dataset.id;
       var entryContainer = this.closest(".group");
-      var currentContent = entryContainer.querySelector(
-        ".whitespace-pre-wrap",
-      ).textContent;
+      var currentContent =
+        entryContainer.querySelector(".user-content").dataset.raw;
 
       var password = prompt(
         i18n.comments_enter_password_edit || "Enter password to edit entry:",
@@ -80,7 +105,12 @@ document.addEventListener("DOMContentLoaded", function () {
           }),
         });
 
-        if (response.ok) {
+        if (response.status === 423) {
+          alert(
+            i18n.comments_locked ||
+              "Too many wrong passwords. Please try again later.",
+          );
+        } else if (response.ok) {
           var result = await response.json();
           if (result.success) {
             location.reload();
@@ -126,7 +156,12 @@ document.addEventListener("DOMContentLoaded", function () {
             body: JSON.stringify({ password: password }),
           });
 
-          if (response.ok) {
+          if (response.status === 423) {
+            alert(
+              i18n.comments_locked ||
+                "Too many wrong passwords. Please try again later.",
+            );
+          } else if (response.ok) {
             var result = await response.json();
             if (result.success) {
               location.reload();
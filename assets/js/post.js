@@ -24,6 +24,7 @@ var postId = commentsSection ? commentsSection.dataset.postId : null;
 if (postId) {
   document.addEventListener("DOMContentLoaded", function () {
     loadComments();
+    loadFormToken(document.getElementById("comment-form"));
   });
 
   document
@@ -34,6 +35,7 @@ if (postId) {
       var author = document.getElementById("comment-author").value;
       var content = document.getElementById("comment-content").value;
       var password = document.getElementById("comment-password").value;
+      var form = this;
 
       if (!author.trim() || !content.trim()) {
         alert(
@@ -51,6 +53,8 @@ if (postId) {
             author: author,
             content: content,
             password: password || null,
+            email: document.getElementById("comment-email").value,
+            form_token: form.dataset.formToken || null,
           }),
         });
 
@@ -63,6 +67,15 @@ if (postId) {
           document.getElementById("comment-author").value = "";
           document.getElementById("comment-content").value = "";
           document.getElementById("comment-password").value = "";
+          var result = await response.json();
+          if (result.data.is_spam) {
+            showToast(
+              i18n.moderation_held ||
+                "Thanks! Your message will appear once it has been reviewed.",
+              "info",
+            );
+          }
+          loadFormToken(form);
           loadComments();
         } else {
           showToast(
@@ -158,63 +171,53 @@ if (postId) {
     }, 150);
   }
 
-  // Like button functionality
+  // Reaction buttons
   (function () {
-    var cid = localStorage.getItem("blog_client_id");
-    if (!cid) {
-      cid = crypto.randomUUID();
-      localStorage.setItem("blog_client_id", cid);
-    }
-    var likeBtn = document.getElementById("like-btn");
-    var likeIcon = document.getElementById("like-icon");
-    var likeCount = document.getElementById("like-count");
-    if (!likeBtn) return;
+    var reactedClasses = ["border-primary/50", "bg-primary/10", "text-base-content"];
+    var idleClasses = [
+      "border-base-300/40",
+      "text-base-content/50",
+      "hover:border-primary/30",
+    ];
 
-    var likedSlugs = JSON.parse(
-      localStorage.getItem("blog_liked_posts") || "{}",
-    );
-    if (likedSlugs[postId]) {
-      likeIcon.className = "ph-fill ph-heart text-2xl text-red-400";
-      likeBtn.classList.add("text-red-400");
-    }
+    document.querySelectorAll(".reaction-btn").forEach(function (btn) {
+      var emoji = btn.querySelector(".reaction-emoji");
+      var count = btn.querySelector(".reaction-count");
 
-    likeBtn.addEventListener("click", async function () {
-      try {
-        var resp = await fetch("/api/post/" + postId + "/like", {
-          method: "POST",
-          headers: { "Content-Type": "application/json" },
-          body: JSON.stringify({ client_id: cid }),
-        });
-        if (resp.status === 429) {
-          showToast(
-            i18n.rate_limit || "Too many requests. Please wait a moment.",
-            "warn",
+      btn.addEventListener("click", async function () {
+        try {
+          var resp = await fetch(
+            "/api/post/" + postId + "/reactions/" + btn.dataset.kind,
+            { method: "POST" },
           );
-          return;
-        }
-        if (!resp.ok && resp.status !== 200) return;
-        var text = await resp.text();
-        if (!text) return;
-        var data = JSON.parse(text);
-        likeCount.textContent = data.like_count;
-        if (data.liked) {
-          likeIcon.className = "ph-fill ph-heart text-2xl text-red-400";
-          likeBtn.classList.add("text-red-400");
-          likedSlugs[postId] = true;
-          likeCelebrate(likeBtn);
-        } else {
-          likeIcon.className = "ph ph-heart text-2xl";
-          likeBtn.classList.remove("text-red-400");
-          delete likedSlugs[postId];
+          if (resp.status === 429) {
+            showToast(
+              i18n.rate_limit || "Too many requests. Please wait a moment.",
+              "warn",
+            );
+            return;
+          }
+          if (!resp.ok) return;
+          var data = await resp.json();
+          count.textContent = data.count;
+          btn.setAttribute("aria-pressed", data.reacted);
+          reactedClasses.forEach(function (c) {
+            btn.classList.toggle(c, data.reacted);
+          });
+          idleClasses.forEach(function (c) {
+            btn.classList.toggle(c, !data.reacted);
+          });
+          if (data.reacted) {
+            likeCelebrate(btn);
+          }
+          emoji.classList.add("like-bounce");
+          setTimeout(function () {
+            emoji.classList.remove("like-bounce");
+          }, 600);
+        } catch (e) {
+          console.error("Reaction error:", e);
         }
-        localStorage.setItem("blog_liked_posts", JSON.stringify(likedSlugs));
-        likeIcon.classList.add("like-bounce");
-        setTimeout(function () {
-          likeIcon.classList.remove("like-bounce");
-        }, 600);
-      } catch (e) {
-        console.error("Like error:", e);
-      }
+      });
     });
   })();
 }
@@ -306,11 +309,13 @@ async function loadComments() {
 
       var body = document.createElement("div");
       body.className = "pl-13";
-      var contentP = document.createElement("p");
-      contentP.className =
-        "text-base-content/90 whitespace-pre-wrap leading-relaxed";
-      contentP.textContent = comment.content;
-      body.appendChild(contentP);
+      // content_html is rendered and sanitized on the server
+      var contentEl = document.createElement("div");
+      contentEl.className =
+        "user-content prose prose-sm max-w-none text-base-content/90 leading-relaxed prose-pre:bg-base-200 prose-pre:text-base-content";
+      contentEl.innerHTML = comment.content_html;
+      contentEl.dataset.raw = comment.content;
+      body.appendChild(contentEl);
       card.appendChild(body);
 
       container.appendChild(card);
@@ -321,9 +326,8 @@ async function loadComments() {
       btn.addEventListener("click", async function () {
         var commentId = this.dataset.id;
         var commentContainer = this.closest(".group");
-        var currentContent = commentContainer.querySelector(
-          ".whitespace-pre-wrap",
-        ).textContent;
+        var currentContent =
+          commentContainer.querySelector(".user-content").dataset.raw;
 
         var password = prompt(
           i18n.comments_enter_password_edit ||
@@ -344,7 +348,12 @@ async function loadComments() {
             body: JSON.stringify({ content: newContent, password: password }),
           });
 
-          if (response.ok) {
+          if (response.status === 423) {
+            alert(
+              i18n.comments_locked ||
+                "Too many wrong passwords. Please try again later.",
+            );
+          } else if (response.ok) {
             var result = await response.json();
             if (result.success) {
               loadComments();
@@ -388,7 +397,12 @@ async function loadComments() {
               body: JSON.stringify({ password: password }),
             });
 
-            if (response.ok) {
+            if (response.status === 423) {
+              alert(
+                i18n.comments_locked ||
+                  "Too many wrong passwords. Please try again later.",
+              );
+            } else if (response.ok) {
               var result = await response.json();
               if (result.success) {
                 loadComments();
@@ -486,3 +500,24 @@ document.addEventListener("DOMContentLoaded", function () {
   mathDone = true;
   checkReveal();
 });
+
+// Share and copy-link buttons
+document.querySelectorAll("[data-share]").forEach(function (button) {
+  button.addEventListener("click", function () {
+    if (navigator.share) {
+      navigator.share({
+        title: button.dataset.shareTitle,
+        text: button.dataset.shareText,
+        url: window.location.href,
+      });
+    } else {
+      navigator.clipboard.writeText(window.location.href);
+    }
+  });
+});
+
+document.querySelectorAll("[data-copy-link]").forEach(function (button) {
+  button.addEventListener("click", function () {
+    navigator.clipboard.writeText(window.location.href);
+  });
+});
@@ -27,6 +27,11 @@
     console.log("Print completed");
   });
 
+  // Print to PDF buttons
+  document.querySelectorAll("[data-print]").forEach((button) => {
+    button.addEventListener("click", () => window.print());
+  });
+
   // Also optimize on Ctrl+P / Cmd+P
   document.addEventListener("keydown", (e) => {
     if ((e.ctrlKey || e.metaKey) && e.key === "p") {
@@ -32,6 +32,22 @@ document.addEventListener("DOMContentLoaded", function () {
     }
   });
 
+  // Search buttons and inline search bars (delegated, as htmx swaps pages)
+  document.addEventListener("click", function (e) {
+    if (e.target.closest && e.target.closest("[data-search-open]")) {
+      searchModal.showModal();
+      searchInput.focus();
+    }
+  });
+  document.addEventListener("keydown", function (e) {
+    if (e.key !== "Enter" && e.key !== " ") return;
+    if (e.target.closest && e.target.closest("[data-search-open]")) {
+      e.preventDefault();
+      searchModal.showModal();
+      searchInput.focus();
+    }
+  });
+
   // Arrow key navigation inside modal
   searchInput.addEventListener("keydown", function (e) {
     var items = searchResults.querySelectorAll("[data-search-item]");
@@ -108,6 +124,14 @@ document.addEventListener("DOMContentLoaded", function () {
     }, 300);
   });
 
+  // Open with a query from the URL (?q=), the target of the WebSite SearchAction
+  var initialQuery = new URLSearchParams(window.location.search).get("q");
+  if (initialQuery && initialQuery.trim().length > 0) {
+    searchModal.showModal();
+    searchInput.value = initialQuery;
+    performSearch(initialQuery.trim());
+  }
+
   function performSearch(query) {
     fetch("/api/search?q=" + encodeURIComponent(query) + "&lang=" + currentLang)
       .then(function (res) {
@@ -20,6 +20,8 @@ git fetch origin main
 git reset --hard origin/main
 
 NEW_HEAD=$(git rev-parse HEAD)
+# Reported by /health/ready
+export CONTENT_COMMIT=$NEW_HEAD
 
 # Ensure data directory exists with proper permissions
 mkdir -p data
@@ -35,9 +37,10 @@ fi
 : ${RESUME_TAG:=default-secret-tag}
 : ${RESUME_TITLE:=miniex::resume}
 
-# Check if any files outside content/assets/templates/docs changed
+# Check if any files outside content/docs changed; assets are baked into the
+# image with their vendored libraries, so they need a rebuild too
 CODE_CHANGED=$(git diff --name-only "$OLD_HEAD" "$NEW_HEAD" -- \
-  ':!contents/' ':!assets/' ':!*.md' ':!LICENSE' | head -1)
+  ':!contents/' ':!*.md' ':!LICENSE' | head -1)
 
 if [ -n "$CODE_CHANGED" ]; then
   # Code changed: full rebuild
@@ -46,42 +49,27 @@ if [ -n "$CODE_CHANGED" ]; then
   RESUME_TAG=$RESUME_TAG RESUME_TITLE=$RESUME_TITLE docker compose -f $COMPOSE_FILE up -d
   docker image prune -f
 else
-  # Content-only change: restart to pick up volume-mounted files
-  echo "Content-only change, restarting containers..."
-  RESUME_TAG=$RESUME_TAG RESUME_TITLE=$RESUME_TITLE docker compose -f $COMPOSE_FILE restart
+  # Content-only change: recreate (no rebuild) to pick up volume-mounted
+  # files and the new CONTENT_COMMIT
+  echo "Content-only change, recreating containers..."
+  RESUME_TAG=$RESUME_TAG RESUME_TITLE=$RESUME_TITLE docker compose -f $COMPOSE_FILE up -d --force-recreate
 fi
 
-# Ping search engines for indexing
+# Wait for the healthcheck: database reachable and content loaded
 echo "Waiting for container to be ready..."
-sleep 5
-
-INDEXNOW_BODY='{
-  "host": "miniex.blog",
-  "key": "0de3222f-6b9a-4e62-9d8e-5d98d960963e",
-  "keyLocation": "https://miniex.blog/assets/indexnow-key.txt",
-  "urlList": ["https://miniex.blog/sitemap.xml"]
-}'
-
-echo "Pinging Google Sitemap..."
-curl -s "https://www.google.com/ping?sitemap=https://miniex.blog/sitemap.xml" || true
-
-echo "Pinging IndexNow (Bing)..."
-curl -s -X POST "https://www.bing.com/indexnow" \
-  -H "Content-Type: application/json" -d "$INDEXNOW_BODY" || true
-
-echo "Pinging IndexNow (Naver)..."
-curl -s -X POST "https://searchadvisor.naver.com/indexnow" \
-  -H "Content-Type: application/json" -d "$INDEXNOW_BODY" || true
-
-echo "Pinging IndexNow (Yandex)..."
-curl -s -X POST "https://yandex.com/indexnow" \
-  -H "Content-Type: application/json" -d "$INDEXNOW_BODY" || true
-
-echo "Pinging IndexNow (Seznam)..."
-curl -s -X POST "https://search.seznam.cz/indexnow" \
-  -H "Content-Type: application/json" -d "$INDEXNOW_BODY" || true
-
-echo "Search engine ping complete."
+HEALTH=""
+for _ in $(seq 1 60); do
+  HEALTH=$(docker inspect --format '{{.State.Health.Status}}' m0000-blog 2>/dev/null || true)
+  [ "$HEALTH" = "healthy" ] && break
+  sleep 2
+done
+if [ "$HEALTH" != "healthy" ]; then
+  echo "Container did not become ready (health: ${HEALTH:-unknown})"
+  docker exec m0000-blog ./blog healthcheck || true
+  ssh-add -D
+  eval $(ssh-agent -k)
+  exit 1
+fi
 
 # Remove the key from the agent and kill the agent
 ssh-add -D
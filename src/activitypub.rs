@@ -0,0 +1,1164 @@
+//! ActivityPub federation. The blog is a single actor that accounts on
+//! Mastodon and similar servers can follow; new posts are delivered to
+//! followers as `Create(Article)` and replies come back through the inbox.
+
+pub mod signature;
+
+use crate::{
+    config::ActivityPubConfig,
+    db::{Database, FederatedReply, Follower},
+    http::{HttpClient, HttpRequest},
+    i18n::Lang,
+    notify::retry_delay,
+    post::{dedup_refs_by_translation, Post},
+    sanitize::{clean_remote_html, normalize_website},
+    SITE_DESCRIPTION, SITE_URL,
+};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use signature::{ActorKey, SignatureHeader};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use url::Url;
+
+pub const ACTIVITY_JSON: &str = "application/activity+json";
+pub const ACTOR_PATH: &str = "/ap/actor";
+pub const INBOX_PATH: &str = "/ap/inbox";
+pub const OUTBOX_PATH: &str = "/ap/outbox";
+pub const FOLLOWERS_PATH: &str = "/ap/followers";
+pub const OBJECT_PATH: &str = "/ap/posts";
+
+pub const ACTIVITYSTREAMS: &str = "https://www.w3.org/ns/activitystreams";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+const OUTBOX_PAGE_SIZE: usize = 20;
+const KEY_BITS: usize = 2048;
+/// Fetched signers are reused for this long before their key is fetched again.
+const SIGNER_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const SIGNER_CACHE_MAX: usize = 1000;
+/// Upper bound on queued deliveries attempted per run.
+const DELIVERY_BATCH_LIMIT: i32 = 100;
+/// Deliveries are given up after this many attempts, about a day of backoff.
+const DELIVERY_MAX_ATTEMPTS: u32 = 10;
+const DELIVERY_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Why an inbox delivery was refused.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InboxError {
+    BadRequest(&'static str),
+    Unauthorized(&'static str),
+    Internal,
+}
+
+/// The parts of an incoming inbox request needed to check its signature.
+pub struct InboxRequest<'a> {
+    pub method: &'a str,
+    pub path_and_query: &'a str,
+    pub headers: &'a HeaderMap,
+    pub body: &'a [u8],
+}
+
+/// A remote actor document, reduced to what we use.
+#[derive(Debug, Clone)]
+pub struct RemoteActor {
+    pub id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+    /// Display name, falling back to `@user@host`.
+    pub display_name: String,
+    pub public_key_pem: Option<String>,
+}
+
+impl RemoteActor {
+    fn from_json(doc: &Value, key_id: &str) -> Option<Self> {
+        let id = http_link(doc["id"].as_str()?)?;
+        let host = Url::parse(&id).ok()?.host_str()?.to_string();
+        let display_name = doc["name"]
+            .as_str()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .or_else(|| {
+                doc["preferredUsername"]
+                    .as_str()
+                    .map(|user| format!("@{}@{}", user, host))
+            })
+            .unwrap_or(host);
+
+        let keys = match &doc["publicKey"] {
+            Value::Array(keys) => keys.iter().collect(),
+            key => vec![key],
+        };
+        let public_key_pem = keys
+            .into_iter()
+            .find(|key| key["id"].as_str() == Some(key_id))
+            .and_then(|key| key["publicKeyPem"].as_str())
+            .map(str::to_string);
+
+        Some(RemoteActor {
+            id,
+            inbox: http_link(doc["inbox"].as_str()?)?,
+            shared_inbox: doc["endpoints"]["sharedInbox"].as_str().and_then(http_link),
+            display_name,
+            public_key_pem,
+        })
+    }
+}
+
+/// Id of a referenced object, whether it is embedded or just linked.
+fn object_id(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value["id"].as_str())
+}
+
+/// `link` unchanged if it is an http(s) URL with a host. Actor documents
+/// are remote input whose links end up in `href`s and outgoing requests.
+fn http_link(link: &str) -> Option<String> {
+    let url = Url::parse(link).ok()?;
+    (matches!(url.scheme(), "http" | "https") && url.host_str().is_some()).then(|| link.to_string())
+}
+
+fn same_host(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.host_str().is_some() && a.host_str() == b.host_str(),
+        _ => false,
+    }
+}
+
+fn site_domain() -> String {
+    Url::parse(SITE_URL)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+pub fn actor_id() -> String {
+    format!("{}{}", SITE_URL, ACTOR_PATH)
+}
+
+fn key_id() -> String {
+    format!("{}#main-key", actor_id())
+}
+
+fn followers_id() -> String {
+    format!("{}{}", SITE_URL, FOLLOWERS_PATH)
+}
+
+fn article_id(slug: &str) -> String {
+    format!("{}{}/{}", SITE_URL, OBJECT_PATH, slug)
+}
+
+/// Slug of the local post an `inReplyTo` points at; both the ActivityPub
+/// object id and the HTML page URL are accepted.
+pub fn post_slug_from_object(id: &str) -> Option<String> {
+    let url = Url::parse(id).ok()?;
+    if url.host_str() != Some(site_domain().as_str()) {
+        return None;
+    }
+    let path = url.path();
+    let slug = path
+        .strip_prefix(&format!("{}/", OBJECT_PATH))
+        .or_else(|| path.strip_prefix("/post/"))?
+        .trim_end_matches('/');
+    (!slug.is_empty() && !slug.contains('/')).then(|| slug.to_string())
+}
+
+/// Posts that are federated: one per translation, newest first.
+pub fn federated_posts(posts: &[Post]) -> Vec<&Post> {
+    let mut posts = dedup_refs_by_translation(posts.iter(), Lang::En);
+    posts.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
+    posts
+}
+
+pub struct Federation {
+    username: String,
+    display_name: String,
+    pub show_replies: bool,
+    key: ActorKey,
+    /// Signers by key id, so repeated deliveries do not refetch their key.
+    signers: Mutex<HashMap<String, (RemoteActor, Instant)>>,
+}
+
+impl Federation {
+    pub fn new(config: &ActivityPubConfig, key: ActorKey) -> Self {
+        Federation {
+            username: config.username.clone(),
+            display_name: config.display_name.clone(),
+            show_replies: config.show_replies,
+            key,
+            signers: Mutex::default(),
+        }
+    }
+
+    /// Load the actor key from the database, generating one on first start.
+    pub async fn load(db: &Database, config: &ActivityPubConfig) -> anyhow::Result<Self> {
+        if db.get_activitypub_key().await?.is_none() {
+            tracing::info!("Generating ActivityPub actor key");
+            let key = tokio::task::spawn_blocking(|| ActorKey::generate(KEY_BITS)).await??;
+            db.store_activitypub_key(&key.private_pem()?, key.public_pem())
+                .await?;
+        }
+        let (private_pem, _) = db
+            .get_activitypub_key()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("ActivityPub key missing after generation"))?;
+        Ok(Self::new(config, ActorKey::from_pem(&private_pem)?))
+    }
+
+    fn acct(&self) -> String {
+        format!("acct:{}@{}", self.username, site_domain())
+    }
+
+    /// JRD for `/.well-known/webfinger`, if `resource` names our actor.
+    pub fn webfinger(&self, resource: &str) -> Option<Value> {
+        if !resource.eq_ignore_ascii_case(&self.acct()) && resource != actor_id() {
+            return None;
+        }
+        Some(json!({
+            "subject": self.acct(),
+            "aliases": [actor_id()],
+            "links": [
+                { "rel": "self", "type": ACTIVITY_JSON, "href": actor_id() },
+                { "rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": SITE_URL },
+            ],
+        }))
+    }
+
+    pub fn actor_document(&self) -> Value {
+        json!({
+            "@context": [ACTIVITYSTREAMS, "https://w3id.org/security/v1"],
+            "id": actor_id(),
+            "type": "Person",
+            "preferredUsername": self.username,
+            "name": self.display_name,
+            "summary": SITE_DESCRIPTION,
+            "url": SITE_URL,
+            "inbox": format!("{}{}", SITE_URL, INBOX_PATH),
+            "outbox": format!("{}{}", SITE_URL, OUTBOX_PATH),
+            "followers": followers_id(),
+            "endpoints": { "sharedInbox": format!("{}{}", SITE_URL, INBOX_PATH) },
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "publicKey": {
+                "id": key_id(),
+                "owner": actor_id(),
+                "publicKeyPem": self.key.public_pem(),
+            },
+        })
+    }
+
+    pub fn article(post: &Post) -> Value {
+        let tags: Vec<Value> = post
+            .metadata
+            .tags
+            .iter()
+            .map(|tag| json!({ "type": "Hashtag", "name": format!("#{}", tag.replace(' ', "")) }))
+            .collect();
+        json!({
+            "id": article_id(&post.slug),
+            "type": "Article",
+            "attributedTo": actor_id(),
+            "name": post.metadata.title,
+            "summary": post.metadata.description,
+            "content": post.content,
+            "mediaType": "text/html",
+            "url": format!("{}/post/{}", SITE_URL, post.slug),
+            "published": post.metadata.created_at.to_rfc3339(),
+            "updated": post.metadata.updated_at.to_rfc3339(),
+            "to": [PUBLIC],
+            "cc": [followers_id()],
+            "tag": tags,
+        })
+    }
+
+    fn create_activity(post: &Post) -> Value {
+        json!({
+            "id": format!("{}#create", article_id(&post.slug)),
+            "type": "Create",
+            "actor": actor_id(),
+            "published": post.metadata.created_at.to_rfc3339(),
+            "to": [PUBLIC],
+            "cc": [followers_id()],
+            "object": Self::article(post),
+        })
+    }
+
+    pub fn outbox(posts: &[Post]) -> Value {
+        let posts = federated_posts(posts);
+        let items: Vec<Value> = posts
+            .iter()
+            .take(OUTBOX_PAGE_SIZE)
+            .map(|post| Self::create_activity(post))
+            .collect();
+        json!({
+            "@context": ACTIVITYSTREAMS,
+            "id": format!("{}{}", SITE_URL, OUTBOX_PATH),
+            "type": "OrderedCollection",
+            "totalItems": posts.len(),
+            "orderedItems": items,
+        })
+    }
+
+    /// Follower count only; the list itself is not published.
+    pub fn followers_collection(count: u32) -> Value {
+        json!({
+            "@context": ACTIVITYSTREAMS,
+            "id": followers_id(),
+            "type": "OrderedCollection",
+            "totalItems": count,
+        })
+    }
+
+    /// Signed GET of an ActivityPub document; servers in "authorized fetch"
+    /// mode refuse unsigned ones.
+    async fn fetch(&self, http: &dyn HttpClient, url: &str) -> anyhow::Result<Value> {
+        let request = HttpRequest::get(url).header("Accept", ACTIVITY_JSON);
+        let request = self.key.sign_request(&key_id(), request, Utc::now())?;
+        let response = http.execute(request).await?;
+        if !response.is_success() {
+            anyhow::bail!("{} responded with status {}", url, response.status);
+        }
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Resolve a signature `keyId` to the actor that owns the key.
+    async fn fetch_signer(
+        &self,
+        http: &dyn HttpClient,
+        key_id: &str,
+    ) -> anyhow::Result<RemoteActor> {
+        let mut key_url = Url::parse(key_id)?;
+        key_url.set_fragment(None);
+        let doc = self.fetch(http, key_url.as_str()).await?;
+
+        let actor = if doc["inbox"].is_string() {
+            RemoteActor::from_json(&doc, key_id)
+        } else {
+            // A standalone key document that points at its owner
+            let owner = doc["owner"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("key document has no owner"))?;
+            let actor_doc = self.fetch(http, owner).await?;
+            RemoteActor::from_json(&actor_doc, key_id).map(|mut actor| {
+                actor.public_key_pem = doc["publicKeyPem"].as_str().map(str::to_string);
+                actor
+            })
+        }
+        .ok_or_else(|| anyhow::anyhow!("invalid actor document for {}", key_id))?;
+
+        if !same_host(&actor.id, key_id) {
+            anyhow::bail!("actor {} is not hosted with key {}", actor.id, key_id);
+        }
+        Ok(actor)
+    }
+
+    fn cached_signer(&self, key_id: &str) -> Option<RemoteActor> {
+        let signers = self.signers.lock().unwrap();
+        let (actor, fetched_at) = signers.get(key_id)?;
+        (fetched_at.elapsed() < SIGNER_CACHE_TTL).then(|| actor.clone())
+    }
+
+    fn cache_signer(&self, key_id: &str, actor: &RemoteActor) {
+        let mut signers = self.signers.lock().unwrap();
+        if signers.len() >= SIGNER_CACHE_MAX {
+            signers.retain(|_, (_, fetched_at)| fetched_at.elapsed() < SIGNER_CACHE_TTL);
+            if signers.len() >= SIGNER_CACHE_MAX {
+                signers.clear();
+            }
+        }
+        signers.insert(key_id.to_string(), (actor.clone(), Instant::now()));
+    }
+
+    async fn verify_request(
+        &self,
+        http: &dyn HttpClient,
+        request: &InboxRequest<'_>,
+    ) -> Result<RemoteActor, InboxError> {
+        let header = |name: &str| {
+            request
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let sig = header("signature")
+            .and_then(|v| SignatureHeader::parse(&v))
+            .ok_or(InboxError::Unauthorized(
+                "missing or malformed Signature header",
+            ))?;
+        if sig
+            .algorithm
+            .as_deref()
+            .is_some_and(|alg| !matches!(alg, "rsa-sha256" | "hs2019"))
+        {
+            return Err(InboxError::Unauthorized("unsupported signature algorithm"));
+        }
+        if !["(request-target)", "host", "date", "digest"]
+            .iter()
+            .all(|h| sig.covers(h))
+        {
+            return Err(InboxError::Unauthorized(
+                "signature must cover (request-target), host, date and digest",
+            ));
+        }
+        if !header("date").is_some_and(|d| signature::date_is_fresh(&d, Utc::now())) {
+            return Err(InboxError::Unauthorized("Date header is missing or stale"));
+        }
+        if !header("digest").is_some_and(|d| signature::digest_matches(&d, request.body)) {
+            return Err(InboxError::Unauthorized("Digest does not match the body"));
+        }
+        let signing_string =
+            signature::signing_string(request.method, request.path_and_query, &sig.headers, header)
+                .ok_or(InboxError::Unauthorized("a signed header is missing"))?;
+
+        let actor = match self.cached_signer(&sig.key_id) {
+            Some(actor) => actor,
+            None => {
+                let actor = self.fetch_signer(http, &sig.key_id).await.map_err(|e| {
+                    tracing::info!("Could not fetch signing key {}: {}", sig.key_id, e);
+                    InboxError::Unauthorized("could not fetch the signing key")
+                })?;
+                self.cache_signer(&sig.key_id, &actor);
+                actor
+            }
+        };
+        let verified = actor
+            .public_key_pem
+            .as_deref()
+            .is_some_and(|pem| signature::verify(pem, &signing_string, &sig.signature));
+        if !verified {
+            return Err(InboxError::Unauthorized("signature verification failed"));
+        }
+        Ok(actor)
+    }
+
+    /// Verify and apply an activity posted to our inbox. `known_slugs` are
+    /// the posts replies may attach to.
+    pub async fn receive(
+        &self,
+        db: &Database,
+        http: &dyn HttpClient,
+        known_slugs: &HashSet<String>,
+        request: &InboxRequest<'_>,
+    ) -> Result<(), InboxError> {
+        let activity: Value = serde_json::from_slice(request.body)
+            .map_err(|_| InboxError::BadRequest("body is not JSON"))?;
+        let actor = self.verify_request(http, request).await?;
+        if activity["actor"].as_str() != Some(actor.id.as_str()) {
+            return Err(InboxError::Unauthorized(
+                "activity actor does not match the signature",
+            ));
+        }
+
+        self.apply(db, http, known_slugs, &actor, &activity)
+            .await
+            .map_err(|e| {
+                tracing::error!("ActivityPub inbox error for {}: {}", actor.id, e);
+                InboxError::Internal
+            })
+    }
+
+    async fn apply(
+        &self,
+        db: &Database,
+        http: &dyn HttpClient,
+        known_slugs: &HashSet<String>,
+        actor: &RemoteActor,
+        activity: &Value,
+    ) -> anyhow::Result<()> {
+        let object = &activity["object"];
+        match activity["type"].as_str().unwrap_or_default() {
+            "Follow" if object_id(object) == Some(actor_id().as_str()) => {
+                db.upsert_follower(&Follower {
+                    actor_id: actor.id.clone(),
+                    inbox: actor.inbox.clone(),
+                    shared_inbox: actor.shared_inbox.clone(),
+                })
+                .await?;
+                tracing::info!("New ActivityPub follower {}", actor.id);
+
+                let accept = json!({
+                    "@context": ACTIVITYSTREAMS,
+                    "id": format!("{}#accepts/{}", actor_id(), uuid::Uuid::new_v4()),
+                    "type": "Accept",
+                    "actor": actor_id(),
+                    "object": activity,
+                });
+                if let Err(e) = self.deliver(http, &actor.inbox, &accept).await {
+                    tracing::warn!("Could not deliver Accept to {}: {}", actor.inbox, e);
+                }
+            }
+            "Undo" if object["type"].as_str() == Some("Follow") => {
+                db.remove_follower(&actor.id).await?;
+                tracing::info!("ActivityPub follower {} left", actor.id);
+            }
+            "Create" | "Update" => {
+                if let Some(reply) = reply_from_note(object, actor, known_slugs) {
+                    db.upsert_federated_reply(&reply).await?;
+                }
+            }
+            "Delete" => match object_id(object) {
+                Some(id) if id == actor.id => db.remove_follower(&actor.id).await?,
+                Some(id) => {
+                    db.delete_federated_reply(id, &actor.id).await?;
+                }
+                None => {}
+            },
+            other => tracing::debug!("Ignoring ActivityPub {} from {}", other, actor.id),
+        }
+        Ok(())
+    }
+
+    pub async fn deliver(
+        &self,
+        http: &dyn HttpClient,
+        inbox: &str,
+        activity: &Value,
+    ) -> anyhow::Result<()> {
+        let mut activity = activity.clone();
+        if activity.get("@context").is_none() {
+            activity["@context"] = json!(ACTIVITYSTREAMS);
+        }
+        let request = HttpRequest::post(inbox, ACTIVITY_JSON, serde_json::to_vec(&activity)?);
+        let request = self.key.sign_request(&key_id(), request, Utc::now())?;
+        let response = http.execute(request).await?;
+        if !response.is_success() {
+            anyhow::bail!("{} responded with status {}", inbox, response.status);
+        }
+        Ok(())
+    }
+
+    /// Queue `Create(Article)` for posts not announced before, one delivery
+    /// per follower inbox, and deliver what is due. On the very first run
+    /// every existing post is only recorded, so enabling federation does not
+    /// flood followers with the archive.
+    pub async fn publish_new_posts(&self, db: &Database, http: &dyn HttpClient, posts: &[Post]) {
+        let first_run = match db.count_federated_posts().await {
+            Ok(count) => count == 0,
+            Err(e) => {
+                tracing::error!("count_federated_posts error: {}", e);
+                return;
+            }
+        };
+        let inboxes = match db.get_follower_inboxes().await {
+            Ok(inboxes) if !first_run => inboxes,
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                tracing::error!("get_follower_inboxes error: {}", e);
+                return;
+            }
+        };
+
+        for post in federated_posts(posts) {
+            let activity = Self::create_activity(post).to_string();
+            match db
+                .record_federated_post(&post.slug, &inboxes, &activity)
+                .await
+            {
+                Ok(true) if !first_run => tracing::info!(
+                    "Queued {} for {} follower inboxes",
+                    post.slug,
+                    inboxes.len()
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("record_federated_post error for {}: {}", post.slug, e),
+            }
+        }
+        self.deliver_due(db, http, Utc::now().timestamp()).await;
+    }
+
+    /// Attempt every queued delivery that is due; failures are retried with
+    /// the notification queue's backoff.
+    pub async fn deliver_due(&self, db: &Database, http: &dyn HttpClient, now: i64) {
+        let due = match db.get_due_deliveries(now, DELIVERY_BATCH_LIMIT).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::error!("ActivityPub delivery queue read failed: {}", e);
+                return;
+            }
+        };
+        for delivery in due {
+            let sent = match serde_json::from_str::<Value>(&delivery.activity) {
+                Ok(activity) => self.deliver(http, &delivery.inbox, &activity).await,
+                Err(e) => Err(e.into()),
+            };
+            let result = match sent {
+                Ok(()) => db.complete_delivery(delivery.id).await,
+                Err(e) => {
+                    let attempts = delivery.attempts + 1;
+                    let retry_at =
+                        (attempts < DELIVERY_MAX_ATTEMPTS).then(|| now + retry_delay(attempts));
+                    tracing::warn!(
+                        "ActivityPub delivery to {} failed (attempt {}/{}): {}",
+                        delivery.inbox,
+                        attempts,
+                        DELIVERY_MAX_ATTEMPTS,
+                        e
+                    );
+                    db.fail_delivery(delivery.id, retry_at, &e.to_string())
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                tracing::error!("ActivityPub delivery queue update failed: {}", e);
+            }
+        }
+    }
+
+    /// Retry queued deliveries in the background.
+    pub fn spawn_delivery_worker(self: Arc<Self>, db: Database, http: Arc<dyn HttpClient>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DELIVERY_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.deliver_due(&db, http.as_ref(), Utc::now().timestamp())
+                    .await;
+            }
+        });
+    }
+}
+
+/// A reply to one of our posts, if `note` is one written by `actor`.
+fn reply_from_note(
+    note: &Value,
+    actor: &RemoteActor,
+    known_slugs: &HashSet<String>,
+) -> Option<FederatedReply> {
+    if !matches!(note["type"].as_str(), Some("Note" | "Article")) {
+        return None;
+    }
+    if object_id(&note["attributedTo"]) != Some(actor.id.as_str()) {
+        return None;
+    }
+    let id = note["id"].as_str().filter(|id| same_host(id, &actor.id))?;
+    let post_slug = object_id(&note["inReplyTo"])
+        .and_then(post_slug_from_object)
+        .filter(|slug| known_slugs.contains(slug))?;
+
+    // Rendered as a link, so only http(s) on the actor's own host; otherwise
+    // the note id, and no reply at all if that fails too.
+    let actor_link = |link: &str| normalize_website(link).filter(|l| same_host(l, &actor.id));
+    let url = match &note["url"] {
+        Value::String(url) => Some(url.as_str()),
+        Value::Object(link) => link["href"].as_str(),
+        _ => None,
+    }
+    .and_then(actor_link)
+    .or_else(|| actor_link(id))?;
+    let created_at = note["published"]
+        .as_str()
+        .and_then(|p| DateTime::parse_from_rfc3339(p).ok())
+        .map(|p| p.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+
+    Some(FederatedReply {
+        id: id.to_string(),
+        post_slug,
+        actor_id: actor.id.clone(),
+        author_name: actor.display_name.clone(),
+        url,
+        content_html: clean_remote_html(note["content"].as_str().unwrap_or_default()),
+        created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use crate::http::{stand_in_server, HttpResponse, ReqwestClient};
+    use crate::post::sample_post;
+    use axum::{
+        extract::State,
+        routing::{get, post},
+        Json, Router,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type Inbox = Arc<Mutex<Vec<Value>>>;
+
+    fn test_federation() -> Federation {
+        let pem = signature::test_key().private_pem().unwrap();
+        Federation::new(
+            &ActivityPubConfig::default(),
+            ActorKey::from_pem(&pem).unwrap(),
+        )
+    }
+
+    /// A stand-in remote server hosting `/users/alice` and an inbox that
+    /// records what it receives. Returns its base URL.
+    async fn remote_server(inbox: Inbox) -> String {
+        stand_in_server(|base| {
+            let actor = json!({
+                "id": format!("{}/users/alice", base),
+                "type": "Person",
+                "preferredUsername": "alice",
+                "inbox": format!("{}/users/alice/inbox", base),
+                "endpoints": { "sharedInbox": format!("{}/inbox", base) },
+                "publicKey": {
+                    "id": format!("{}/users/alice#main-key", base),
+                    "owner": format!("{}/users/alice", base),
+                    "publicKeyPem": signature::test_key().public_pem(),
+                },
+            });
+            Router::new()
+                .route("/users/alice", get(move || async move { Json(actor) }))
+                .route(
+                    "/users/alice/inbox",
+                    post(|State(inbox): State<Inbox>, body: String| async move {
+                        inbox
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_str(&body).unwrap());
+                        axum::http::StatusCode::ACCEPTED
+                    }),
+                )
+                .route(
+                    "/inbox",
+                    post(|State(inbox): State<Inbox>, body: String| async move {
+                        inbox
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_str(&body).unwrap());
+                        axum::http::StatusCode::ACCEPTED
+                    }),
+                )
+                .with_state(inbox)
+        })
+        .await
+    }
+
+    /// Sign `activity` as alice for delivery to our inbox.
+    fn signed_delivery(base: &str, activity: &Value) -> (HeaderMap, Vec<u8>) {
+        let body = serde_json::to_vec(activity).unwrap();
+        let request = HttpRequest::post(
+            &format!("{}{}", SITE_URL, INBOX_PATH),
+            ACTIVITY_JSON,
+            body.clone(),
+        );
+        let signed = signature::test_key()
+            .sign_request(
+                &format!("{}/users/alice#main-key", base),
+                request,
+                Utc::now(),
+            )
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        for (name, value) in &signed.headers {
+            headers.insert(
+                axum::http::HeaderName::from_bytes(name.to_ascii_lowercase().as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        (headers, body)
+    }
+
+    async fn deliver_to_us(
+        federation: &Federation,
+        db: &Database,
+        base: &str,
+        activity: &Value,
+    ) -> Result<(), InboxError> {
+        let (headers, body) = signed_delivery(base, activity);
+        let slugs = HashSet::from(["hello".to_string()]);
+        let request = InboxRequest {
+            method: "POST",
+            path_and_query: INBOX_PATH,
+            headers: &headers,
+            body: &body,
+        };
+        federation
+            .receive(db, &ReqwestClient::new(true), &slugs, &request)
+            .await
+    }
+
+    #[test]
+    fn test_webfinger_matches_acct_and_actor() {
+        let federation = test_federation();
+        let jrd = federation.webfinger("acct:blog@miniex.blog").unwrap();
+        assert_eq!(jrd["links"][0]["href"], "https://miniex.blog/ap/actor");
+        assert!(federation
+            .webfinger("https://miniex.blog/ap/actor")
+            .is_some());
+        assert!(federation.webfinger("acct:other@miniex.blog").is_none());
+    }
+
+    #[test]
+    fn test_actor_and_article_documents() {
+        let actor = test_federation().actor_document();
+        assert_eq!(actor["inbox"], "https://miniex.blog/ap/inbox");
+        assert_eq!(
+            actor["publicKey"]["publicKeyPem"],
+            signature::test_key().public_pem()
+        );
+
+        let outbox = Federation::outbox(&[sample_post("hello")]);
+        let article = &outbox["orderedItems"][0]["object"];
+        assert_eq!(outbox["totalItems"], 1);
+        assert_eq!(article["id"], "https://miniex.blog/ap/posts/hello");
+        assert_eq!(article["url"], "https://miniex.blog/post/hello");
+        assert_eq!(article["published"], "2024-01-01T00:00:00+09:00");
+        assert_eq!(article["tag"][0]["name"], "#rust");
+    }
+
+    #[test]
+    fn test_post_slug_from_object() {
+        for id in [
+            "https://miniex.blog/ap/posts/hello",
+            "https://miniex.blog/post/hello/",
+        ] {
+            assert_eq!(post_slug_from_object(id), Some("hello".to_string()));
+        }
+        assert_eq!(
+            post_slug_from_object("https://evil.example/post/hello"),
+            None
+        );
+        assert_eq!(post_slug_from_object("https://miniex.blog/guestbook"), None);
+    }
+
+    #[tokio::test]
+    async fn test_follow_and_undo() {
+        let inbox: Inbox = Default::default();
+        let base = remote_server(inbox.clone()).await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let alice = format!("{}/users/alice", base);
+
+        let follow = json!({
+            "id": format!("{}/follows/1", base),
+            "type": "Follow",
+            "actor": alice,
+            "object": actor_id(),
+        });
+        deliver_to_us(&federation, &db, &base, &follow)
+            .await
+            .unwrap();
+        assert_eq!(db.count_followers().await.unwrap(), 1);
+        assert_eq!(
+            db.get_follower_inboxes().await.unwrap(),
+            vec![format!("{}/inbox", base)]
+        );
+        {
+            let received = inbox.lock().unwrap();
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0]["type"], "Accept");
+            assert_eq!(received[0]["object"]["id"], follow["id"]);
+        }
+
+        let undo = json!({
+            "id": format!("{}/undo/1", base),
+            "type": "Undo",
+            "actor": alice,
+            "object": follow,
+        });
+        deliver_to_us(&federation, &db, &base, &undo).await.unwrap();
+        assert_eq!(db.count_followers().await.unwrap(), 0);
+    }
+
+    /// A client for a network that is down.
+    struct Offline;
+
+    #[async_trait::async_trait]
+    impl HttpClient for Offline {
+        async fn execute(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+            anyhow::bail!("offline: {}", request.url)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signing_keys_are_cached() {
+        let base = remote_server(Default::default()).await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let follow = json!({
+            "id": format!("{}/follows/1", base),
+            "type": "Follow",
+            "actor": format!("{}/users/alice", base),
+            "object": actor_id(),
+        });
+        deliver_to_us(&federation, &db, &base, &follow)
+            .await
+            .unwrap();
+
+        // The key fetched for the Follow verifies the Undo without a request
+        let undo = json!({
+            "id": format!("{}/undo/1", base),
+            "type": "Undo",
+            "actor": format!("{}/users/alice", base),
+            "object": follow,
+        });
+        let (headers, body) = signed_delivery(&base, &undo);
+        let request = InboxRequest {
+            method: "POST",
+            path_and_query: INBOX_PATH,
+            headers: &headers,
+            body: &body,
+        };
+        federation
+            .receive(&db, &Offline, &HashSet::new(), &request)
+            .await
+            .unwrap();
+        assert_eq!(db.count_followers().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_signatures() {
+        let base = remote_server(Default::default()).await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let follow = json!({
+            "type": "Follow",
+            "actor": format!("{}/users/alice", base),
+            "object": actor_id(),
+        });
+
+        // Body swapped after signing
+        let (headers, _) = signed_delivery(&base, &follow);
+        let tampered = serde_json::to_vec(&json!({ "type": "Follow", "actor": "x" })).unwrap();
+        let request = InboxRequest {
+            method: "POST",
+            path_and_query: INBOX_PATH,
+            headers: &headers,
+            body: &tampered,
+        };
+        let err = federation
+            .receive(&db, &ReqwestClient::new(true), &HashSet::new(), &request)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InboxError::Unauthorized("Digest does not match the body")
+        );
+
+        // Signed for a different path
+        let (headers, body) = signed_delivery(&base, &follow);
+        let request = InboxRequest {
+            method: "POST",
+            path_and_query: "/elsewhere",
+            headers: &headers,
+            body: &body,
+        };
+        let err = federation
+            .receive(&db, &ReqwestClient::new(true), &HashSet::new(), &request)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InboxError::Unauthorized("signature verification failed")
+        );
+
+        // Actor that does not own the key
+        let spoofed = json!({
+            "type": "Follow",
+            "actor": "https://mastodon.example/users/bob",
+            "object": actor_id(),
+        });
+        let err = deliver_to_us(&federation, &db, &base, &spoofed)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InboxError::Unauthorized("activity actor does not match the signature")
+        );
+        assert_eq!(db.count_followers().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_replies_are_stored_and_deleted() {
+        let base = remote_server(Default::default()).await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let alice = format!("{}/users/alice", base);
+        let note_id = format!("{}/notes/1", base);
+
+        let create = json!({
+            "type": "Create",
+            "actor": alice,
+            "object": {
+                "id": note_id,
+                "type": "Note",
+                "attributedTo": alice,
+                "inReplyTo": "https://miniex.blog/ap/posts/hello",
+                "content": "<p>Nice <script>x</script>post</p>",
+                "published": "2024-02-01T10:00:00Z",
+            },
+        });
+        deliver_to_us(&federation, &db, &base, &create)
+            .await
+            .unwrap();
+
+        let replies = db.get_federated_replies_for_post("hello").await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].author_name, "@alice@127.0.0.1");
+        assert_eq!(replies[0].content_html, "<p>Nice post</p>");
+
+        let delete = json!({
+            "type": "Delete",
+            "actor": alice,
+            "object": { "id": note_id, "type": "Tombstone" },
+        });
+        deliver_to_us(&federation, &db, &base, &delete)
+            .await
+            .unwrap();
+        assert!(db
+            .get_federated_replies_for_post("hello")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_actor_links_must_be_http() {
+        let key_id = "https://social.example/users/alice#main-key";
+        let actor = |id: &str, inbox: &str, shared_inbox: &str| {
+            let doc = json!({
+                "id": id,
+                "type": "Person",
+                "inbox": inbox,
+                "endpoints": { "sharedInbox": shared_inbox },
+            });
+            RemoteActor::from_json(&doc, key_id)
+        };
+        let alice = "https://social.example/users/alice";
+        let inbox = "https://social.example/users/alice/inbox";
+
+        let ok = actor(alice, inbox, "https://social.example/inbox").unwrap();
+        assert_eq!(ok.id, alice);
+        assert_eq!(
+            ok.shared_inbox.as_deref(),
+            Some("https://social.example/inbox")
+        );
+        assert!(actor("javascript://social.example/%0Aalert(1)", inbox, "").is_none());
+        assert!(actor(alice, "javascript:alert(1)", "").is_none());
+        // A bad shared inbox is dropped in favour of the personal one
+        let no_shared = actor(alice, inbox, "javascript://social.example/inbox").unwrap();
+        assert_eq!(no_shared.shared_inbox, None);
+    }
+
+    #[test]
+    fn test_reply_url_must_be_http_on_actor_host() {
+        let actor = RemoteActor {
+            id: "https://social.example/users/alice".to_string(),
+            inbox: "https://social.example/users/alice/inbox".to_string(),
+            shared_inbox: None,
+            display_name: "Alice".to_string(),
+            public_key_pem: None,
+        };
+        let known_slugs = HashSet::from(["hello".to_string()]);
+        let reply_url = |url: Value| {
+            let note = json!({
+                "id": "https://social.example/notes/1",
+                "type": "Note",
+                "attributedTo": actor.id,
+                "inReplyTo": "https://miniex.blog/ap/posts/hello",
+                "url": url,
+                "content": "<p>Hi</p>",
+            });
+            reply_from_note(&note, &actor, &known_slugs).map(|reply| reply.url)
+        };
+
+        assert_eq!(
+            reply_url(json!("https://social.example/@alice/1")).as_deref(),
+            Some("https://social.example/@alice/1")
+        );
+        assert_eq!(
+            reply_url(json!({ "href": "https://social.example/@alice/2" })).as_deref(),
+            Some("https://social.example/@alice/2")
+        );
+        // Anything else falls back to the note id
+        for url in [
+            json!("javascript:alert(1)"),
+            json!("javascript://social.example/%0aalert(1)"),
+            json!("https://elsewhere.example/@alice/1"),
+            Value::Null,
+        ] {
+            assert_eq!(
+                reply_url(url).as_deref(),
+                Some("https://social.example/notes/1")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_skips_archive_then_delivers_new_posts() {
+        let inbox: Inbox = Default::default();
+        let base = remote_server(inbox.clone()).await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let http = ReqwestClient::new(true);
+        db.upsert_follower(&Follower {
+            actor_id: format!("{}/users/alice", base),
+            inbox: format!("{}/users/alice/inbox", base),
+            shared_inbox: None,
+        })
+        .await
+        .unwrap();
+
+        let mut posts = vec![sample_post("old")];
+        federation.publish_new_posts(&db, &http, &posts).await;
+        assert!(inbox.lock().unwrap().is_empty());
+
+        posts.push(sample_post("new"));
+        federation.publish_new_posts(&db, &http, &posts).await;
+        federation.publish_new_posts(&db, &http, &posts).await;
+
+        let received = inbox.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["type"], "Create");
+        assert_eq!(
+            received[0]["object"]["id"],
+            "https://miniex.blog/ap/posts/new"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_per_inbox() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let base = stand_in_server(|_| {
+            Router::new().route(
+                "/inbox",
+                post(move || async move {
+                    // Down for the first attempt only
+                    if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                        axum::http::StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        axum::http::StatusCode::ACCEPTED
+                    }
+                }),
+            )
+        })
+        .await;
+        let db = test_db().await;
+        let federation = test_federation();
+        let http = ReqwestClient::new(true);
+        db.upsert_follower(&Follower {
+            actor_id: format!("{}/users/bob", base),
+            inbox: format!("{}/inbox", base),
+            shared_inbox: None,
+        })
+        .await
+        .unwrap();
+
+        let mut posts = vec![sample_post("old")];
+        federation.publish_new_posts(&db, &http, &posts).await;
+        posts.push(sample_post("new"));
+        federation.publish_new_posts(&db, &http, &posts).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Not due again until the backoff has elapsed
+        let now = Utc::now().timestamp();
+        assert!(db.get_due_deliveries(now, 10).await.unwrap().is_empty());
+        let later = now + 60 * 60;
+        let due = db.get_due_deliveries(later, 10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].attempts, 1);
+
+        federation.deliver_due(&db, &http, later).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(db.get_due_deliveries(later, 10).await.unwrap().is_empty());
+    }
+}
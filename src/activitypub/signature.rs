@@ -0,0 +1,291 @@
+//! HTTP Signatures (draft-cavage-http-signatures-12) as used by Mastodon:
+//! `rsa-sha256` over `(request-target) host date digest`.
+
+use crate::http::HttpRequest;
+use argon2::password_hash::rand_core::OsRng;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use rsa::{
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding},
+    signature::{SignatureEncoding, Signer, Verifier},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// Signed requests older or newer than this are rejected.
+pub const MAX_CLOCK_SKEW_SECS: i64 = 12 * 60 * 60;
+
+/// The blog actor's signing key.
+pub struct ActorKey {
+    private: RsaPrivateKey,
+    public_pem: String,
+}
+
+impl ActorKey {
+    pub fn generate(bits: usize) -> anyhow::Result<Self> {
+        Self::from_private(RsaPrivateKey::new(&mut OsRng, bits)?)
+    }
+
+    pub fn from_pem(private_pem: &str) -> anyhow::Result<Self> {
+        Self::from_private(RsaPrivateKey::from_pkcs8_pem(private_pem)?)
+    }
+
+    fn from_private(private: RsaPrivateKey) -> anyhow::Result<Self> {
+        let public_pem = RsaPublicKey::from(&private).to_public_key_pem(LineEnding::LF)?;
+        Ok(ActorKey {
+            private,
+            public_pem,
+        })
+    }
+
+    pub fn private_pem(&self) -> anyhow::Result<String> {
+        Ok(self.private.to_pkcs8_pem(LineEnding::LF)?.to_string())
+    }
+
+    pub fn public_pem(&self) -> &str {
+        &self.public_pem
+    }
+
+    /// Add `Host`, `Date`, `Digest` (when there is a body) and `Signature`
+    /// headers to an outgoing request.
+    pub fn sign_request(
+        &self,
+        key_id: &str,
+        request: HttpRequest,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<HttpRequest> {
+        let url = url::Url::parse(&request.url)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let mut request = request
+            .header("Host", &host)
+            .header("Date", &http_date(now));
+        let mut names = vec!["(request-target)", "host", "date"];
+        if let Some(body) = &request.body {
+            let digest = digest_header(body);
+            request = request.header("Digest", &digest);
+            names.push("digest");
+        }
+
+        let names: Vec<String> = names.into_iter().map(str::to_string).collect();
+        let method = request.method.as_str().to_ascii_lowercase();
+        let signing_string = signing_string(&method, &target, &names, |name| {
+            request
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        })
+        .ok_or_else(|| anyhow::anyhow!("missing header for signature"))?;
+
+        let signature = SigningKey::<Sha256>::new(self.private.clone())
+            .sign(signing_string.as_bytes())
+            .to_bytes();
+        let value = format!(
+            r#"keyId="{}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+            key_id,
+            names.join(" "),
+            BASE64.encode(signature)
+        );
+        Ok(request.header("Signature", &value))
+    }
+}
+
+/// A parsed `Signature` request header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    /// Covered headers, lowercase. Defaults to `date` per the draft.
+    pub headers: Vec<String>,
+    pub signature: Vec<u8>,
+}
+
+impl SignatureHeader {
+    pub fn parse(value: &str) -> Option<Self> {
+        static PARAM_RE: OnceLock<Regex> = OnceLock::new();
+        let re = PARAM_RE.get_or_init(|| Regex::new(r#"([A-Za-z]+)\s*=\s*"([^"]*)""#).unwrap());
+
+        let (mut key_id, mut algorithm, mut headers, mut signature) = (None, None, None, None);
+        for cap in re.captures_iter(value) {
+            let v = cap[2].to_string();
+            match &cap[1] {
+                "keyId" => key_id = Some(v),
+                "algorithm" => algorithm = Some(v.to_ascii_lowercase()),
+                "headers" => headers = Some(v),
+                "signature" => signature = BASE64.decode(v.as_bytes()).ok(),
+                _ => {}
+            }
+        }
+
+        Some(SignatureHeader {
+            key_id: key_id?,
+            algorithm,
+            headers: headers
+                .unwrap_or_else(|| "date".to_string())
+                .split_ascii_whitespace()
+                .map(str::to_ascii_lowercase)
+                .collect(),
+            signature: signature?,
+        })
+    }
+
+    pub fn covers(&self, header: &str) -> bool {
+        self.headers.iter().any(|h| h == header)
+    }
+}
+
+/// Build the string that is signed: one `name: value` line per covered header.
+pub fn signing_string(
+    method: &str,
+    path_and_query: &str,
+    headers: &[String],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let lines = headers
+        .iter()
+        .map(|name| match name.as_str() {
+            "(request-target)" => Some(format!(
+                "(request-target): {} {}",
+                method.to_ascii_lowercase(),
+                path_and_query
+            )),
+            _ => lookup(name).map(|value| format!("{}: {}", name, value.trim())),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(lines.join("\n"))
+}
+
+/// Check an `rsa-sha256` signature against a PEM public key (SPKI or PKCS#1).
+pub fn verify(public_pem: &str, signing_string: &str, signature: &[u8]) -> bool {
+    let key = match RsaPublicKey::from_public_key_pem(public_pem.trim())
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(public_pem.trim()))
+    {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let Ok(signature) = Signature::try_from(signature) else {
+        return false;
+    };
+    VerifyingKey::<Sha256>::new(key)
+        .verify(signing_string.as_bytes(), &signature)
+        .is_ok()
+}
+
+pub fn digest_header(body: &[u8]) -> String {
+    format!("SHA-256={}", BASE64.encode(Sha256::digest(body)))
+}
+
+/// Whether a `Digest` header value matches the body. Only SHA-256 is accepted.
+pub fn digest_matches(header: &str, body: &[u8]) -> bool {
+    let expected = BASE64.encode(Sha256::digest(body));
+    header.split(',').any(|digest| {
+        digest
+            .trim()
+            .split_once('=')
+            .is_some_and(|(algorithm, value)| {
+                algorithm.eq_ignore_ascii_case("SHA-256") && value == expected
+            })
+    })
+}
+
+pub fn http_date(now: DateTime<Utc>) -> String {
+    now.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether a `Date` header is within the allowed clock skew of `now`.
+pub fn date_is_fresh(header: &str, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc2822(header.trim())
+        .map(|date| (now - date.with_timezone(&Utc)).num_seconds().abs() <= MAX_CLOCK_SKEW_SECS)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+pub(crate) fn test_key() -> &'static ActorKey {
+    static KEY: OnceLock<ActorKey> = OnceLock::new();
+    KEY.get_or_init(|| ActorKey::generate(1024).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_lookup(request: &HttpRequest) -> impl Fn(&str) -> Option<String> + '_ {
+        |name| {
+            request
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify_roundtrip() {
+        let key = test_key();
+        let request = HttpRequest::post_form("https://remote.example/inbox?x=1", &[("a", "b")]);
+        let signed = key
+            .sign_request("https://miniex.blog/ap/actor#main-key", request, Utc::now())
+            .unwrap();
+
+        let sig_value = header_lookup(&signed)("signature").unwrap();
+        let sig = SignatureHeader::parse(&sig_value).unwrap();
+        assert_eq!(sig.key_id, "https://miniex.blog/ap/actor#main-key");
+        assert_eq!(
+            sig.headers,
+            vec!["(request-target)", "host", "date", "digest"]
+        );
+
+        let text =
+            signing_string("POST", "/inbox?x=1", &sig.headers, header_lookup(&signed)).unwrap();
+        assert!(text.starts_with("(request-target): post /inbox?x=1\nhost: remote.example\n"));
+        assert!(verify(key.public_pem(), &text, &sig.signature));
+        assert!(!verify(
+            key.public_pem(),
+            &text.replace("post", "get"),
+            &sig.signature
+        ));
+    }
+
+    #[test]
+    fn test_pem_roundtrip() {
+        let key = test_key();
+        let restored = ActorKey::from_pem(&key.private_pem().unwrap()).unwrap();
+        assert_eq!(restored.public_pem(), key.public_pem());
+    }
+
+    #[test]
+    fn test_parse_signature_header_defaults() {
+        let sig = SignatureHeader::parse(r#"keyId="k",signature="AAEC""#).unwrap();
+        assert_eq!(sig.headers, vec!["date"]);
+        assert_eq!(sig.signature, vec![0, 1, 2]);
+        assert!(SignatureHeader::parse(r#"keyId="k""#).is_none());
+    }
+
+    #[test]
+    fn test_digest_and_date() {
+        let body = b"{\"type\":\"Follow\"}";
+        assert!(digest_matches(&digest_header(body), body));
+        assert!(!digest_matches(&digest_header(b"other"), body));
+        assert!(!digest_matches("MD5=abc", body));
+
+        let now = Utc::now();
+        assert!(date_is_fresh(&http_date(now), now));
+        assert!(!date_is_fresh(
+            &http_date(now - chrono::Duration::days(1)),
+            now
+        ));
+        assert!(!date_is_fresh("yesterday", now));
+    }
+}
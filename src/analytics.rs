@@ -0,0 +1,370 @@
+//! Per-day post view and site visitor analytics.
+//!
+//! Every counted hit on a post lands in `post_views_daily` under the post's
+//! slug and the calendar day in the configured site timezone. Unique
+//! visitors are counted once per post and day by their [`Visitor`] key: a
+//! keyed hash of the cookie ID, or of the day, address and user agent
+//! without a cookie, so no raw IP is stored. Requests classified as
+//! automated by [`crate::bots`] are tallied apart from views.
+//!
+//! Site visits are dated the same way. Per-visitor rows are kept for
+//! `VISITOR_RETENTION_DAYS` and then compacted into daily totals by
+//! [`spawn_visitor_compaction`], which also drops post view rows once they
+//! are past their day and the dedup window.
+
+use crate::{
+    config::Config,
+    db::{DailyViews, DailyVisitors, Database},
+    visitor::Visitor,
+    SharedState,
+};
+use axum::http::{HeaderMap, Method};
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Range shown when a query gives no dates.
+pub const DEFAULT_RANGE_DAYS: i64 = 30;
+/// Longest range a single query may ask for.
+pub const MAX_RANGE_DAYS: i64 = 366;
+
+/// An inclusive range of days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// Parse optional `from`/`to` query values. A missing `to` is `today`; a
+    /// missing `from` is the start of the default window ending at `to`.
+    pub fn parse(from: Option<&str>, to: Option<&str>, today: NaiveDate) -> Result<Self, String> {
+        let parse = |name: &str, value: &str| {
+            NaiveDate::parse_from_str(value, DATE_FORMAT)
+                .map_err(|_| format!("`{}` must be a date like 2024-01-31", name))
+        };
+        let to = match to.filter(|s| !s.is_empty()) {
+            Some(to) => parse("to", to)?,
+            None => today,
+        };
+        let from = match from.filter(|s| !s.is_empty()) {
+            Some(from) => parse("from", from)?,
+            None => to - Duration::days(DEFAULT_RANGE_DAYS - 1),
+        };
+        if from > to {
+            return Err("`from` must not be after `to`".to_string());
+        }
+        if (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(format!("ranges are limited to {} days", MAX_RANGE_DAYS));
+        }
+        Ok(DateRange { from, to })
+    }
+
+    /// First day, as stored in the database.
+    pub fn start(&self) -> String {
+        self.from.format(DATE_FORMAT).to_string()
+    }
+
+    /// Last day, as stored in the database.
+    pub fn end(&self) -> String {
+        self.to.format(DATE_FORMAT).to_string()
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let to = self.to;
+        self.from.iter_days().take_while(move |d| *d <= to)
+    }
+}
+
+/// A per-day row that [`fill_days`] can zero-fill.
+pub trait DailyRow {
+    fn date(&self) -> &str;
+    fn empty(date: String) -> Self;
+}
+
+impl DailyRow for DailyViews {
+    fn date(&self) -> &str {
+        &self.date
+    }
+
+    fn empty(date: String) -> Self {
+        DailyViews {
+            date,
+            views: 0,
+            visitors: 0,
+        }
+    }
+}
+
+impl DailyRow for DailyVisitors {
+    fn date(&self) -> &str {
+        &self.date
+    }
+
+    fn empty(date: String) -> Self {
+        DailyVisitors { date, visitors: 0 }
+    }
+}
+
+/// One entry per day in `range`, with zeros for days that had no rows.
+pub fn fill_days<T: DailyRow>(range: &DateRange, rows: Vec<T>) -> Vec<T> {
+    let mut rows = rows.into_iter().peekable();
+    range
+        .days()
+        .map(|day| {
+            let date = day.format(DATE_FORMAT).to_string();
+            while rows.peek().is_some_and(|r| r.date() < date.as_str()) {
+                rows.next();
+            }
+            match rows.next_if(|r| r.date() == date) {
+                Some(row) => row,
+                None => T::empty(date),
+            }
+        })
+        .collect()
+}
+
+/// The current calendar day in `tz`.
+pub fn today_in(tz: Tz) -> NaiveDate {
+    Utc::now().with_timezone(&tz).date_naive()
+}
+
+/// Compact old per-visitor rows at startup and once a day after that.
+pub fn spawn_visitor_compaction(db: Database, config: &Config) {
+    let tz = config.site_timezone;
+    let retention = Duration::days(config.visitor_retention_days.max(1));
+    let dedup_window = config.view_dedup_window;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60 * 24));
+        loop {
+            interval.tick().await;
+            let before = (today_in(tz) - retention).format(DATE_FORMAT).to_string();
+            match db.compact_visitors(&before).await {
+                Ok(0) => {}
+                Ok(rows) => tracing::info!("Compacted {} visitor rows before {}", rows, before),
+                Err(e) => tracing::error!("compact_visitors error: {}", e),
+            }
+
+            let today = today_in(tz).format(DATE_FORMAT).to_string();
+            match db
+                .prune_view_visitors(&today, Utc::now() - dedup_window)
+                .await
+            {
+                Ok(0) => {}
+                Ok(rows) => tracing::info!("Pruned {} post view visitor rows", rows),
+                Err(e) => tracing::error!("prune_view_visitors error: {}", e),
+            }
+        }
+    });
+}
+
+/// Count a request for a post as a view, or tally it as a bot hit. Returns
+/// the post's lifetime view count.
+pub async fn record_post_view(
+    state: &SharedState,
+    slug: &str,
+    method: &Method,
+    headers: &HeaderMap,
+    visitor: &Visitor,
+) -> Result<u32, sqlx::Error> {
+    let now = Utc::now();
+    let today = today_in(state.config.site_timezone)
+        .format(DATE_FORMAT)
+        .to_string();
+
+    if let Some(reason) = state.bots.classify(method, headers) {
+        state
+            .db
+            .record_bot_hit(slug, &today, reason.as_str())
+            .await?;
+        let counts = state.db.get_view_counts(&[slug.to_string()]).await?;
+        return Ok(counts.get(slug).copied().unwrap_or(0));
+    }
+
+    let (_, total) = state
+        .db
+        .record_view(
+            slug,
+            &today,
+            &visitor.key,
+            now,
+            state.config.view_dedup_window,
+        )
+        .await?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, DATE_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_date_range_parse() {
+        let today = day("2024-03-31");
+        let range = DateRange::parse(None, None, today).unwrap();
+        assert_eq!(range.from, day("2024-03-02"));
+        assert_eq!(range.to, today);
+        assert_eq!(range.days().count(), 30);
+
+        let range = DateRange::parse(Some("2024-01-01"), Some("2024-01-07"), today).unwrap();
+        assert_eq!(range.days().count(), 7);
+
+        assert!(DateRange::parse(Some("2024-02-01"), Some("2024-01-01"), today).is_err());
+        assert!(DateRange::parse(Some("yesterday"), None, today).is_err());
+        assert!(DateRange::parse(Some("2022-01-01"), None, today).is_err());
+    }
+
+    #[test]
+    fn test_fill_days_zero_fills_gaps() {
+        let range =
+            DateRange::parse(Some("2024-01-01"), Some("2024-01-04"), day("2024-01-04")).unwrap();
+        let rows = vec![
+            DailyViews {
+                date: "2024-01-02".to_string(),
+                views: 5,
+                visitors: 3,
+            },
+            DailyViews {
+                date: "2024-01-04".to_string(),
+                views: 1,
+                visitors: 1,
+            },
+        ];
+        let filled = fill_days(&range, rows);
+        let views: Vec<u32> = filled.iter().map(|d| d.views).collect();
+        assert_eq!(views, vec![0, 5, 0, 1]);
+        assert_eq!(filled[0].date, "2024-01-01");
+    }
+
+    #[tokio::test]
+    async fn test_daily_views_and_top_posts() {
+        let db = crate::db::test_db().await;
+
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let window = Duration::minutes(30);
+        let view = |slug: &'static str, time: &'static str, visitor: &'static str| {
+            let db = &db;
+            async move {
+                db.record_view(slug, &time[..10], visitor, at(time), window)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(view("a", "2024-01-01T09:00:00Z", "v1").await, (true, 1));
+        // A reload within the window is not counted again.
+        assert_eq!(view("a", "2024-01-01T09:10:00Z", "v1").await, (false, 1));
+        assert_eq!(view("a", "2024-01-01T09:45:00Z", "v1").await, (true, 2));
+        view("a", "2024-01-02T09:00:00Z", "v1").await;
+        view("b", "2024-01-02T09:00:00Z", "v2").await;
+        assert_eq!(view("a", "2024-01-02T09:00:00Z", "v3").await, (true, 4));
+
+        let a = db
+            .get_daily_views(Some("a"), "2024-01-01", "2024-01-02")
+            .await
+            .unwrap();
+        assert_eq!((a[0].views, a[0].visitors), (2, 1));
+        assert_eq!((a[1].views, a[1].visitors), (2, 2));
+
+        let site = db
+            .get_daily_views(None, "2024-01-02", "2024-01-02")
+            .await
+            .unwrap();
+        assert_eq!((site[0].views, site[0].visitors), (3, 3));
+
+        let top = db
+            .get_top_posts("2024-01-02", "2024-01-02", 1)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].slug.as_str(), top[0].views), ("a", 2));
+
+        let totals = db
+            .get_view_counts(&["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(totals["a"], 4);
+        assert_eq!(totals["b"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_view_visitor_pruning() {
+        let db = crate::db::test_db().await;
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let window = Duration::minutes(30);
+        for (visitor, time) in [
+            ("v1", "2024-01-01T09:00:00Z"),
+            ("v2", "2024-01-01T23:50:00Z"),
+            ("v3", "2024-01-02T00:05:00Z"),
+        ] {
+            db.record_view("a", &time[..10], visitor, at(time), window)
+                .await
+                .unwrap();
+        }
+
+        // v2 is still inside the window and v3 is from today.
+        let now = at("2024-01-02T00:10:00Z");
+        assert_eq!(
+            db.prune_view_visitors("2024-01-02", now - window)
+                .await
+                .unwrap(),
+            1
+        );
+        // A repeat view from v2 is still deduplicated.
+        assert_eq!(
+            db.record_view("a", "2024-01-01", "v2", now, window)
+                .await
+                .unwrap(),
+            (false, 3)
+        );
+        let now = at("2024-01-02T01:00:00Z");
+        assert_eq!(
+            db.prune_view_visitors("2024-01-02", now - window)
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_visitor_compaction_keeps_totals() {
+        let db = crate::db::test_db().await;
+
+        for (key, date) in [
+            ("v1", "2024-01-01"),
+            ("v2", "2024-01-01"),
+            ("v1", "2024-01-03"),
+            ("v3", "2024-01-03"),
+            ("v4", "not a date"),
+        ] {
+            db.record_visit(key, date).await.unwrap();
+        }
+        assert_eq!(db.get_visitor_stats("2024-01-01").await.unwrap(), (2, 4));
+
+        // Both 2024-01-01 rows plus the invalid one go.
+        assert_eq!(db.compact_visitors("2024-01-02").await.unwrap(), 3);
+        assert_eq!(db.get_visitor_stats("2024-01-01").await.unwrap(), (2, 3));
+        assert_eq!(db.get_visitor_stats("2024-01-03").await.unwrap(), (2, 3));
+
+        let range =
+            DateRange::parse(Some("2024-01-01"), Some("2024-01-03"), day("2024-01-03")).unwrap();
+        let days = fill_days(
+            &range,
+            db.get_daily_visitors(&range.start(), &range.end())
+                .await
+                .unwrap(),
+        );
+        let visitors: Vec<u32> = days.iter().map(|d| d.visitors).collect();
+        assert_eq!(visitors, vec![2, 0, 2]);
+
+        // Compacting again changes nothing.
+        assert_eq!(db.compact_visitors("2024-01-02").await.unwrap(), 0);
+        assert_eq!(db.get_visitor_stats("2024-01-03").await.unwrap(), (2, 3));
+    }
+}
@@ -0,0 +1,254 @@
+//! Content-hashed URLs for files under `assets/`.
+//!
+//! At startup every file is hashed, and pages link to
+//! `/assets/<name>.<hash>.<ext>` instead of the plain path. The hashed URL
+//! changes exactly when the file does, so it can be cached forever, while
+//! plain paths are only cached briefly. In debug builds the manifest is
+//! rebuilt whenever a file under `assets/` changes.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, Uri},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+pub const ASSETS_DIR: &str = "assets";
+const ASSETS_URL: &str = "/assets";
+/// Route label for `/assets`, which is a nested service without a
+/// `MatchedPath`.
+pub const ASSETS_ROUTE: &str = "/assets/*path";
+/// Hex digits of the content hash kept in URLs.
+const HASH_LEN: usize = 12;
+
+/// For hashed URLs whose hash is current.
+const CACHE_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// For plain paths, which keep their URL when the file changes.
+const CACHE_SHORT: &str = "public, max-age=3600";
+/// For hashed URLs from an older version of the file, e.g. a page cached
+/// across a deploy; the current file is served but not kept.
+const CACHE_STALE: &str = "no-cache";
+
+/// Content hash of each file, keyed by its path under `assets/`.
+#[derive(Debug, Default)]
+pub struct AssetManifest {
+    hashes: HashMap<String, String>,
+}
+
+impl AssetManifest {
+    pub fn scan(dir: &Path) -> Self {
+        let mut manifest = AssetManifest::default();
+        manifest.scan_dir(dir, "");
+        manifest
+    }
+
+    fn scan_dir(&mut self, dir: &Path, prefix: &str) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("Failed to read {}: {}", dir.display(), e);
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let path = entry.path();
+            let key = format!("{}{}", prefix, name);
+            if path.is_dir() {
+                self.scan_dir(&path, &format!("{}/", key));
+            } else if let Ok(bytes) = std::fs::read(&path) {
+                self.hashes.insert(key, hash(&bytes));
+            }
+        }
+    }
+
+    /// URL for `path` under `assets/`, with the content hash before the
+    /// extension. Unknown files and files without an extension keep their
+    /// plain path.
+    pub fn url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        let hashed = self.hashes.get(path).and_then(|hash| {
+            let (dir, file) = split_file(path);
+            let (stem, ext) = file.rsplit_once('.').filter(|(stem, _)| !stem.is_empty())?;
+            Some(format!("{}{}.{}.{}", dir, stem, hash, ext))
+        });
+        match hashed {
+            Some(hashed) => format!("{}/{}", ASSETS_URL, hashed),
+            None => {
+                if !self.hashes.contains_key(path) {
+                    tracing::warn!("Asset {:?} is not in the manifest", path);
+                }
+                format!("{}/{}", ASSETS_URL, path)
+            }
+        }
+    }
+
+    /// The plain path behind a hashed one, and whether the hash is still the
+    /// file's current one. `None` for paths that are not hashed.
+    pub fn unhash(&self, path: &str) -> Option<(String, bool)> {
+        let (dir, file) = split_file(path);
+        let (stem, ext) = file.rsplit_once('.')?;
+        let (base, hash) = stem.rsplit_once('.')?;
+        if base.is_empty() || !is_hash(hash) {
+            return None;
+        }
+        let plain = format!("{}{}.{}", dir, base, ext);
+        let current = self.hashes.get(&plain)? == hash;
+        Some((plain, current))
+    }
+}
+
+/// `("dir/", "file")` for `"dir/file"`.
+fn split_file(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))[..HASH_LEN].to_string()
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() == HASH_LEN && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn global() -> &'static RwLock<Arc<AssetManifest>> {
+    static MANIFEST: OnceLock<RwLock<Arc<AssetManifest>>> = OnceLock::new();
+    MANIFEST.get_or_init(|| RwLock::new(Arc::new(AssetManifest::scan(Path::new(ASSETS_DIR)))))
+}
+
+/// The process-wide manifest, built from `assets/` on first use.
+pub fn manifest() -> Arc<AssetManifest> {
+    global().read().unwrap().clone()
+}
+
+/// Hash `assets/` again, after files changed on disk.
+pub fn reload() {
+    let manifest = Arc::new(AssetManifest::scan(Path::new(ASSETS_DIR)));
+    *global().write().unwrap() = manifest;
+}
+
+/// URL for `path` under `assets/` in the process-wide manifest.
+pub fn url(path: &str) -> String {
+    manifest().url(path)
+}
+
+/// Map hashed `/assets` paths back to their files and set caching.
+pub async fn serve_hashed(mut req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let path = path
+        .strip_prefix(ASSETS_URL)
+        .unwrap_or(path)
+        .trim_start_matches('/');
+    let cache = match manifest().unhash(path) {
+        Some((plain, current)) => {
+            let uri = match req.uri().query() {
+                Some(query) => format!("{}/{}?{}", ASSETS_URL, plain, query),
+                None => format!("{}/{}", ASSETS_URL, plain),
+            };
+            if let Ok(uri) = uri.parse::<Uri>() {
+                *req.uri_mut() = uri;
+            }
+            if current {
+                CACHE_IMMUTABLE
+            } else {
+                CACHE_STALE
+            }
+        }
+        None => CACHE_SHORT,
+    };
+
+    let mut response = next.run(req).await;
+    if response.status().is_success() {
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static(cache));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_with(files: &[(&str, &[u8])]) -> AssetManifest {
+        AssetManifest {
+            hashes: files
+                .iter()
+                .map(|(path, bytes)| (path.to_string(), hash(bytes)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_url_round_trip() {
+        let manifest = manifest_with(&[
+            ("js/app.js", b"app"),
+            ("vendor/katex/katex.min.css", b"katex"),
+            ("robots", b"none"),
+        ]);
+
+        let url = manifest.url("js/app.js");
+        let app_hash = hash(b"app");
+        assert_eq!(url, format!("/assets/js/app.{}.js", app_hash));
+        let hashed = url.trim_start_matches("/assets/");
+        assert_eq!(
+            manifest.unhash(hashed),
+            Some(("js/app.js".to_string(), true))
+        );
+
+        let url = manifest.url("/vendor/katex/katex.min.css");
+        assert!(url.starts_with("/assets/vendor/katex/katex.min."));
+        assert_eq!(
+            manifest.unhash(url.trim_start_matches("/assets/")),
+            Some(("vendor/katex/katex.min.css".to_string(), true))
+        );
+
+        // No extension, or not in the manifest: plain path
+        assert_eq!(manifest.url("robots"), "/assets/robots");
+        assert_eq!(manifest.url("js/missing.js"), "/assets/js/missing.js");
+    }
+
+    #[test]
+    fn test_unhash() {
+        let manifest = manifest_with(&[("js/app.js", b"app")]);
+        // An older hash still maps to the file, marked stale
+        assert_eq!(
+            manifest.unhash("js/app.0123456789ab.js"),
+            Some(("js/app.js".to_string(), false))
+        );
+        assert_eq!(manifest.unhash("js/app.js"), None);
+        assert_eq!(manifest.unhash("js/other.0123456789ab.js"), None);
+        assert_eq!(manifest.unhash("js/app.0123456789AB.js"), None);
+        assert_eq!(manifest.unhash("js/.0123456789ab.js"), None);
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("blog-assets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("styles")).unwrap();
+        std::fs::write(dir.join("styles/site.css"), b"body{}").unwrap();
+        std::fs::write(dir.join("logo.png"), b"png").unwrap();
+
+        let manifest = AssetManifest::scan(&dir);
+        assert_eq!(
+            manifest.url("styles/site.css"),
+            format!("/assets/styles/site.{}.css", hash(b"body{}"))
+        );
+        assert_eq!(
+            manifest.url("logo.png"),
+            format!("/assets/logo.{}.png", hash(b"png"))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{config::Config, SharedState};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::Duration;
+use sha2::{Digest, Sha256};
+
+pub const SESSION_COOKIE: &str = "admin_session";
+
+/// Check a login attempt against the configured admin password hash.
+pub fn verify_admin_password(config: &Config, password: &str) -> bool {
+    use argon2::password_hash::PasswordHash;
+    use argon2::{Argon2, PasswordVerifier};
+
+    let Some(stored_hash) = config.admin_password_hash.as_deref() else {
+        return false;
+    };
+    match PasswordHash::new(stored_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(e) => {
+            tracing::error!("ADMIN_PASSWORD_HASH is not a valid argon2 hash: {}", e);
+            false
+        }
+    }
+}
+
+/// Hash a password for use as `ADMIN_PASSWORD_HASH`.
+pub fn hash_admin_password(password: &str) -> String {
+    use argon2::password_hash::rand_core::OsRng;
+    use argon2::password_hash::SaltString;
+    use argon2::{Argon2, PasswordHasher};
+
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("password hashing failed")
+        .to_string()
+}
+
+/// Random 256-bit session token, hex encoded. Only its hash is stored.
+pub fn new_session_token() -> String {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Look up a cookie by name in the request headers.
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (k, v) = pair.trim().split_once('=')?;
+            (k == name).then_some(v.trim())
+        })
+}
+
+pub fn session_cookie(token: &str, ttl: Duration) -> String {
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; Secure; SameSite=Strict",
+        SESSION_COOKIE,
+        token,
+        ttl.num_seconds()
+    )
+}
+
+pub fn clear_session_cookie() -> String {
+    format!(
+        "{}=; Path=/; Max-Age=0; HttpOnly; Secure; SameSite=Strict",
+        SESSION_COOKIE
+    )
+}
+
+/// Whether the request carries a live admin session.
+pub async fn is_admin(state: &SharedState, headers: &HeaderMap) -> bool {
+    let Some(token) = cookie_value(headers, SESSION_COOKIE) else {
+        return false;
+    };
+    match state.db.is_admin_session_valid(&hash_token(token)).await {
+        Ok(valid) => valid,
+        Err(e) => {
+            tracing::error!("admin session lookup failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Middleware guarding the admin area; anonymous requests go to the login page.
+pub async fn require_admin(State(state): State<SharedState>, req: Request, next: Next) -> Response {
+    if is_admin(&state, req.headers()).await {
+        next.run(req).await
+    } else {
+        Redirect::to("/admin/login").into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_cookie_value_finds_named_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("lang=ko; admin_session=abc123; other=x"),
+        );
+        assert_eq!(cookie_value(&headers, SESSION_COOKIE), Some("abc123"));
+        assert_eq!(cookie_value(&headers, "lang"), Some("ko"));
+        assert_eq!(cookie_value(&headers, "missing"), None);
+    }
+
+    #[test]
+    fn test_cookie_value_ignores_prefix_matches() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("xadmin_session=evil"),
+        );
+        assert_eq!(cookie_value(&headers, SESSION_COOKIE), None);
+    }
+
+    #[test]
+    fn test_verify_admin_password() {
+        let config = Config {
+            admin_password_hash: Some(hash_admin_password("hunter2")),
+            ..Config::default()
+        };
+        assert!(verify_admin_password(&config, "hunter2"));
+        assert!(!verify_admin_password(&config, "hunter3"));
+    }
+
+    #[test]
+    fn test_verify_admin_password_disabled_without_hash() {
+        assert!(!verify_admin_password(&Config::default(), ""));
+    }
+
+    #[test]
+    fn test_session_tokens_are_unique() {
+        let a = new_session_token();
+        let b = new_session_token();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        assert_ne!(hash_token(&a), a);
+    }
+}
@@ -0,0 +1,220 @@
+//! Tell automated requests apart from readers before a view is counted.
+//!
+//! Crawlers, link previewers, feed readers and uptime checks are recognised
+//! from their User-Agent, and browser prefetches and `HEAD` requests from the
+//! request itself. Browsers always send `Accept-Language`, so a request
+//! without one is treated as automated as well.
+
+use axum::http::{header, HeaderMap, Method};
+use regex::RegexSet;
+
+const BUNDLED_PATTERNS: &str = include_str!("bots/user-agents.txt");
+
+/// Why a request was not counted as a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotReason {
+    Head,
+    Prefetch,
+    MissingUserAgent,
+    UserAgent,
+    MissingAcceptLanguage,
+}
+
+impl BotReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BotReason::Head => "head",
+            BotReason::Prefetch => "prefetch",
+            BotReason::MissingUserAgent => "no-user-agent",
+            BotReason::UserAgent => "user-agent",
+            BotReason::MissingAcceptLanguage => "no-accept-language",
+        }
+    }
+}
+
+/// User-Agent patterns as one case-insensitive set.
+pub struct BotFilter {
+    patterns: RegexSet,
+}
+
+impl BotFilter {
+    /// Patterns from a text file (one regex per line, `#` comments) or from
+    /// a crawler-user-agents style JSON array of `{"pattern": ...}` objects.
+    /// Invalid patterns are skipped.
+    pub fn parse(source: &str) -> Self {
+        let lines: Vec<String> = match serde_json::from_str::<Vec<serde_json::Value>>(source) {
+            Ok(entries) => entries
+                .iter()
+                .filter_map(|e| e["pattern"].as_str().map(str::to_string))
+                .collect(),
+            Err(_) => source
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        };
+        let valid: Vec<String> = lines
+            .into_iter()
+            .filter_map(|p| {
+                let pattern = format!("(?i){}", p);
+                match regex::Regex::new(&pattern) {
+                    Ok(_) => Some(pattern),
+                    Err(e) => {
+                        tracing::warn!("Skipping invalid bot pattern {:?}: {}", p, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        BotFilter {
+            patterns: RegexSet::new(valid).expect("patterns were validated individually"),
+        }
+    }
+
+    /// The list at `path` when given and readable, else the bundled list.
+    pub fn load(path: Option<&str>) -> Self {
+        if let Some(path) = path {
+            match std::fs::read_to_string(path) {
+                Ok(source) => {
+                    let filter = Self::parse(&source);
+                    tracing::info!(
+                        "Loaded {} bot User-Agent patterns from {}",
+                        filter.patterns.len(),
+                        path
+                    );
+                    return filter;
+                }
+                Err(e) => tracing::warn!("Could not read bot User-Agent list {}: {}", path, e),
+            }
+        }
+        Self::parse(BUNDLED_PATTERNS)
+    }
+
+    pub fn is_bot_user_agent(&self, user_agent: &str) -> bool {
+        self.patterns.is_match(user_agent)
+    }
+
+    /// `Some(reason)` when the request should not count as a view.
+    pub fn classify(&self, method: &Method, headers: &HeaderMap) -> Option<BotReason> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        if method == Method::HEAD {
+            return Some(BotReason::Head);
+        }
+        let prefetch = ["purpose", "sec-purpose", "x-purpose", "x-moz"]
+            .iter()
+            .filter_map(|name| header(name))
+            .any(|v| {
+                let v = v.to_ascii_lowercase();
+                v.contains("prefetch") || v.contains("preview")
+            });
+        if prefetch {
+            return Some(BotReason::Prefetch);
+        }
+        match headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+        {
+            None | Some("") => return Some(BotReason::MissingUserAgent),
+            Some(ua) if self.is_bot_user_agent(ua) => return Some(BotReason::UserAgent),
+            Some(_) => {}
+        }
+        if !headers.contains_key(header::ACCEPT_LANGUAGE) {
+            return Some(BotReason::MissingAcceptLanguage);
+        }
+        None
+    }
+}
+
+impl Default for BotFilter {
+    fn default() -> Self {
+        Self::parse(BUNDLED_PATTERNS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const CHROME: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+
+    fn browser_headers(user_agent: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static(user_agent));
+        headers.insert(
+            header::ACCEPT_LANGUAGE,
+            HeaderValue::from_static("ko-KR,ko;q=0.9"),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_bundled_list_matches_crawlers_not_browsers() {
+        let filter = BotFilter::default();
+        for ua in [
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Mozilla/5.0 (compatible; Yeti/1.1; +http://naver.me/spd)",
+            "facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)",
+            "Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; GPTBot/1.2; +https://openai.com/gptbot)",
+            "curl/8.5.0",
+            "Go-http-client/1.1",
+            "Mozilla/5.0 (compatible; UptimeRobot/2.0; http://www.uptimerobot.com/)",
+        ] {
+            assert!(filter.is_bot_user_agent(ua), "{}", ua);
+        }
+        for ua in [
+            CHROME,
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+            "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+        ] {
+            assert!(!filter.is_bot_user_agent(ua), "{}", ua);
+        }
+    }
+
+    #[test]
+    fn test_classify_request() {
+        let filter = BotFilter::default();
+        assert_eq!(
+            filter.classify(&Method::GET, &browser_headers(CHROME)),
+            None
+        );
+        assert_eq!(
+            filter.classify(&Method::HEAD, &browser_headers(CHROME)),
+            Some(BotReason::Head)
+        );
+
+        let mut prefetch = browser_headers(CHROME);
+        prefetch.insert(
+            "sec-purpose",
+            HeaderValue::from_static("prefetch;prerender"),
+        );
+        assert_eq!(
+            filter.classify(&Method::GET, &prefetch),
+            Some(BotReason::Prefetch)
+        );
+
+        let mut no_lang = browser_headers(CHROME);
+        no_lang.remove(header::ACCEPT_LANGUAGE);
+        assert_eq!(
+            filter.classify(&Method::GET, &no_lang),
+            Some(BotReason::MissingAcceptLanguage)
+        );
+
+        assert_eq!(
+            filter.classify(&Method::GET, &HeaderMap::new()),
+            Some(BotReason::MissingUserAgent)
+        );
+    }
+
+    #[test]
+    fn test_parse_crawler_user_agents_json() {
+        let filter = BotFilter::parse(
+            r#"[{"pattern": "Googlebot\\/", "instances": []}, {"pattern": "("}, {"pattern": "bingbot"}]"#,
+        );
+        assert!(filter.is_bot_user_agent("Googlebot/2.1"));
+        assert!(filter.is_bot_user_agent("BingBot"));
+        assert!(!filter.is_bot_user_agent(CHROME));
+    }
+}
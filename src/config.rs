@@ -0,0 +1,553 @@
+use crate::csp::parse_extra;
+use crate::ratelimit::{parse_networks, parse_policy, RateLimitPolicy};
+use crate::reactions::{parse_kinds, ReactionKind, DEFAULT_REACTIONS};
+use chrono::Duration;
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use std::net::SocketAddr;
+
+/// Runtime configuration, read once from environment variables at startup.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Argon2 hash of the admin password (`ADMIN_PASSWORD_HASH`).
+    /// The admin area refuses every login while this is unset.
+    pub admin_password_hash: Option<String>,
+    /// Lifetime of an admin session (`ADMIN_SESSION_HOURS`, default 12).
+    pub admin_session_ttl: Duration,
+    /// Score at which a submission is held as spam (`SPAM_THRESHOLD`, default 1.0).
+    pub spam_threshold: f64,
+    /// Submissions faster than this after loading the form look automated
+    /// (`SPAM_MIN_SUBMIT_SECS`, default 3).
+    pub spam_min_submit_secs: i64,
+    /// Links allowed before each extra one adds to the score (`SPAM_MAX_LINKS`, default 2).
+    pub spam_max_links: usize,
+    /// Word/regex blocklist file (`SPAM_BLOCKLIST`, default `data/spam-blocklist.txt`).
+    pub spam_blocklist_path: String,
+    /// Key for signing form tokens (`FORM_TOKEN_SECRET`). A random key is
+    /// generated per process when unset, which invalidates open forms on restart.
+    pub form_token_secret: Option<String>,
+    pub notify: NotifyConfig,
+    /// Send Webmentions for external links in recently published posts
+    /// after each content load (`WEBMENTION_SEND_ENABLED`, default false).
+    pub webmention_send_enabled: bool,
+    /// Only posts created within this many days are considered newly
+    /// published (`WEBMENTION_SEND_MAX_AGE_DAYS`, default 30).
+    pub webmention_send_max_age_days: i64,
+    pub activitypub: ActivityPubConfig,
+    /// Put each post's full HTML in feeds instead of only its description
+    /// (`FEED_FULL_CONTENT`, default true).
+    pub feed_full_content: bool,
+    pub indexnow: IndexNowConfig,
+    /// Where generated Open Graph images are cached (`OG_CACHE_DIR`, default `data/og`).
+    pub og_cache_dir: String,
+    /// Repeat views of a post by the same visitor within this window are
+    /// counted once (`VIEW_DEDUP_MINUTES`, default 30).
+    pub view_dedup_window: Duration,
+    /// User-Agent list that replaces the bundled bot patterns
+    /// (`BOT_USER_AGENTS`): one regex per line, or crawler-user-agents JSON.
+    pub bot_user_agents_path: Option<String>,
+    /// Key for signing visitor cookies and hashing visitor keys
+    /// (`VISITOR_SECRET`). A generated key kept in the database is used when unset.
+    pub visitor_secret: Option<String>,
+    /// Timezone whose calendar days post views and visitor statistics are
+    /// counted in
+    /// (`SITE_TIMEZONE`, an IANA name, default `UTC`).
+    pub site_timezone: Tz,
+    /// Per-visitor rows older than this many days are compacted into daily
+    /// totals (`VISITOR_RETENTION_DAYS`, default 90).
+    pub visitor_retention_days: i64,
+    /// Reaction kinds offered on posts (`REACTIONS`, `name:emoji` pairs
+    /// separated by commas, default `heart:❤️,thumbsup:👍,tada:🎉,thinking:🤔`).
+    pub reactions: Vec<ReactionKind>,
+    /// Commit the content was deployed from, reported by `/health/ready`
+    /// (`CONTENT_COMMIT`, e.g. `git rev-parse HEAD` in the deploy script).
+    pub content_commit: Option<String>,
+    pub rate_limit: RateLimitConfig,
+    pub password_lockout: PasswordLockoutConfig,
+    pub csp: CspConfig,
+    pub metrics: MetricsConfig,
+}
+
+/// Owner notifications for new comments and guestbook entries.
+#[derive(Clone, Debug)]
+pub struct NotifyConfig {
+    /// Send email through SMTP (`NOTIFY_EMAIL_ENABLED`).
+    pub email_enabled: bool,
+    /// SMTP relay host (`SMTP_HOST`).
+    pub smtp_host: Option<String>,
+    /// SMTP port (`SMTP_PORT`, default 587).
+    pub smtp_port: u16,
+    /// SMTP credentials (`SMTP_USERNAME`, `SMTP_PASSWORD`); optional for local relays.
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    /// Upgrade the connection with STARTTLS (`SMTP_STARTTLS`, default true).
+    /// Only disable this for a relay on the same host.
+    pub smtp_starttls: bool,
+    /// Sender and recipient addresses (`NOTIFY_EMAIL_FROM`, `NOTIFY_EMAIL_TO`).
+    pub email_from: Option<String>,
+    pub email_to: Option<String>,
+    /// POST a JSON payload to a webhook (`NOTIFY_WEBHOOK_ENABLED`).
+    pub webhook_enabled: bool,
+    /// Webhook target (`NOTIFY_WEBHOOK_URL`).
+    pub webhook_url: Option<String>,
+    /// Collect notifications for this long and send them as one digest
+    /// (`NOTIFY_DIGEST_MINUTES`, default 0 = send on the next poll).
+    pub digest_minutes: i64,
+    /// How often the queue is checked (`NOTIFY_POLL_SECS`, default 30).
+    pub poll_secs: u64,
+    /// Deliveries are given up after this many failures (`NOTIFY_MAX_ATTEMPTS`, default 8).
+    pub max_attempts: u32,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            email_enabled: false,
+            smtp_host: None,
+            smtp_port: 587,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_starttls: true,
+            email_from: None,
+            email_to: None,
+            webhook_enabled: false,
+            webhook_url: None,
+            digest_minutes: 0,
+            poll_secs: 30,
+            max_attempts: 8,
+        }
+    }
+}
+
+impl NotifyConfig {
+    fn from_env() -> Self {
+        let defaults = NotifyConfig::default();
+        NotifyConfig {
+            email_enabled: env_flag("NOTIFY_EMAIL_ENABLED").unwrap_or(defaults.email_enabled),
+            smtp_host: env_string("SMTP_HOST"),
+            smtp_port: env_parse("SMTP_PORT").unwrap_or(defaults.smtp_port),
+            smtp_username: env_string("SMTP_USERNAME"),
+            smtp_password: env_string("SMTP_PASSWORD"),
+            smtp_starttls: env_flag("SMTP_STARTTLS").unwrap_or(defaults.smtp_starttls),
+            email_from: env_string("NOTIFY_EMAIL_FROM"),
+            email_to: env_string("NOTIFY_EMAIL_TO"),
+            webhook_enabled: env_flag("NOTIFY_WEBHOOK_ENABLED").unwrap_or(defaults.webhook_enabled),
+            webhook_url: env_string("NOTIFY_WEBHOOK_URL"),
+            digest_minutes: env_parse("NOTIFY_DIGEST_MINUTES").unwrap_or(defaults.digest_minutes),
+            poll_secs: env_parse("NOTIFY_POLL_SECS").unwrap_or(defaults.poll_secs),
+            max_attempts: env_parse("NOTIFY_MAX_ATTEMPTS").unwrap_or(defaults.max_attempts),
+        }
+    }
+}
+
+/// ActivityPub federation of posts.
+#[derive(Clone, Debug)]
+pub struct ActivityPubConfig {
+    /// Serve the actor, WebFinger and inbox, and deliver new posts to
+    /// followers (`ACTIVITYPUB_ENABLED`, default false).
+    pub enabled: bool,
+    /// Account name, as in `@blog@miniex.blog` (`ACTIVITYPUB_USERNAME`, default `blog`).
+    pub username: String,
+    /// Profile display name (`ACTIVITYPUB_DISPLAY_NAME`, default `miniex.blog`).
+    pub display_name: String,
+    /// Show federated replies under posts (`ACTIVITYPUB_SHOW_REPLIES`, default true).
+    /// Replies are stored either way.
+    pub show_replies: bool,
+}
+
+impl Default for ActivityPubConfig {
+    fn default() -> Self {
+        ActivityPubConfig {
+            enabled: false,
+            username: "blog".to_string(),
+            display_name: "miniex.blog".to_string(),
+            show_replies: true,
+        }
+    }
+}
+
+impl ActivityPubConfig {
+    fn from_env() -> Self {
+        let defaults = ActivityPubConfig::default();
+        ActivityPubConfig {
+            enabled: env_flag("ACTIVITYPUB_ENABLED").unwrap_or(defaults.enabled),
+            username: env_string("ACTIVITYPUB_USERNAME").unwrap_or(defaults.username),
+            display_name: env_string("ACTIVITYPUB_DISPLAY_NAME").unwrap_or(defaults.display_name),
+            show_replies: env_flag("ACTIVITYPUB_SHOW_REPLIES").unwrap_or(defaults.show_replies),
+        }
+    }
+}
+
+/// Report new and updated posts to search engines through IndexNow.
+#[derive(Clone, Debug)]
+pub struct IndexNowConfig {
+    /// Submit changed URLs after each content load (`INDEXNOW_ENABLED`, default false).
+    pub enabled: bool,
+    /// Submission endpoint (`INDEXNOW_ENDPOINT`, default `https://api.indexnow.org/indexnow`).
+    pub endpoint: String,
+    /// URLs per request (`INDEXNOW_BATCH_SIZE`, default 100; the protocol allows 10,000).
+    pub batch_size: usize,
+    /// Log what would be submitted without sending or recording anything
+    /// (`INDEXNOW_DRY_RUN`, default false).
+    pub dry_run: bool,
+}
+
+impl Default for IndexNowConfig {
+    fn default() -> Self {
+        IndexNowConfig {
+            enabled: false,
+            endpoint: "https://api.indexnow.org/indexnow".to_string(),
+            batch_size: 100,
+            dry_run: false,
+        }
+    }
+}
+
+impl IndexNowConfig {
+    fn from_env() -> Self {
+        let defaults = IndexNowConfig::default();
+        IndexNowConfig {
+            enabled: env_flag("INDEXNOW_ENABLED").unwrap_or(defaults.enabled),
+            endpoint: env_string("INDEXNOW_ENDPOINT").unwrap_or(defaults.endpoint),
+            batch_size: env_parse::<usize>("INDEXNOW_BATCH_SIZE")
+                .map(|n| n.clamp(1, 10_000))
+                .unwrap_or(defaults.batch_size),
+            dry_run: env_flag("INDEXNOW_DRY_RUN").unwrap_or(defaults.dry_run),
+        }
+    }
+}
+
+/// Request rate limits per route group and which clients are trusted or blocked.
+///
+/// Policies are written `<interval>,<burst>`: one request is allowed back
+/// every interval (`500ms`, `10s`, `2m`), and at most `burst` in a row.
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// Reactions, visits and webmentions (`RATE_LIMIT_WRITE`, default `2s,5`).
+    pub write: RateLimitPolicy,
+    /// New comments and guestbook entries (`RATE_LIMIT_SUBMIT`, default `10s,3`).
+    pub submit: RateLimitPolicy,
+    /// Password-checked edits and deletes and the admin login
+    /// (`RATE_LIMIT_PASSWORD`, default `20s,3`).
+    pub password: RateLimitPolicy,
+    /// Signed deliveries to the ActivityPub inbox, each of which may fetch
+    /// the signer's key (`RATE_LIMIT_INBOX`, default `1s,30`).
+    pub inbox: RateLimitPolicy,
+    /// Reverse proxies whose `X-Forwarded-For` is believed
+    /// (`TRUSTED_PROXIES`, comma-separated IPs or CIDRs, default none).
+    pub trusted_proxies: Vec<IpNet>,
+    /// Clients refused outright (`BLOCKED_IPS`, comma-separated IPs or CIDRs).
+    pub blocked: Vec<IpNet>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            write: RateLimitPolicy::new("write", std::time::Duration::from_secs(2), 5),
+            submit: RateLimitPolicy::new("submit", std::time::Duration::from_secs(10), 3),
+            password: RateLimitPolicy::new("password", std::time::Duration::from_secs(20), 3),
+            inbox: RateLimitPolicy::new("inbox", std::time::Duration::from_secs(1), 30),
+            trusted_proxies: Vec::new(),
+            blocked: Vec::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn from_env() -> Self {
+        let defaults = RateLimitConfig::default();
+        let policy = |key: &str, default: RateLimitPolicy| {
+            env_string(key)
+                .and_then(|raw| {
+                    let parsed = parse_policy(default.name, &raw);
+                    if parsed.is_none() {
+                        tracing::warn!("Ignoring invalid value for {}: {:?}", key, raw);
+                    }
+                    parsed
+                })
+                .unwrap_or(default)
+        };
+        let networks = |key: &str| {
+            env_string(key)
+                .map(|raw| {
+                    let (nets, invalid) = parse_networks(&raw);
+                    for entry in invalid {
+                        tracing::warn!("Ignoring invalid entry in {}: {:?}", key, entry);
+                    }
+                    nets
+                })
+                .unwrap_or_default()
+        };
+        RateLimitConfig {
+            write: policy("RATE_LIMIT_WRITE", defaults.write),
+            submit: policy("RATE_LIMIT_SUBMIT", defaults.submit),
+            password: policy("RATE_LIMIT_PASSWORD", defaults.password),
+            inbox: policy("RATE_LIMIT_INBOX", defaults.inbox),
+            trusted_proxies: networks("TRUSTED_PROXIES"),
+            blocked: networks("BLOCKED_IPS"),
+        }
+    }
+}
+
+/// Lockouts after repeated wrong passwords on comment and guestbook edits
+/// and deletes.
+#[derive(Clone, Debug)]
+pub struct PasswordLockoutConfig {
+    /// Wrong passwords for one comment or entry before it locks
+    /// (`PASSWORD_LOCKOUT_ITEM_THRESHOLD`, default 5).
+    pub item_threshold: u32,
+    /// Wrong passwords from one client address, across all items, before it
+    /// locks (`PASSWORD_LOCKOUT_CLIENT_THRESHOLD`, default 10).
+    pub client_threshold: u32,
+    /// First lockout; each further failure doubles it
+    /// (`PASSWORD_LOCKOUT_MINUTES`, default 1).
+    pub base: Duration,
+    /// Longest lockout, and how long failures are remembered
+    /// (`PASSWORD_LOCKOUT_MAX_HOURS`, default 24).
+    pub max: Duration,
+}
+
+impl Default for PasswordLockoutConfig {
+    fn default() -> Self {
+        PasswordLockoutConfig {
+            item_threshold: 5,
+            client_threshold: 10,
+            base: Duration::minutes(1),
+            max: Duration::hours(24),
+        }
+    }
+}
+
+impl PasswordLockoutConfig {
+    fn from_env() -> Self {
+        let defaults = PasswordLockoutConfig::default();
+        PasswordLockoutConfig {
+            item_threshold: env_parse::<u32>("PASSWORD_LOCKOUT_ITEM_THRESHOLD")
+                .map(|n| n.max(1))
+                .unwrap_or(defaults.item_threshold),
+            client_threshold: env_parse::<u32>("PASSWORD_LOCKOUT_CLIENT_THRESHOLD")
+                .map(|n| n.max(1))
+                .unwrap_or(defaults.client_threshold),
+            base: env_duration("PASSWORD_LOCKOUT_MINUTES", Duration::try_minutes, 1)
+                .unwrap_or(defaults.base),
+            max: env_duration("PASSWORD_LOCKOUT_MAX_HOURS", Duration::try_hours, 1)
+                .unwrap_or(defaults.max),
+        }
+    }
+}
+
+/// The `Content-Security-Policy` header.
+#[derive(Clone, Debug)]
+pub struct CspConfig {
+    /// Send a policy at all (`CSP_ENABLED`, default true).
+    pub enabled: bool,
+    /// Only report violations instead of blocking them (`CSP_REPORT_ONLY`,
+    /// default false in release builds and true in debug builds, where the
+    /// live-reload script has no nonce).
+    pub report_only: bool,
+    /// Ask browsers to post violations to `/csp-report` (`CSP_REPORTS`, default true).
+    pub reports: bool,
+    /// Sources added to the built-in directives (`CSP_EXTRA`, e.g.
+    /// `img-src https://img.example.com; connect-src https://api.example.com`).
+    pub extra: Vec<(String, Vec<String>)>,
+}
+
+impl Default for CspConfig {
+    fn default() -> Self {
+        CspConfig {
+            enabled: true,
+            report_only: cfg!(debug_assertions),
+            reports: true,
+            extra: Vec::new(),
+        }
+    }
+}
+
+impl CspConfig {
+    fn from_env() -> Self {
+        let defaults = CspConfig::default();
+        CspConfig {
+            enabled: env_flag("CSP_ENABLED").unwrap_or(defaults.enabled),
+            report_only: env_flag("CSP_REPORT_ONLY").unwrap_or(defaults.report_only),
+            reports: env_flag("CSP_REPORTS").unwrap_or(defaults.reports),
+            extra: env_string("CSP_EXTRA")
+                .map(|raw| parse_extra(&raw))
+                .unwrap_or(defaults.extra),
+        }
+    }
+}
+
+/// The Prometheus `/metrics` endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` at all (`METRICS_ENABLED`). Defaults to on only when
+    /// `METRICS_ADDR` is set, so the site never exposes metrics unasked.
+    pub enabled: bool,
+    /// Serve `/metrics` on this address only, instead of on the site
+    /// (`METRICS_ADDR`, e.g. `127.0.0.1:9100`).
+    pub addr: Option<SocketAddr>,
+}
+
+impl MetricsConfig {
+    fn from_env() -> Self {
+        let addr = env_parse("METRICS_ADDR");
+        MetricsConfig {
+            enabled: env_flag("METRICS_ENABLED").unwrap_or(addr.is_some()),
+            addr,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            admin_password_hash: None,
+            admin_session_ttl: Duration::hours(12),
+            spam_threshold: 1.0,
+            spam_min_submit_secs: 3,
+            spam_max_links: 2,
+            spam_blocklist_path: "data/spam-blocklist.txt".to_string(),
+            form_token_secret: None,
+            notify: NotifyConfig::default(),
+            webmention_send_enabled: false,
+            webmention_send_max_age_days: 30,
+            activitypub: ActivityPubConfig::default(),
+            feed_full_content: true,
+            indexnow: IndexNowConfig::default(),
+            og_cache_dir: "data/og".to_string(),
+            view_dedup_window: Duration::minutes(30),
+            bot_user_agents_path: None,
+            visitor_secret: None,
+            site_timezone: Tz::UTC,
+            visitor_retention_days: 90,
+            reactions: parse_kinds(DEFAULT_REACTIONS),
+            content_commit: None,
+            rate_limit: RateLimitConfig::default(),
+            password_lockout: PasswordLockoutConfig::default(),
+            csp: CspConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let defaults = Config::default();
+
+        let admin_password_hash = env_string("ADMIN_PASSWORD_HASH");
+        if admin_password_hash.is_none() {
+            tracing::warn!("ADMIN_PASSWORD_HASH is not set; admin login is disabled");
+        }
+
+        Config {
+            admin_password_hash,
+            admin_session_ttl: env_duration("ADMIN_SESSION_HOURS", Duration::try_hours, 1)
+                .unwrap_or(defaults.admin_session_ttl),
+            spam_threshold: env_parse("SPAM_THRESHOLD").unwrap_or(defaults.spam_threshold),
+            spam_min_submit_secs: env_parse("SPAM_MIN_SUBMIT_SECS")
+                .unwrap_or(defaults.spam_min_submit_secs),
+            spam_max_links: env_parse("SPAM_MAX_LINKS").unwrap_or(defaults.spam_max_links),
+            spam_blocklist_path: env_string("SPAM_BLOCKLIST")
+                .unwrap_or(defaults.spam_blocklist_path),
+            form_token_secret: env_string("FORM_TOKEN_SECRET"),
+            notify: NotifyConfig::from_env(),
+            webmention_send_enabled: env_flag("WEBMENTION_SEND_ENABLED")
+                .unwrap_or(defaults.webmention_send_enabled),
+            webmention_send_max_age_days: env_parse("WEBMENTION_SEND_MAX_AGE_DAYS")
+                .unwrap_or(defaults.webmention_send_max_age_days),
+            activitypub: ActivityPubConfig::from_env(),
+            feed_full_content: env_flag("FEED_FULL_CONTENT").unwrap_or(defaults.feed_full_content),
+            indexnow: IndexNowConfig::from_env(),
+            og_cache_dir: env_string("OG_CACHE_DIR").unwrap_or(defaults.og_cache_dir),
+            view_dedup_window: env_duration("VIEW_DEDUP_MINUTES", Duration::try_minutes, 0)
+                .unwrap_or(defaults.view_dedup_window),
+            bot_user_agents_path: env_string("BOT_USER_AGENTS"),
+            visitor_secret: env_string("VISITOR_SECRET"),
+            site_timezone: env_parse("SITE_TIMEZONE").unwrap_or(defaults.site_timezone),
+            visitor_retention_days: env_parse("VISITOR_RETENTION_DAYS")
+                .unwrap_or(defaults.visitor_retention_days),
+            reactions: env_string("REACTIONS")
+                .map(|raw| parse_kinds(&raw))
+                .filter(|kinds| {
+                    if kinds.is_empty() {
+                        tracing::warn!("REACTIONS has no valid `name:emoji` pairs; using defaults");
+                    }
+                    !kinds.is_empty()
+                })
+                .unwrap_or(defaults.reactions),
+            content_commit: env_string("CONTENT_COMMIT"),
+            rate_limit: RateLimitConfig::from_env(),
+            password_lockout: PasswordLockoutConfig::from_env(),
+            csp: CspConfig::from_env(),
+            metrics: MetricsConfig::from_env(),
+        }
+    }
+}
+
+fn env_string(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn env_flag(key: &str) -> Option<bool> {
+    let raw = env_string(key)?;
+    match raw.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => {
+            tracing::warn!("Ignoring invalid value for {}: {:?}", key, raw);
+            None
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    let raw = env_string(key)?;
+    match raw.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid value for {}: {:?}", key, raw);
+            None
+        }
+    }
+}
+
+/// `key` as a count of `unit`s (e.g. `Duration::try_hours`), raised to at
+/// least `min`. Values too large for a `Duration` are ignored like
+/// unparsable ones instead of panicking.
+fn env_duration(key: &str, unit: fn(i64) -> Option<Duration>, min: i64) -> Option<Duration> {
+    let n = env_parse::<i64>(key)?.max(min);
+    let duration = unit(n);
+    if duration.is_none() {
+        tracing::warn!("Ignoring out-of-range value for {}: {}", key, n);
+    }
+    duration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_duration() {
+        let key = "BLOG_TEST_ENV_DURATION";
+        std::env::set_var(key, "3");
+        assert_eq!(
+            env_duration(key, Duration::try_hours, 1),
+            Some(Duration::hours(3))
+        );
+        std::env::set_var(key, "-5");
+        assert_eq!(
+            env_duration(key, Duration::try_hours, 1),
+            Some(Duration::hours(1))
+        );
+        // Out of chrono's range: ignored rather than panicking
+        std::env::set_var(key, i64::MAX.to_string());
+        assert_eq!(env_duration(key, Duration::try_hours, 1), None);
+        std::env::set_var(key, "soon");
+        assert_eq!(env_duration(key, Duration::try_minutes, 0), None);
+        std::env::remove_var(key);
+    }
+}
@@ -0,0 +1,276 @@
+//! Content Security Policy with a per-request nonce.
+//!
+//! Every response gets a fresh nonce, and only inline scripts and styles
+//! carrying it may run, so the policy needs no `'unsafe-inline'`. The nonce
+//! is kept in a task-local while the handler runs, which lets [`Blog::new`]
+//! pick it up for templates without every handler passing it along.
+//!
+//! [`Blog::new`]: crate::Blog::new
+
+use crate::{config::CspConfig, vendor, SharedState};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+/// Where browsers send violation reports.
+pub const REPORT_PATH: &str = "/csp-report";
+/// Name of the report endpoint in `Reporting-Endpoints`.
+const REPORT_GROUP: &str = "csp";
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// The nonce for the response being rendered, or an empty string outside a
+/// request (tests, background work).
+pub fn current_nonce() -> String {
+    NONCE.try_with(String::clone).unwrap_or_default()
+}
+
+/// Run `f` as if rendering a response with `nonce`.
+pub async fn with_nonce<F: std::future::Future>(nonce: String, f: F) -> F::Output {
+    NONCE.scope(nonce, f).await
+}
+
+fn new_nonce() -> String {
+    use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    BASE64.encode(bytes)
+}
+
+/// Parse `CSP_EXTRA`: `directive source…` groups separated by `;`, e.g.
+/// `img-src https://img.example.com; connect-src https://api.example.com`.
+pub fn parse_extra(raw: &str) -> Vec<(String, Vec<String>)> {
+    raw.split(';')
+        .filter_map(|group| {
+            let mut words = group.split_whitespace();
+            let directive = words.next()?.to_ascii_lowercase();
+            let valid = directive
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '-');
+            valid.then(|| (directive, words.map(str::to_string).collect()))
+        })
+        .collect()
+}
+
+/// The policy for one response.
+pub fn policy(config: &CspConfig, nonce: &str, fallback_origins: &[String]) -> String {
+    let nonce = format!("'nonce-{}'", nonce);
+    let mut directives: Vec<(String, Vec<String>)> = [
+        ("default-src", vec!["'self'"]),
+        (
+            "script-src",
+            vec!["'self'", &nonce, "https://www.googletagmanager.com"],
+        ),
+        (
+            "style-src",
+            vec!["'self'", &nonce, "https://fonts.googleapis.com"],
+        ),
+        ("font-src", vec!["'self'", "https://fonts.gstatic.com"]),
+        ("img-src", vec!["'self'", "data:", "https:"]),
+        (
+            "connect-src",
+            vec!["'self'", "https://www.google-analytics.com"],
+        ),
+        ("object-src", vec!["'none'"]),
+        ("base-uri", vec!["'self'"]),
+        ("form-action", vec!["'self'"]),
+        ("frame-ancestors", vec!["'none'"]),
+    ]
+    .into_iter()
+    .map(|(name, sources)| {
+        (
+            name.to_string(),
+            sources.into_iter().map(str::to_string).collect(),
+        )
+    })
+    .collect();
+
+    // Libraries without a local copy are loaded from upstream
+    for (name, sources) in directives.iter_mut() {
+        if matches!(name.as_str(), "script-src" | "style-src" | "font-src") {
+            sources.extend(fallback_origins.iter().cloned());
+        }
+    }
+    for (name, extra) in &config.extra {
+        match directives.iter_mut().find(|(n, _)| n == name) {
+            Some((_, sources)) => sources.extend(extra.iter().cloned()),
+            None => directives.push((name.clone(), extra.clone())),
+        }
+    }
+    if config.reports {
+        directives.push(("report-uri".to_string(), vec![REPORT_PATH.to_string()]));
+        directives.push(("report-to".to_string(), vec![REPORT_GROUP.to_string()]));
+    }
+
+    directives
+        .iter()
+        .map(|(name, sources)| {
+            if sources.is_empty() {
+                name.clone()
+            } else {
+                format!("{} {}", name, sources.join(" "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Give each request a nonce and send the matching policy with the response.
+pub async fn content_security_policy(
+    State(state): State<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let config = &state.config.csp;
+    if !config.enabled {
+        return next.run(req).await;
+    }
+
+    let nonce = new_nonce();
+    let mut response = with_nonce(nonce.clone(), next.run(req)).await;
+
+    let name = if config.report_only {
+        HeaderName::from_static("content-security-policy-report-only")
+    } else {
+        header::CONTENT_SECURITY_POLICY
+    };
+    let value = policy(config, &nonce, vendor::assets().fallback_origins());
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(name, value);
+    }
+    let endpoints = format!("{}=\"{}\"", REPORT_GROUP, REPORT_PATH);
+    if let (true, Ok(value)) = (config.reports, HeaderValue::from_str(&endpoints)) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("reporting-endpoints"), value);
+    }
+    response
+}
+
+/// One violation, from either the `report-uri` or the Reporting API format.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub document: String,
+    pub directive: String,
+    pub blocked: String,
+}
+
+/// Violations in a report body. `report-uri` sends
+/// `{"csp-report": {...}}` with kebab-case keys; the Reporting API sends an
+/// array of `{"type": "csp-violation", "body": {...}}` with camelCase keys.
+pub fn parse_report(body: &[u8]) -> Vec<Violation> {
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return Vec::new();
+    };
+    let field = |v: &serde_json::Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| v.get(*k).and_then(|f| f.as_str()))
+            .unwrap_or_default()
+            .chars()
+            .take(500)
+            .collect::<String>()
+    };
+    let violation = |v: &serde_json::Value| Violation {
+        document: field(v, &["document-uri", "documentURL"]),
+        directive: field(
+            v,
+            &[
+                "effective-directive",
+                "violated-directive",
+                "effectiveDirective",
+            ],
+        ),
+        blocked: field(v, &["blocked-uri", "blockedURL"]),
+    };
+
+    if let Some(report) = value.get("csp-report") {
+        return vec![violation(report)];
+    }
+    value
+        .as_array()
+        .map(|reports| {
+            reports
+                .iter()
+                .filter(|r| r.get("type").and_then(|t| t.as_str()) == Some("csp-violation"))
+                .filter_map(|r| r.get("body"))
+                .map(violation)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_is_strict() {
+        let config = CspConfig::default();
+        let policy = policy(&config, "abc", &[]);
+        assert!(policy.contains("script-src 'self' 'nonce-abc' "));
+        assert!(policy.contains("style-src 'self' 'nonce-abc' "));
+        assert!(policy.contains("object-src 'none'"));
+        assert!(policy.contains("report-uri /csp-report; report-to csp"));
+        assert!(!policy.contains("unsafe-inline"));
+        assert!(!policy.contains("cdn.jsdelivr.net"));
+    }
+
+    #[test]
+    fn test_policy_fallbacks_and_extras() {
+        let config = CspConfig {
+            extra: parse_extra("img-src https://img.example.com; worker-src 'self'; BAD_1 x"),
+            reports: false,
+            ..CspConfig::default()
+        };
+        let policy = policy(&config, "n", &["https://cdn.example.com".to_string()]);
+        assert!(policy.contains(
+            "script-src 'self' 'nonce-n' https://www.googletagmanager.com https://cdn.example.com;"
+        ));
+        assert!(
+            policy.contains("font-src 'self' https://fonts.gstatic.com https://cdn.example.com;")
+        );
+        assert!(policy.contains("img-src 'self' data: https: https://img.example.com;"));
+        assert!(policy.ends_with("; worker-src 'self'"));
+        assert!(!policy.contains("report-uri"));
+        assert!(!policy.to_lowercase().contains("bad_1"));
+    }
+
+    #[tokio::test]
+    async fn test_nonce_reaches_blog() {
+        assert_eq!(current_nonce(), "");
+        let blog = with_nonce("abc".to_string(), async { crate::Blog::new() }).await;
+        assert_eq!(blog.nonce, "abc");
+        assert_ne!(new_nonce(), new_nonce());
+    }
+
+    #[test]
+    fn test_parse_report_formats() {
+        let legacy = br#"{"csp-report": {"document-uri": "https://miniex.blog/post/a",
+            "violated-directive": "script-src-elem", "blocked-uri": "inline"}}"#;
+        assert_eq!(
+            parse_report(legacy),
+            vec![Violation {
+                document: "https://miniex.blog/post/a".to_string(),
+                directive: "script-src-elem".to_string(),
+                blocked: "inline".to_string(),
+            }]
+        );
+
+        let reporting =
+            br#"[{"type": "csp-violation", "body": {"documentURL": "https://miniex.blog/",
+            "effectiveDirective": "style-src-attr", "blockedURL": "inline"}},
+            {"type": "deprecation", "body": {}}]"#;
+        let violations = parse_report(reporting);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].directive, "style-src-attr");
+
+        assert!(parse_report(b"not json").is_empty());
+    }
+}
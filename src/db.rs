@@ -1,33 +1,253 @@
+use crate::{
+    notify::NotificationEvent, reactions::LEGACY_LIKE_KIND, sanitize::render_user_markdown,
+    spam::SpamVerdict,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
+use sqlx::{
+    sqlite::{SqlitePoolOptions, SqliteRow},
+    Pool, Row, Sqlite,
+};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Date under which view counts from before daily aggregation are kept.
+pub const LEGACY_VIEW_DATE: &str = "1970-01-01";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
     pub id: String,
     pub post_id: String,
     pub author: String,
+    /// Markdown source as written by the author.
     pub content: String,
+    /// Sanitized HTML rendering of `content`.
+    pub content_html: String,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing)]
     pub password_hash: Option<String>,
+    #[serde(skip_serializing)]
+    pub spam_score: f64,
+    pub is_spam: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Guestbook {
     pub id: String,
     pub author: String,
+    /// Markdown source as written by the author.
     pub content: String,
+    /// Sanitized HTML rendering of `content`.
+    pub content_html: String,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing)]
     pub password_hash: Option<String>,
+    #[serde(skip_serializing)]
+    pub spam_score: f64,
+    pub is_spam: bool,
+    /// Author's homepage, an absolute http(s) URL.
+    pub website: Option<String>,
+    /// Pinned entries are listed before all others.
+    pub pinned: bool,
+    /// The owner's reply in Markdown, shown beneath the entry.
+    pub reply: Option<String>,
+    /// Sanitized HTML rendering of `reply`.
+    pub reply_html: Option<String>,
+    pub replied_at: Option<DateTime<Utc>>,
+}
+
+/// User-submitted content that goes through spam moderation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Comment,
+    Guestbook,
+}
+
+impl ItemKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ItemKind::Comment => "comment",
+            ItemKind::Guestbook => "guestbook",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            ItemKind::Comment => "comments",
+            ItemKind::Guestbook => "guestbook",
+        }
+    }
+}
+
+const COMMENT_COLUMNS: &str =
+    "id, post_id, author, content, created_at, password_hash, spam_score, is_spam";
+const GUESTBOOK_COLUMNS: &str =
+    "id, author, content, created_at, password_hash, spam_score, is_spam, \
+     website, pinned, reply, replied_at";
+
+fn parse_timestamp(row: &SqliteRow, column: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&row.get::<String, _>(column))
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+fn comment_from_row(row: SqliteRow) -> Comment {
+    Comment {
+        id: row.get("id"),
+        post_id: row.get("post_id"),
+        author: row.get("author"),
+        content_html: render_user_markdown(&row.get::<String, _>("content")),
+        content: row.get("content"),
+        created_at: parse_timestamp(&row, "created_at"),
+        password_hash: row.get("password_hash"),
+        spam_score: row.get("spam_score"),
+        is_spam: row.get("is_spam"),
+    }
+}
+
+fn guestbook_from_row(row: SqliteRow) -> Guestbook {
+    Guestbook {
+        id: row.get("id"),
+        author: row.get("author"),
+        content_html: render_user_markdown(&row.get::<String, _>("content")),
+        content: row.get("content"),
+        created_at: parse_timestamp(&row, "created_at"),
+        password_hash: row.get("password_hash"),
+        spam_score: row.get("spam_score"),
+        is_spam: row.get("is_spam"),
+        website: row.get("website"),
+        pinned: row.get("pinned"),
+        reply_html: row
+            .get::<Option<String>, _>("reply")
+            .map(|reply| render_user_markdown(&reply)),
+        reply: row.get("reply"),
+        replied_at: row
+            .get::<Option<String>, _>("replied_at")
+            .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+            .map(|at| at.with_timezone(&Utc)),
+    }
+}
+
+/// Per-post engagement totals shown in the admin area.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostStats {
+    pub slug: String,
+    pub views: u32,
+    pub reactions: u32,
+}
+
+/// Views of one post, or all posts, on one day.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyViews {
+    pub date: String,
+    pub views: u32,
+    pub visitors: u32,
+}
+
+/// Unique site visitors on one day.
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyVisitors {
+    pub date: String,
+    pub visitors: u32,
+}
+
+/// Automated requests over a date range, grouped by why they were not counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct BotHits {
+    pub reason: String,
+    pub hits: u32,
+}
+
+/// Views of one post over a date range.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostViews {
+    pub slug: String,
+    pub views: u32,
+    pub visitors: u32,
+}
+
+/// Result of [`Database::count_password_attempt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordAttempt {
+    /// A lock on this subject is in force until the given time; nothing was
+    /// counted.
+    Locked(String, DateTime<Utc>),
+    /// The attempt was counted against every subject. Holds the subjects it
+    /// locked, with their failure count and lock end.
+    Counted(Vec<(String, u32, DateTime<Utc>)>),
+}
+
+/// A verified Webmention shown under a post.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webmention {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+    pub post_slug: String,
+    pub author_name: Option<String>,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webmention {
+    /// Host of the mentioning page, shown when it has no author name.
+    pub fn source_host(&self) -> String {
+        url::Url::parse(&self.source)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.source.clone())
+    }
+}
+
+/// A reply to one of our posts received over ActivityPub.
+#[derive(Debug, Clone, Serialize)]
+pub struct FederatedReply {
+    /// ActivityPub object id of the reply.
+    pub id: String,
+    pub post_slug: String,
+    pub actor_id: String,
+    /// Display name, falling back to `@user@host`.
+    pub author_name: String,
+    /// Human-readable page of the reply.
+    pub url: String,
+    /// Sanitized HTML body.
+    pub content_html: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A remote account following the blog actor.
+#[derive(Debug, Clone)]
+pub struct Follower {
+    pub actor_id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+}
+
+/// A pending delivery in the notification retry queue.
+#[derive(Debug, Clone)]
+pub struct QueuedNotification {
+    pub id: i64,
+    pub event: NotificationEvent,
+    pub created_at: i64,
+    pub attempts: u32,
+}
+
+/// A pending activity in the ActivityPub delivery queue.
+#[derive(Debug, Clone)]
+pub struct QueuedDelivery {
+    pub id: i64,
+    pub inbox: String,
+    /// Serialized activity, as delivered.
+    pub activity: String,
+    pub attempts: u32,
 }
 
 #[derive(Debug, Clone)]
 pub struct Database {
     pub pool: Pool<Sqlite>,
+    /// Channels that get a queue entry for every new comment or guestbook entry.
+    notification_channels: Arc<Vec<&'static str>>,
 }
 
 impl Database {
@@ -76,10 +296,306 @@ impl Database {
             .execute(&pool)
             .await?;
 
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS admin_sessions (
+                token_hash TEXT PRIMARY KEY,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Failed password checks per comment, guestbook entry and client address
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS password_failures (
+                subject TEXT PRIMARY KEY,
+                failures INTEGER NOT NULL,
+                last_failure_at TEXT NOT NULL,
+                locked_until TEXT
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Spam moderation columns, added in place so existing rows survive
+        for table in ["comments", "guestbook"] {
+            Self::add_column_if_missing(
+                &pool,
+                table,
+                "spam_score",
+                "spam_score REAL NOT NULL DEFAULT 0",
+            )
+            .await?;
+            Self::add_column_if_missing(
+                &pool,
+                table,
+                "is_spam",
+                "is_spam INTEGER NOT NULL DEFAULT 0",
+            )
+            .await?;
+            Self::add_column_if_missing(&pool, table, "spam_trained", "spam_trained TEXT").await?;
+        }
+
+        // Guestbook author websites, pinning and owner replies
+        Self::add_column_if_missing(&pool, "guestbook", "website", "website TEXT").await?;
+        Self::add_column_if_missing(
+            &pool,
+            "guestbook",
+            "pinned",
+            "pinned INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        Self::add_column_if_missing(&pool, "guestbook", "reply", "reply TEXT").await?;
+        Self::add_column_if_missing(&pool, "guestbook", "replied_at", "replied_at TEXT").await?;
+
+        // Naive-Bayes token counts, trained from moderator decisions
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS spam_tokens (
+                token TEXT PRIMARY KEY,
+                spam_count INTEGER NOT NULL DEFAULT 0,
+                ham_count INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS spam_corpus (
+                label TEXT PRIMARY KEY,
+                docs INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Incoming Webmentions; (source, target) is unique so a resend updates in place
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webmentions (
+                id TEXT PRIMARY KEY,
+                source TEXT NOT NULL,
+                target TEXT NOT NULL,
+                post_slug TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                author_name TEXT,
+                title TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                UNIQUE(source, target)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_webmentions_post ON webmentions(post_slug, status)",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Outgoing Webmentions already attempted, so reloads do not resend
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webmentions_sent (
+                post_slug TEXT NOT NULL,
+                target TEXT NOT NULL,
+                endpoint TEXT,
+                status_code INTEGER,
+                sent_at TEXT NOT NULL,
+                PRIMARY KEY (post_slug, target)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Generated server secrets, kept so they survive restarts
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS secrets (
+                name TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // ActivityPub actor key pair; a single row generated on first start
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS activitypub_keys (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                private_key_pem TEXT NOT NULL,
+                public_key_pem TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS activitypub_followers (
+                actor_id TEXT PRIMARY KEY,
+                inbox TEXT NOT NULL,
+                shared_inbox TEXT,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Posts already announced to followers, so reloads do not resend
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS activitypub_published (
+                post_slug TEXT PRIMARY KEY,
+                published_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Activities still to be delivered, one row per inbox, removed once
+        // delivered; retried like the notification queue
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS activitypub_deliveries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                inbox TEXT NOT NULL,
+                activity TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                failed_at INTEGER
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_activitypub_deliveries_due ON activitypub_deliveries(failed_at, next_attempt_at)",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS activitypub_replies (
+                id TEXT PRIMARY KEY,
+                post_slug TEXT NOT NULL,
+                actor_id TEXT NOT NULL,
+                author_name TEXT NOT NULL,
+                url TEXT NOT NULL,
+                content_html TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_activitypub_replies_post ON activitypub_replies(post_slug)",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Last version of each URL reported to IndexNow
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS indexnow_submissions (
+                url TEXT PRIMARY KEY,
+                version TEXT NOT NULL,
+                submitted_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Notification retry queue; one row per event and channel, removed once delivered
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS notification_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                failed_at INTEGER
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_notification_queue_due ON notification_queue(channel, failed_at, next_attempt_at)",
+        )
+        .execute(&pool)
+        .await?;
+
         // Migrate stats tables (drop & recreate if schema mismatch)
         Self::migrate_stats_tables(&pool).await?;
 
-        Ok(Database { pool })
+        Ok(Database {
+            pool,
+            notification_channels: Arc::new(Vec::new()),
+        })
+    }
+
+    /// Queue notifications for new comments and guestbook entries on these channels.
+    pub fn with_notification_channels(mut self, channels: Vec<&'static str>) -> Self {
+        self.notification_channels = Arc::new(channels);
+        self
+    }
+
+    /// A round trip to SQLite, for readiness checks.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query_scalar::<_, i64>("SELECT 1")
+            .fetch_one(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Add a column to an existing table unless it is already there.
+    async fn add_column_if_missing(
+        pool: &Pool<Sqlite>,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(pool)
+            .await?;
+        let exists = rows.iter().any(|r| r.get::<String, _>("name") == column);
+        if !exists {
+            tracing::info!("Migrating table '{}': adding column '{}'", table, column);
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {}", table, definition))
+                .execute(pool)
+                .await?;
+        }
+        Ok(())
     }
 
     /// Check each stats table for expected columns; drop & recreate on mismatch.
@@ -92,21 +608,47 @@ impl Database {
         // (table_name, expected_columns, create DDL)
         let tables: &[(&str, &[&str], &str)] = &[
             (
-                "post_views",
-                &["post_slug", "count"],
-                "CREATE TABLE post_views (
-                    post_slug TEXT PRIMARY KEY,
-                    count INTEGER NOT NULL DEFAULT 0
+                "post_views_daily",
+                &["post_slug", "view_date", "views", "visitors"],
+                "CREATE TABLE post_views_daily (
+                    post_slug TEXT NOT NULL,
+                    view_date TEXT NOT NULL,
+                    views INTEGER NOT NULL DEFAULT 0,
+                    visitors INTEGER NOT NULL DEFAULT 0,
+                    PRIMARY KEY (post_slug, view_date)
                 )",
             ),
             (
-                "post_likes",
-                &["post_slug", "client_id", "created_at"],
-                "CREATE TABLE post_likes (
+                "post_view_visitors",
+                &["post_slug", "view_date", "visitor_hash", "last_viewed_at"],
+                "CREATE TABLE post_view_visitors (
                     post_slug TEXT NOT NULL,
-                    client_id TEXT NOT NULL,
+                    view_date TEXT NOT NULL,
+                    visitor_hash TEXT NOT NULL,
+                    last_viewed_at TEXT NOT NULL,
+                    PRIMARY KEY (post_slug, view_date, visitor_hash)
+                )",
+            ),
+            (
+                "bot_hits_daily",
+                &["post_slug", "hit_date", "reason", "hits"],
+                "CREATE TABLE bot_hits_daily (
+                    post_slug TEXT NOT NULL,
+                    hit_date TEXT NOT NULL,
+                    reason TEXT NOT NULL,
+                    hits INTEGER NOT NULL DEFAULT 0,
+                    PRIMARY KEY (post_slug, hit_date, reason)
+                )",
+            ),
+            (
+                "post_reactions",
+                &["post_slug", "kind", "visitor_key", "created_at"],
+                "CREATE TABLE post_reactions (
+                    post_slug TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    visitor_key TEXT NOT NULL,
                     created_at TEXT NOT NULL,
-                    PRIMARY KEY (post_slug, client_id)
+                    PRIMARY KEY (post_slug, kind, visitor_key)
                 )",
             ),
             (
@@ -118,6 +660,15 @@ impl Database {
                     PRIMARY KEY (client_id, visited_date)
                 )",
             ),
+            (
+                "visitors_daily",
+                &["visited_date", "visitors", "retired"],
+                "CREATE TABLE visitors_daily (
+                    visited_date TEXT PRIMARY KEY,
+                    visitors INTEGER NOT NULL DEFAULT 0,
+                    retired INTEGER NOT NULL DEFAULT 0
+                )",
+            ),
         ];
 
         for (name, expected_cols, ddl) in tables {
@@ -147,10 +698,57 @@ impl Database {
             }
         }
 
-        // Indexes (always IF NOT EXISTS, safe to run)
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_post_likes_slug ON post_likes(post_slug)")
+        // Lifetime counters from before daily aggregation keep counting
+        // toward each post's total under a date no range query reaches.
+        let legacy = sqlx::query("PRAGMA table_info(post_views)")
+            .fetch_all(pool)
+            .await?;
+        if legacy.iter().any(|r| r.get::<String, _>("name") == "count") {
+            tracing::info!("Migrating table 'post_views' into 'post_views_daily'");
+            sqlx::query(
+                "INSERT OR IGNORE INTO post_views_daily (post_slug, view_date, views, visitors)
+                 SELECT post_slug, ?, count, 0 FROM post_views",
+            )
+            .bind(LEGACY_VIEW_DATE)
+            .execute(pool)
+            .await?;
+        }
+        sqlx::query("DROP TABLE IF EXISTS post_views")
+            .execute(pool)
+            .await?;
+
+        // Single likes from before reactions become the default reaction
+        let legacy = sqlx::query("PRAGMA table_info(post_likes)")
+            .fetch_all(pool)
+            .await?;
+        if legacy
+            .iter()
+            .any(|r| r.get::<String, _>("name") == "client_id")
+        {
+            tracing::info!("Migrating table 'post_likes' into 'post_reactions'");
+            sqlx::query(
+                "INSERT OR IGNORE INTO post_reactions (post_slug, kind, visitor_key, created_at)
+                 SELECT post_slug, ?, client_id, created_at FROM post_likes",
+            )
+            .bind(LEGACY_LIKE_KIND)
+            .execute(pool)
+            .await?;
+        }
+        sqlx::query("DROP TABLE IF EXISTS post_likes")
             .execute(pool)
             .await?;
+
+        // Indexes (always IF NOT EXISTS, safe to run)
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_post_views_daily_date ON post_views_daily(view_date)",
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_post_reactions_slug ON post_reactions(post_slug)",
+        )
+        .execute(pool)
+        .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_visitors_date ON visitors(visited_date)")
             .execute(pool)
             .await?;
@@ -165,6 +763,7 @@ impl Database {
         author: &str,
         content: &str,
         password: Option<&str>,
+        spam: &SpamVerdict,
     ) -> Result<Comment, sqlx::Error> {
         let password_hash = password.map(|p| self.hash_password(p));
 
@@ -173,12 +772,16 @@ impl Database {
             post_id: post_id.to_string(),
             author: author.to_string(),
             content: content.to_string(),
+            content_html: render_user_markdown(content),
             created_at: Utc::now(),
             password_hash: password_hash.clone(),
+            spam_score: spam.score,
+            is_spam: spam.is_spam,
         };
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO comments (id, post_id, author, content, created_at, password_hash) VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO comments (id, post_id, author, content, created_at, password_hash, spam_score, is_spam) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&comment.id)
         .bind(&comment.post_id)
@@ -186,42 +789,38 @@ impl Database {
         .bind(&comment.content)
         .bind(comment.created_at.to_rfc3339())
         .bind(&password_hash)
-        .execute(&self.pool)
+        .bind(comment.spam_score)
+        .bind(comment.is_spam)
+        .execute(&mut *tx)
         .await?;
 
+        self.enqueue_notification(&mut tx, &NotificationEvent::from(&comment))
+            .await?;
+        tx.commit().await?;
+
         Ok(comment)
     }
 
     pub async fn get_comments_by_post(&self, post_id: &str) -> Result<Vec<Comment>, sqlx::Error> {
-        let rows = sqlx::query(
-            "SELECT id, post_id, author, content, created_at, password_hash FROM comments WHERE post_id = ? ORDER BY created_at DESC"
-        )
-        .bind(post_id)
-        .fetch_all(&self.pool)
-        .await?;
-
-        let comments = rows
-            .into_iter()
-            .map(|row| Comment {
-                id: row.get("id"),
-                post_id: row.get("post_id"),
-                author: row.get("author"),
-                content: row.get("content"),
-                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))
-                    .unwrap()
-                    .with_timezone(&Utc),
-                password_hash: row.get("password_hash"),
-            })
-            .collect();
+        let query = format!(
+            "SELECT {} FROM comments WHERE post_id = ? AND is_spam = 0 ORDER BY created_at DESC",
+            COMMENT_COLUMNS
+        );
+        let rows = sqlx::query(&query)
+            .bind(post_id)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(comments)
+        Ok(rows.into_iter().map(comment_from_row).collect())
     }
 
+    /// Replace the content if `password` matches, re-scored as `spam`.
     pub async fn update_comment(
         &self,
         comment_id: &str,
         content: &str,
         password: &str,
+        spam: &SpamVerdict,
     ) -> Result<bool, sqlx::Error> {
         let row = sqlx::query("SELECT password_hash FROM comments WHERE id = ?")
             .bind(comment_id)
@@ -241,11 +840,15 @@ impl Database {
                 return Ok(false);
             }
 
-            sqlx::query("UPDATE comments SET content = ? WHERE id = ?")
-                .bind(content)
-                .bind(comment_id)
-                .execute(&self.pool)
-                .await?;
+            sqlx::query(
+                "UPDATE comments SET content = ?, spam_score = ?, is_spam = ? WHERE id = ?",
+            )
+            .bind(content)
+            .bind(spam.score)
+            .bind(spam.is_spam)
+            .bind(comment_id)
+            .execute(&self.pool)
+            .await?;
 
             Ok(true)
         } else {
@@ -292,7 +895,9 @@ impl Database {
         &self,
         author: &str,
         content: &str,
+        website: Option<&str>,
         password: Option<&str>,
+        spam: &SpamVerdict,
     ) -> Result<Guestbook, sqlx::Error> {
         let password_hash = password.map(|p| self.hash_password(p));
 
@@ -300,21 +905,37 @@ impl Database {
             id: Uuid::new_v4().to_string(),
             author: author.to_string(),
             content: content.to_string(),
+            content_html: render_user_markdown(content),
             created_at: Utc::now(),
             password_hash: password_hash.clone(),
+            spam_score: spam.score,
+            is_spam: spam.is_spam,
+            website: website.map(str::to_string),
+            pinned: false,
+            reply: None,
+            reply_html: None,
+            replied_at: None,
         };
 
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO guestbook (id, author, content, created_at, password_hash) VALUES (?, ?, ?, ?, ?)"
+            "INSERT INTO guestbook (id, author, content, created_at, password_hash, spam_score, is_spam, website) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&entry.id)
         .bind(&entry.author)
         .bind(&entry.content)
         .bind(entry.created_at.to_rfc3339())
         .bind(&password_hash)
-        .execute(&self.pool)
+        .bind(entry.spam_score)
+        .bind(entry.is_spam)
+        .bind(&entry.website)
+        .execute(&mut *tx)
         .await?;
 
+        self.enqueue_notification(&mut tx, &NotificationEvent::from(&entry))
+            .await?;
+        tx.commit().await?;
+
         Ok(entry)
     }
 
@@ -324,29 +945,20 @@ impl Database {
     ) -> Result<Vec<Guestbook>, sqlx::Error> {
         let limit = limit.unwrap_or(50);
 
-        let rows = sqlx::query(
-            "SELECT id, author, content, created_at, password_hash FROM guestbook ORDER BY created_at DESC LIMIT ?"
-        )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
-
-        let entries = rows
-            .into_iter()
-            .map(|row| Guestbook {
-                id: row.get("id"),
-                author: row.get("author"),
-                content: row.get("content"),
-                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))
-                    .unwrap()
-                    .with_timezone(&Utc),
-                password_hash: row.get("password_hash"),
-            })
-            .collect();
+        let query = format!(
+            "SELECT {} FROM guestbook WHERE is_spam = 0 ORDER BY pinned DESC, created_at DESC LIMIT ?",
+            GUESTBOOK_COLUMNS
+        );
+        let rows = sqlx::query(&query)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(entries)
+        Ok(rows.into_iter().map(guestbook_from_row).collect())
     }
 
+    /// One page of public entries. Pinned entries come first whichever way
+    /// the rest are sorted.
     pub async fn get_guestbook_entries_paged(
         &self,
         offset: i32,
@@ -355,8 +967,8 @@ impl Database {
     ) -> Result<Vec<Guestbook>, sqlx::Error> {
         let order = if sort_asc { "ASC" } else { "DESC" };
         let query = format!(
-            "SELECT id, author, content, created_at, password_hash FROM guestbook ORDER BY created_at {} LIMIT ? OFFSET ?",
-            order
+            "SELECT {} FROM guestbook WHERE is_spam = 0 ORDER BY pinned DESC, created_at {} LIMIT ? OFFSET ?",
+            GUESTBOOK_COLUMNS, order
         );
 
         let rows = sqlx::query(&query)
@@ -365,34 +977,23 @@ impl Database {
             .fetch_all(&self.pool)
             .await?;
 
-        let entries = rows
-            .into_iter()
-            .map(|row| Guestbook {
-                id: row.get("id"),
-                author: row.get("author"),
-                content: row.get("content"),
-                created_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("created_at"))
-                    .unwrap()
-                    .with_timezone(&Utc),
-                password_hash: row.get("password_hash"),
-            })
-            .collect();
-
-        Ok(entries)
+        Ok(rows.into_iter().map(guestbook_from_row).collect())
     }
 
     pub async fn count_guestbook_entries(&self) -> Result<u32, sqlx::Error> {
-        let row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM guestbook")
+        let row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM guestbook WHERE is_spam = 0")
             .fetch_one(&self.pool)
             .await?;
         Ok(row.0 as u32)
     }
 
+    /// Replace the content if `password` matches, re-scored as `spam`.
     pub async fn update_guestbook_entry(
         &self,
         entry_id: &str,
         content: &str,
         password: &str,
+        spam: &SpamVerdict,
     ) -> Result<bool, sqlx::Error> {
         let row = sqlx::query("SELECT password_hash FROM guestbook WHERE id = ?")
             .bind(entry_id)
@@ -412,11 +1013,15 @@ impl Database {
                 return Ok(false);
             }
 
-            sqlx::query("UPDATE guestbook SET content = ? WHERE id = ?")
-                .bind(content)
-                .bind(entry_id)
-                .execute(&self.pool)
-                .await?;
+            sqlx::query(
+                "UPDATE guestbook SET content = ?, spam_score = ?, is_spam = ? WHERE id = ?",
+            )
+            .bind(content)
+            .bind(spam.score)
+            .bind(spam.is_spam)
+            .bind(entry_id)
+            .execute(&self.pool)
+            .await?;
 
             Ok(true)
         } else {
@@ -459,19 +1064,198 @@ impl Database {
     }
 
     // View count methods
-    pub async fn increment_view(&self, slug: &str) -> Result<u32, sqlx::Error> {
+
+    /// Count a view of `slug` on `date`, and a unique visitor the first time
+    /// `visitor_hash` is seen for that post and day. A repeat view within
+    /// `dedup_window` of the visitor's last counted view is not counted.
+    /// Returns whether the view counted and the post's lifetime view count.
+    pub async fn record_view(
+        &self,
+        slug: &str,
+        date: &str,
+        visitor_hash: &str,
+        now: DateTime<Utc>,
+        dedup_window: chrono::Duration,
+    ) -> Result<(bool, u32), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let last_viewed: Option<(String,)> = sqlx::query_as(
+            "SELECT last_viewed_at FROM post_view_visitors
+             WHERE post_slug = ? AND view_date = ? AND visitor_hash = ?",
+        )
+        .bind(slug)
+        .bind(date)
+        .bind(visitor_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let recent = last_viewed.as_ref().is_some_and(|(at,)| {
+            DateTime::parse_from_rfc3339(at)
+                .is_ok_and(|at| now - at.with_timezone(&Utc) < dedup_window)
+        });
+        let counted = !recent;
+
+        if counted {
+            let new_visitor = last_viewed.is_none();
+            sqlx::query(
+                "INSERT INTO post_view_visitors (post_slug, view_date, visitor_hash, last_viewed_at)
+                 VALUES (?, ?, ?, ?)
+                 ON CONFLICT(post_slug, view_date, visitor_hash)
+                 DO UPDATE SET last_viewed_at = excluded.last_viewed_at",
+            )
+            .bind(slug)
+            .bind(date)
+            .bind(visitor_hash)
+            .bind(now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+            "INSERT INTO post_views_daily (post_slug, view_date, views, visitors) VALUES (?, ?, 1, ?)
+             ON CONFLICT(post_slug, view_date)
+             DO UPDATE SET views = views + 1, visitors = visitors + excluded.visitors",
+            )
+            .bind(slug)
+            .bind(date)
+            .bind(new_visitor as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let total: (i64,) = sqlx::query_as(
+            "SELECT COALESCE(SUM(views), 0) FROM post_views_daily WHERE post_slug = ?",
+        )
+        .bind(slug)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((counted, total.0 as u32))
+    }
+
+    /// Delete per-visitor view rows that can no longer affect counting:
+    /// from a day before `today` and last counted before `cutoff`.
+    pub async fn prune_view_visitors(
+        &self,
+        today: &str,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM post_view_visitors WHERE view_date < ? AND last_viewed_at < ?",
+        )
+        .bind(today)
+        .bind(cutoff.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Tally a request to `slug` that was classified as automated.
+    pub async fn record_bot_hit(
+        &self,
+        slug: &str,
+        date: &str,
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT INTO post_views (post_slug, count) VALUES (?, 1) ON CONFLICT(post_slug) DO UPDATE SET count = count + 1",
+            "INSERT INTO bot_hits_daily (post_slug, hit_date, reason, hits) VALUES (?, ?, ?, 1)
+             ON CONFLICT(post_slug, hit_date, reason) DO UPDATE SET hits = hits + 1",
         )
         .bind(slug)
+        .bind(date)
+        .bind(reason)
         .execute(&self.pool)
         .await?;
+        Ok(())
+    }
+
+    /// Bot hits between `from` and `to` (inclusive) by reason, for one post
+    /// or all posts, most frequent first.
+    pub async fn get_bot_hits(
+        &self,
+        slug: Option<&str>,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<BotHits>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT reason, SUM(hits) AS hits
+             FROM bot_hits_daily
+             WHERE hit_date BETWEEN ? AND ? AND (? IS NULL OR post_slug = ?)
+             GROUP BY reason
+             ORDER BY hits DESC, reason",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(slug)
+        .bind(slug)
+        .fetch_all(&self.pool)
+        .await?;
 
-        let row: (i32,) = sqlx::query_as("SELECT count FROM post_views WHERE post_slug = ?")
-            .bind(slug)
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(row.0 as u32)
+        Ok(rows
+            .into_iter()
+            .map(|row| BotHits {
+                reason: row.get("reason"),
+                hits: row.get::<i64, _>("hits") as u32,
+            })
+            .collect())
+    }
+
+    /// Daily views and visitors between `from` and `to` (inclusive), for one
+    /// post or summed over all posts. Days without views are omitted.
+    pub async fn get_daily_views(
+        &self,
+        slug: Option<&str>,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<DailyViews>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT view_date, SUM(views) AS views, SUM(visitors) AS visitors
+             FROM post_views_daily
+             WHERE view_date BETWEEN ? AND ? AND (? IS NULL OR post_slug = ?)
+             GROUP BY view_date
+             ORDER BY view_date",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(slug)
+        .bind(slug)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DailyViews {
+                date: row.get("view_date"),
+                views: row.get::<i64, _>("views") as u32,
+                visitors: row.get::<i64, _>("visitors") as u32,
+            })
+            .collect())
+    }
+
+    /// The `limit` most viewed posts between `from` and `to` (inclusive).
+    pub async fn get_top_posts(
+        &self,
+        from: &str,
+        to: &str,
+        limit: u32,
+    ) -> Result<Vec<PostViews>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT post_slug, SUM(views) AS views, SUM(visitors) AS visitors
+             FROM post_views_daily
+             WHERE view_date BETWEEN ? AND ?
+             GROUP BY post_slug
+             ORDER BY views DESC, visitors DESC, post_slug
+             LIMIT ?",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PostViews {
+                slug: row.get("post_slug"),
+                views: row.get::<i64, _>("views") as u32,
+                visitors: row.get::<i64, _>("visitors") as u32,
+            })
+            .collect())
     }
 
     pub async fn get_view_counts(
@@ -484,7 +1268,8 @@ impl Database {
         }
         let placeholders: Vec<&str> = slugs.iter().map(|_| "?").collect();
         let query = format!(
-            "SELECT post_slug, count FROM post_views WHERE post_slug IN ({})",
+            "SELECT post_slug, SUM(views) AS count FROM post_views_daily
+             WHERE post_slug IN ({}) GROUP BY post_slug",
             placeholders.join(",")
         );
         let mut q = sqlx::query(&query);
@@ -494,61 +1279,72 @@ impl Database {
         let rows = q.fetch_all(&self.pool).await?;
         for row in rows {
             let slug: String = row.get("post_slug");
-            let count: i32 = row.get("count");
+            let count: i64 = row.get("count");
             map.insert(slug, count as u32);
         }
         Ok(map)
     }
 
-    // Like methods
-    pub async fn toggle_like(
+    // Reaction methods
+
+    /// Add or remove `kind` on `slug` for a server-issued visitor key (see
+    /// [`crate::visitor`]). Returns whether it is now set and the new count
+    /// of that kind on the post.
+    pub async fn toggle_reaction(
         &self,
         slug: &str,
-        client_id: &str,
+        kind: &str,
+        visitor_key: &str,
     ) -> Result<(bool, u32), sqlx::Error> {
-        let existing =
-            sqlx::query("SELECT 1 FROM post_likes WHERE post_slug = ? AND client_id = ?")
-                .bind(slug)
-                .bind(client_id)
-                .fetch_optional(&self.pool)
-                .await?;
-
-        if existing.is_some() {
-            sqlx::query("DELETE FROM post_likes WHERE post_slug = ? AND client_id = ?")
-                .bind(slug)
-                .bind(client_id)
-                .execute(&self.pool)
-                .await?;
-        } else {
+        let mut tx = self.pool.begin().await?;
+        let removed = sqlx::query(
+            "DELETE FROM post_reactions WHERE post_slug = ? AND kind = ? AND visitor_key = ?",
+        )
+        .bind(slug)
+        .bind(kind)
+        .bind(visitor_key)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if !removed {
             sqlx::query(
-                "INSERT INTO post_likes (post_slug, client_id, created_at) VALUES (?, ?, ?)",
+                "INSERT INTO post_reactions (post_slug, kind, visitor_key, created_at)
+                 VALUES (?, ?, ?, ?)",
             )
             .bind(slug)
-            .bind(client_id)
+            .bind(kind)
+            .bind(visitor_key)
             .bind(Utc::now().to_rfc3339())
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
         }
 
-        let row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM post_likes WHERE post_slug = ?")
-            .bind(slug)
-            .fetch_one(&self.pool)
-            .await?;
+        let row: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM post_reactions WHERE post_slug = ? AND kind = ?")
+                .bind(slug)
+                .bind(kind)
+                .fetch_one(&mut *tx)
+                .await?;
+        tx.commit().await?;
 
-        Ok((existing.is_none(), row.0 as u32))
+        Ok((!removed, row.0 as u32))
     }
 
-    pub async fn get_like_counts(
+    /// Reaction counts by kind for each of `slugs` that has any.
+    pub async fn get_reaction_counts(
         &self,
         slugs: &[String],
-    ) -> Result<HashMap<String, u32>, sqlx::Error> {
-        let mut map = HashMap::new();
+    ) -> Result<HashMap<String, HashMap<String, u32>>, sqlx::Error> {
+        let mut map: HashMap<String, HashMap<String, u32>> = HashMap::new();
         if slugs.is_empty() {
             return Ok(map);
         }
         let placeholders: Vec<&str> = slugs.iter().map(|_| "?").collect();
         let query = format!(
-            "SELECT post_slug, COUNT(*) as cnt FROM post_likes WHERE post_slug IN ({}) GROUP BY post_slug",
+            "SELECT post_slug, kind, COUNT(*) as cnt FROM post_reactions
+             WHERE post_slug IN ({}) GROUP BY post_slug, kind",
             placeholders.join(",")
         );
         let mut q = sqlx::query(&query);
@@ -558,100 +1354,1130 @@ impl Database {
         let rows = q.fetch_all(&self.pool).await?;
         for row in rows {
             let slug: String = row.get("post_slug");
-            let count: i32 = row.get("cnt");
-            map.insert(slug, count as u32);
+            let kind: String = row.get("kind");
+            let count: i64 = row.get("cnt");
+            map.entry(slug).or_default().insert(kind, count as u32);
         }
         Ok(map)
     }
 
-    pub async fn has_liked(&self, slug: &str, client_id: &str) -> Result<bool, sqlx::Error> {
-        let row = sqlx::query("SELECT 1 FROM post_likes WHERE post_slug = ? AND client_id = ?")
-            .bind(slug)
-            .bind(client_id)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(row.is_some())
+    /// Kinds the visitor has set on `slug`.
+    pub async fn get_visitor_reactions(
+        &self,
+        slug: &str,
+        visitor_key: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT kind FROM post_reactions WHERE post_slug = ? AND visitor_key = ?",
+        )
+        .bind(slug)
+        .bind(visitor_key)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|(kind,)| kind).collect())
     }
 
     // Visitor methods
-    pub async fn record_visit(&self, client_id: &str, date: &str) -> Result<(), sqlx::Error> {
+    /// Mark the visitor key as seen on `date`.
+    pub async fn record_visit(&self, visitor_key: &str, date: &str) -> Result<(), sqlx::Error> {
         sqlx::query("INSERT OR IGNORE INTO visitors (client_id, visited_date) VALUES (?, ?)")
-            .bind(client_id)
+            .bind(visitor_key)
             .bind(date)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// Visitors on `date` and all-time unique visitors. The all-time count
+    /// covers live rows plus visitors whose rows were all compacted away, so
+    /// a visitor who returns after compaction is counted again.
     pub async fn get_visitor_stats(&self, date: &str) -> Result<(u32, u32), sqlx::Error> {
-        let today: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM visitors WHERE visited_date = ?")
-            .bind(date)
-            .fetch_one(&self.pool)
-            .await?;
+        let today: (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM visitors WHERE visited_date = ?)
+                  + COALESCE((SELECT visitors FROM visitors_daily WHERE visited_date = ?), 0)",
+        )
+        .bind(date)
+        .bind(date)
+        .fetch_one(&self.pool)
+        .await?;
 
-        let total: (i32,) = sqlx::query_as("SELECT COUNT(DISTINCT client_id) FROM visitors")
-            .fetch_one(&self.pool)
-            .await?;
+        let total: (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(DISTINCT client_id) FROM visitors)
+                  + (SELECT COALESCE(SUM(retired), 0) FROM visitors_daily)",
+        )
+        .fetch_one(&self.pool)
+        .await?;
 
         Ok((today.0 as u32, total.0 as u32))
     }
 
-    // Password hashing functions
-    fn hash_password(&self, password: &str) -> String {
-        use argon2::password_hash::rand_core::OsRng;
-        use argon2::password_hash::SaltString;
-        use argon2::{Argon2, PasswordHasher};
-
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        argon2
-            .hash_password(password.as_bytes(), &salt)
-            .expect("password hashing failed")
-            .to_string()
+    /// Visitors per day between `from` and `to` (inclusive), from live and
+    /// compacted rows. Days without visitors are left out.
+    pub async fn get_daily_visitors(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<DailyVisitors>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT visited_date, SUM(visitors) AS visitors FROM (
+                 SELECT visited_date, COUNT(*) AS visitors FROM visitors
+                 WHERE visited_date BETWEEN ? AND ? GROUP BY visited_date
+                 UNION ALL
+                 SELECT visited_date, visitors FROM visitors_daily
+                 WHERE visited_date BETWEEN ? AND ?
+             )
+             GROUP BY visited_date
+             ORDER BY visited_date",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| DailyVisitors {
+                date: row.get("visited_date"),
+                visitors: row.get::<i64, _>("visitors") as u32,
+            })
+            .collect())
     }
 
-    fn verify_password(&self, password: &str, stored_hash: &str) -> bool {
-        if stored_hash.starts_with("$argon2") {
-            use argon2::password_hash::PasswordHash;
-            use argon2::{Argon2, PasswordVerifier};
-            let parsed = PasswordHash::new(stored_hash).ok();
-            parsed
-                .map(|h| {
-                    Argon2::default()
-                        .verify_password(password.as_bytes(), &h)
-                        .is_ok()
-                })
-                .unwrap_or(false)
-        } else {
-            // Legacy DefaultHasher format: verify for transparent migration
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            let mut hasher = DefaultHasher::new();
-            password.hash(&mut hasher);
-            format!("{:x}", hasher.finish()) == stored_hash
-        }
+    /// Fold per-visitor rows dated before `before` into `visitors_daily` and
+    /// delete them, along with rows whose date is not `YYYY-MM-DD`. Visitors
+    /// with no rows left are added to `retired` under their last day so the
+    /// all-time count is kept. Returns the number of rows removed.
+    pub async fn compact_visitors(&self, before: &str) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let invalid = sqlx::query(
+            "DELETE FROM visitors
+             WHERE visited_date NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]'",
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO visitors_daily (visited_date, visitors, retired)
+             SELECT visited_date, COUNT(*), 0 FROM visitors
+             WHERE visited_date < ? GROUP BY visited_date
+             ON CONFLICT(visited_date) DO UPDATE SET visitors = visitors + excluded.visitors",
+        )
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        // `WHERE true` keeps SQLite from reading ON CONFLICT as a join constraint
+        sqlx::query(
+            "INSERT INTO visitors_daily (visited_date, visitors, retired)
+             SELECT last_date, 0, COUNT(*) FROM (
+                 SELECT MAX(visited_date) AS last_date FROM visitors
+                 GROUP BY client_id HAVING last_date < ?
+             )
+             WHERE true
+             GROUP BY last_date
+             ON CONFLICT(visited_date) DO UPDATE SET retired = retired + excluded.retired",
+        )
+        .bind(before)
+        .execute(&mut *tx)
+        .await?;
+        let compacted = sqlx::query("DELETE FROM visitors WHERE visited_date < ?")
+            .bind(before)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(invalid.rows_affected() + compacted.rows_affected())
     }
 
-    async fn rehash_if_legacy(
+    // Admin session methods
+    pub async fn create_admin_session(
         &self,
-        table: &str,
-        id_column: &str,
-        id: &str,
-        password: &str,
-        stored_hash: &str,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
     ) -> Result<(), sqlx::Error> {
-        if !stored_hash.starts_with("$argon2") {
-            let new_hash = self.hash_password(password);
-            let query = format!(
-                "UPDATE {} SET password_hash = ? WHERE {} = ?",
-                table, id_column
-            );
-            sqlx::query(&query)
-                .bind(&new_hash)
-                .bind(id)
-                .execute(&self.pool)
+        sqlx::query("DELETE FROM admin_sessions WHERE expires_at < ?")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO admin_sessions (token_hash, created_at, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(token_hash)
+        .bind(Utc::now().to_rfc3339())
+        .bind(expires_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn is_admin_session_valid(&self, token_hash: &str) -> Result<bool, sqlx::Error> {
+        let row =
+            sqlx::query("SELECT 1 FROM admin_sessions WHERE token_hash = ? AND expires_at > ?")
+                .bind(token_hash)
+                .bind(Utc::now().to_rfc3339())
+                .fetch_optional(&self.pool)
                 .await?;
-        }
+        Ok(row.is_some())
+    }
+
+    pub async fn delete_admin_session(&self, token_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM admin_sessions WHERE token_hash = ?")
+            .bind(token_hash)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
+
+    // Password failure methods
+    /// Count a password attempt as a failure against each subject before the
+    /// password is checked, in one transaction with the lock check so that
+    /// parallel guesses cannot all slip in under a threshold. Nothing is
+    /// counted while any subject is locked. `lock_for` gives the lock length
+    /// for a subject's new failure count and threshold. Failures before
+    /// `forget_before` no longer count, and such stale rows are dropped once
+    /// their lock has passed.
+    pub async fn count_password_attempt(
+        &self,
+        subjects: &[(String, u32)],
+        now: DateTime<Utc>,
+        forget_before: DateTime<Utc>,
+        lock_for: impl Fn(u32, u32) -> Option<chrono::Duration>,
+    ) -> Result<PasswordAttempt, sqlx::Error> {
+        let (now_str, forget_before) = (now.to_rfc3339(), forget_before.to_rfc3339());
+        // IMMEDIATE takes the write lock up front, so the check and the
+        // count below cannot interleave with another attempt's
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let mut latest: Option<(String, DateTime<Utc>)> = None;
+        for (subject, _) in subjects {
+            let row: Option<(String,)> = sqlx::query_as(
+                "SELECT locked_until FROM password_failures WHERE subject = ? AND locked_until > ?",
+            )
+            .bind(subject)
+            .bind(&now_str)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let until = row.and_then(|(until,)| DateTime::parse_from_rfc3339(&until).ok());
+            if let Some(until) = until.map(|u| u.with_timezone(&Utc)) {
+                if latest.as_ref().is_none_or(|(_, l)| until > *l) {
+                    latest = Some((subject.clone(), until));
+                }
+            }
+        }
+        if let Some((subject, until)) = latest {
+            tx.rollback().await?;
+            return Ok(PasswordAttempt::Locked(subject, until));
+        }
+
+        sqlx::query(
+            "DELETE FROM password_failures
+             WHERE last_failure_at < ? AND (locked_until IS NULL OR locked_until < ?)",
+        )
+        .bind(&forget_before)
+        .bind(&now_str)
+        .execute(&mut *tx)
+        .await?;
+
+        let mut locked = Vec::new();
+        for (subject, threshold) in subjects {
+            let (failures,): (i64,) = sqlx::query_as(
+                "INSERT INTO password_failures (subject, failures, last_failure_at)
+                 VALUES (?, 1, ?)
+                 ON CONFLICT(subject) DO UPDATE SET
+                     failures = CASE WHEN last_failure_at < ? THEN 1 ELSE failures + 1 END,
+                     last_failure_at = excluded.last_failure_at
+                 RETURNING failures",
+            )
+            .bind(subject)
+            .bind(&now_str)
+            .bind(&forget_before)
+            .fetch_one(&mut *tx)
+            .await?;
+            let failures = failures as u32;
+            if let Some(duration) = lock_for(failures, *threshold) {
+                let until = now + duration;
+                sqlx::query("UPDATE password_failures SET locked_until = ? WHERE subject = ?")
+                    .bind(until.to_rfc3339())
+                    .bind(subject)
+                    .execute(&mut *tx)
+                    .await?;
+                locked.push((subject.clone(), failures, until));
+            }
+        }
+        tx.commit().await?;
+        Ok(PasswordAttempt::Counted(locked))
+    }
+
+    /// Take back one counted failure of `subject` after a correct password,
+    /// and the lock it set, if `locked_until` is still the subject's lock.
+    pub async fn uncount_password_failure(
+        &self,
+        subject: &str,
+        locked_until: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE password_failures SET
+                 failures = MAX(failures - 1, 0),
+                 locked_until = CASE WHEN locked_until = ? THEN NULL ELSE locked_until END
+             WHERE subject = ?",
+        )
+        .bind(locked_until.map(|until| until.to_rfc3339()))
+        .bind(subject)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether a comment or guestbook entry with `id` exists.
+    pub async fn item_exists(&self, kind: ItemKind, id: &str) -> Result<bool, sqlx::Error> {
+        let query = format!("SELECT 1 FROM {} WHERE id = ?", kind.table());
+        let row: Option<(i64,)> = sqlx::query_as(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn clear_password_failures(&self, subject: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM password_failures WHERE subject = ?")
+            .bind(subject)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Admin moderation methods (no password check; callers must be authenticated)
+    pub async fn get_all_comments_paged(
+        &self,
+        offset: i32,
+        limit: i32,
+    ) -> Result<Vec<Comment>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM comments ORDER BY created_at DESC LIMIT ? OFFSET ?",
+            COMMENT_COLUMNS
+        );
+        let rows = sqlx::query(&query)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(comment_from_row).collect())
+    }
+
+    /// All guestbook entries including ones held as spam, newest first.
+    pub async fn get_all_guestbook_entries_paged(
+        &self,
+        offset: i32,
+        limit: i32,
+    ) -> Result<Vec<Guestbook>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM guestbook ORDER BY created_at DESC LIMIT ? OFFSET ?",
+            GUESTBOOK_COLUMNS
+        );
+        let rows = sqlx::query(&query)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(guestbook_from_row).collect())
+    }
+
+    pub async fn count_all_guestbook_entries(&self) -> Result<u32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM guestbook")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.0 as u32)
+    }
+
+    pub async fn count_held_spam(&self) -> Result<u32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM comments WHERE is_spam = 1)
+                  + (SELECT COUNT(*) FROM guestbook WHERE is_spam = 1)",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.0 as u32)
+    }
+
+    pub async fn count_comments(&self) -> Result<u32, sqlx::Error> {
+        let row: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM comments")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.0 as u32)
+    }
+
+    pub async fn admin_delete_comment(&self, comment_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM comments WHERE id = ?")
+            .bind(comment_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Pin or unpin an entry. Returns false when it does not exist.
+    pub async fn set_guestbook_pinned(
+        &self,
+        entry_id: &str,
+        pinned: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE guestbook SET pinned = ? WHERE id = ?")
+            .bind(pinned)
+            .bind(entry_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set the owner's reply to an entry, or remove it with `None`.
+    /// Returns false when the entry does not exist.
+    pub async fn set_guestbook_reply(
+        &self,
+        entry_id: &str,
+        reply: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let replied_at = reply.map(|_| Utc::now().to_rfc3339());
+        let result = sqlx::query("UPDATE guestbook SET reply = ?, replied_at = ? WHERE id = ?")
+            .bind(reply)
+            .bind(replied_at)
+            .bind(entry_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn admin_delete_guestbook_entry(&self, entry_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM guestbook WHERE id = ?")
+            .bind(entry_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn clear_reactions(&self, slug: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM post_reactions WHERE post_slug = ?")
+            .bind(slug)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Views and reactions for every post that has either, most viewed first.
+    pub async fn get_post_stats(&self) -> Result<Vec<PostStats>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT s.slug AS slug,
+                    COALESCE((SELECT SUM(views) FROM post_views_daily WHERE post_slug = s.slug), 0) AS views,
+                    (SELECT COUNT(*) FROM post_reactions WHERE post_slug = s.slug) AS reactions
+             FROM (SELECT post_slug AS slug FROM post_views_daily
+                   UNION SELECT post_slug FROM post_reactions) s
+             ORDER BY views DESC, reactions DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PostStats {
+                slug: row.get("slug"),
+                views: row.get::<i64, _>("views") as u32,
+                reactions: row.get::<i64, _>("reactions") as u32,
+            })
+            .collect())
+    }
+
+    // Spam classifier methods
+
+    /// Author, content and previous training label of a moderated item.
+    pub async fn get_spam_training_item(
+        &self,
+        kind: ItemKind,
+        id: &str,
+    ) -> Result<Option<(String, String, Option<String>)>, sqlx::Error> {
+        let query = format!(
+            "SELECT author, content, spam_trained FROM {} WHERE id = ?",
+            kind.table()
+        );
+        let row = sqlx::query(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| (r.get("author"), r.get("content"), r.get("spam_trained"))))
+    }
+
+    pub async fn set_spam_decision(
+        &self,
+        kind: ItemKind,
+        id: &str,
+        is_spam: bool,
+    ) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "UPDATE {} SET is_spam = ?, spam_trained = ? WHERE id = ?",
+            kind.table()
+        );
+        sqlx::query(&query)
+            .bind(is_spam)
+            .bind(if is_spam { "spam" } else { "ham" })
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Add (`delta = 1`) or remove (`delta = -1`) one document's tokens from a class.
+    pub async fn adjust_spam_tokens(
+        &self,
+        tokens: &[String],
+        is_spam: bool,
+        delta: i32,
+    ) -> Result<(), sqlx::Error> {
+        let (column, label) = if is_spam {
+            ("spam_count", "spam")
+        } else {
+            ("ham_count", "ham")
+        };
+        let mut tx = self.pool.begin().await?;
+
+        let token_query = format!(
+            "INSERT INTO spam_tokens (token, {col}) VALUES (?, MAX(?, 0))
+             ON CONFLICT(token) DO UPDATE SET {col} = MAX({col} + ?, 0)",
+            col = column
+        );
+        for token in tokens {
+            sqlx::query(&token_query)
+                .bind(token)
+                .bind(delta)
+                .bind(delta)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO spam_corpus (label, docs) VALUES (?, MAX(?, 0))
+             ON CONFLICT(label) DO UPDATE SET docs = MAX(docs + ?, 0)",
+        )
+        .bind(label)
+        .bind(delta)
+        .bind(delta)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Per-token (spam, ham) counts for the given tokens.
+    pub async fn get_spam_token_counts(
+        &self,
+        tokens: &[String],
+    ) -> Result<HashMap<String, (u32, u32)>, sqlx::Error> {
+        let mut map = HashMap::new();
+        if tokens.is_empty() {
+            return Ok(map);
+        }
+        let placeholders: Vec<&str> = tokens.iter().map(|_| "?").collect();
+        let query = format!(
+            "SELECT token, spam_count, ham_count FROM spam_tokens WHERE token IN ({})",
+            placeholders.join(",")
+        );
+        let mut q = sqlx::query(&query);
+        for token in tokens {
+            q = q.bind(token);
+        }
+        for row in q.fetch_all(&self.pool).await? {
+            let spam: i64 = row.get("spam_count");
+            let ham: i64 = row.get("ham_count");
+            map.insert(row.get("token"), (spam as u32, ham as u32));
+        }
+        Ok(map)
+    }
+
+    /// Number of trained (spam, ham) documents.
+    pub async fn get_spam_corpus_sizes(&self) -> Result<(u32, u32), sqlx::Error> {
+        let rows = sqlx::query("SELECT label, docs FROM spam_corpus")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut sizes = (0, 0);
+        for row in rows {
+            let docs = row.get::<i64, _>("docs") as u32;
+            match row.get::<String, _>("label").as_str() {
+                "spam" => sizes.0 = docs,
+                _ => sizes.1 = docs,
+            }
+        }
+        Ok(sizes)
+    }
+
+    // Webmention methods
+
+    /// Record a received Webmention as pending verification and return its id.
+    pub async fn upsert_webmention(
+        &self,
+        source: &str,
+        target: &str,
+        post_slug: &str,
+    ) -> Result<String, sqlx::Error> {
+        let now = Utc::now().to_rfc3339();
+        let row = sqlx::query(
+            "INSERT INTO webmentions (id, source, target, post_slug, status, created_at, updated_at)
+             VALUES (?, ?, ?, ?, 'pending', ?, ?)
+             ON CONFLICT(source, target) DO UPDATE SET updated_at = excluded.updated_at
+             RETURNING id",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(source)
+        .bind(target)
+        .bind(post_slug)
+        .bind(&now)
+        .bind(&now)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get("id"))
+    }
+
+    pub async fn mark_webmention_verified(
+        &self,
+        id: &str,
+        author_name: Option<&str>,
+        title: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webmentions SET status = 'verified', author_name = ?, title = ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(author_name)
+        .bind(title)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Drop a mention whose source no longer links to us (or never did).
+    pub async fn delete_webmention(&self, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM webmentions WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_webmentions_for_post(
+        &self,
+        post_slug: &str,
+    ) -> Result<Vec<Webmention>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, source, target, post_slug, author_name, title, created_at FROM webmentions
+             WHERE post_slug = ? AND status = 'verified' ORDER BY created_at ASC",
+        )
+        .bind(post_slug)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Webmention {
+                id: row.get("id"),
+                source: row.get("source"),
+                target: row.get("target"),
+                post_slug: row.get("post_slug"),
+                author_name: row.get("author_name"),
+                title: row.get("title"),
+                created_at: parse_timestamp(&row, "created_at"),
+            })
+            .collect())
+    }
+
+    pub async fn has_sent_webmention(
+        &self,
+        post_slug: &str,
+        target: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT 1 FROM webmentions_sent WHERE post_slug = ? AND target = ?")
+            .bind(post_slug)
+            .bind(target)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn record_sent_webmention(
+        &self,
+        post_slug: &str,
+        target: &str,
+        endpoint: Option<&str>,
+        status_code: Option<u16>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO webmentions_sent (post_slug, target, endpoint, status_code, sent_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(post_slug)
+        .bind(target)
+        .bind(endpoint)
+        .bind(status_code.map(i64::from))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ActivityPub methods
+
+    /// The stored actor key pair as (private PEM, public PEM).
+    pub async fn get_activitypub_key(&self) -> Result<Option<(String, String)>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT private_key_pem, public_key_pem FROM activitypub_keys WHERE id = 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| (row.get("private_key_pem"), row.get("public_key_pem"))))
+    }
+
+    /// The secret stored under `name`, storing `generated` first if there is
+    /// none yet. Concurrent callers all get the value that won.
+    pub async fn get_or_create_secret(
+        &self,
+        name: &str,
+        generated: &str,
+    ) -> Result<String, sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO secrets (name, value, created_at) VALUES (?, ?, ?)")
+            .bind(name)
+            .bind(generated)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        let row: (String,) = sqlx::query_as("SELECT value FROM secrets WHERE name = ?")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.0)
+    }
+
+    /// Store a freshly generated key pair unless one already exists.
+    pub async fn store_activitypub_key(
+        &self,
+        private_key_pem: &str,
+        public_key_pem: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO activitypub_keys (id, private_key_pem, public_key_pem, created_at)
+             VALUES (1, ?, ?, ?)",
+        )
+        .bind(private_key_pem)
+        .bind(public_key_pem)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn upsert_follower(&self, follower: &Follower) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO activitypub_followers (actor_id, inbox, shared_inbox, created_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(actor_id) DO UPDATE SET inbox = excluded.inbox, shared_inbox = excluded.shared_inbox",
+        )
+        .bind(&follower.actor_id)
+        .bind(&follower.inbox)
+        .bind(&follower.shared_inbox)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_follower(&self, actor_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM activitypub_followers WHERE actor_id = ?")
+            .bind(actor_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn count_followers(&self) -> Result<u32, sqlx::Error> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM activitypub_followers")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get::<i64, _>("count") as u32)
+    }
+
+    /// Distinct inboxes to deliver to, preferring each server's shared inbox.
+    pub async fn get_follower_inboxes(&self) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT COALESCE(shared_inbox, inbox) as inbox FROM activitypub_followers",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.get("inbox")).collect())
+    }
+
+    pub async fn count_federated_posts(&self) -> Result<u32, sqlx::Error> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM activitypub_published")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get::<i64, _>("count") as u32)
+    }
+
+    /// Mark a post as announced and queue `activity` for each of `inboxes`
+    /// in the same transaction; returns false if it already was announced.
+    pub async fn record_federated_post(
+        &self,
+        post_slug: &str,
+        inboxes: &[String],
+        activity: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO activitypub_published (post_slug, published_at) VALUES (?, ?)",
+        )
+        .bind(post_slug)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let now = Utc::now().timestamp();
+        for inbox in inboxes {
+            sqlx::query(
+                "INSERT INTO activitypub_deliveries (inbox, activity, created_at, next_attempt_at) VALUES (?, ?, ?, ?)",
+            )
+            .bind(inbox)
+            .bind(activity)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Queued deliveries whose next attempt is due, oldest first.
+    pub async fn get_due_deliveries(
+        &self,
+        now: i64,
+        limit: i32,
+    ) -> Result<Vec<QueuedDelivery>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, inbox, activity, attempts FROM activitypub_deliveries
+             WHERE failed_at IS NULL AND next_attempt_at <= ?
+             ORDER BY id LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| QueuedDelivery {
+                id: row.get("id"),
+                inbox: row.get("inbox"),
+                activity: row.get("activity"),
+                attempts: row.get::<i64, _>("attempts") as u32,
+            })
+            .collect())
+    }
+
+    pub async fn complete_delivery(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM activitypub_deliveries WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed delivery. With `retry_at` it is retried then, without
+    /// it it is marked as permanently failed.
+    pub async fn fail_delivery(
+        &self,
+        id: i64,
+        retry_at: Option<i64>,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE activitypub_deliveries
+             SET attempts = attempts + 1,
+                 next_attempt_at = COALESCE(?, next_attempt_at),
+                 failed_at = CASE WHEN ? IS NULL THEN ? ELSE NULL END,
+                 last_error = ?
+             WHERE id = ?",
+        )
+        .bind(retry_at)
+        .bind(retry_at)
+        .bind(Utc::now().timestamp())
+        .bind(error)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert or update a reply; an existing reply keeps its author.
+    pub async fn upsert_federated_reply(&self, reply: &FederatedReply) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO activitypub_replies (id, post_slug, actor_id, author_name, url, content_html, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET content_html = excluded.content_html, author_name = excluded.author_name
+             WHERE activitypub_replies.actor_id = excluded.actor_id",
+        )
+        .bind(&reply.id)
+        .bind(&reply.post_slug)
+        .bind(&reply.actor_id)
+        .bind(&reply.author_name)
+        .bind(&reply.url)
+        .bind(&reply.content_html)
+        .bind(reply.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Delete a reply, but only on behalf of the actor who wrote it.
+    pub async fn delete_federated_reply(
+        &self,
+        id: &str,
+        actor_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM activitypub_replies WHERE id = ? AND actor_id = ?")
+            .bind(id)
+            .bind(actor_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_federated_replies_for_post(
+        &self,
+        post_slug: &str,
+    ) -> Result<Vec<FederatedReply>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, post_slug, actor_id, author_name, url, content_html, created_at
+             FROM activitypub_replies WHERE post_slug = ? ORDER BY created_at ASC",
+        )
+        .bind(post_slug)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| FederatedReply {
+                id: row.get("id"),
+                post_slug: row.get("post_slug"),
+                actor_id: row.get("actor_id"),
+                author_name: row.get("author_name"),
+                url: row.get("url"),
+                content_html: row.get("content_html"),
+                created_at: parse_timestamp(&row, "created_at"),
+            })
+            .collect())
+    }
+
+    /// Submitted version (post `updated_at`) of every URL sent to IndexNow.
+    pub async fn get_indexnow_versions(&self) -> Result<HashMap<String, String>, sqlx::Error> {
+        let rows = sqlx::query("SELECT url, version FROM indexnow_submissions")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("url"), row.get("version")))
+            .collect())
+    }
+
+    pub async fn record_indexnow_submissions(
+        &self,
+        submitted: &[(String, String)],
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        for (url, version) in submitted {
+            sqlx::query(
+                "INSERT INTO indexnow_submissions (url, version, submitted_at) VALUES (?, ?, ?)
+                 ON CONFLICT(url) DO UPDATE SET version = excluded.version, submitted_at = excluded.submitted_at",
+            )
+            .bind(url)
+            .bind(version)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    // Notification queue methods
+
+    async fn enqueue_notification(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+        event: &NotificationEvent,
+    ) -> Result<(), sqlx::Error> {
+        if self.notification_channels.is_empty() {
+            return Ok(());
+        }
+        let payload = serde_json::to_string(event).map_err(|e| sqlx::Error::Encode(e.into()))?;
+        let now = Utc::now().timestamp();
+        for channel in self.notification_channels.iter() {
+            sqlx::query(
+                "INSERT INTO notification_queue (channel, payload, created_at, next_attempt_at) VALUES (?, ?, ?, ?)",
+            )
+            .bind(channel)
+            .bind(&payload)
+            .bind(now)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Queued notifications for a channel whose next attempt is due, oldest first.
+    pub async fn get_due_notifications(
+        &self,
+        channel: &str,
+        now: i64,
+        limit: i32,
+    ) -> Result<Vec<QueuedNotification>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, payload, created_at, attempts FROM notification_queue
+             WHERE channel = ? AND failed_at IS NULL AND next_attempt_at <= ?
+             ORDER BY id LIMIT ?",
+        )
+        .bind(channel)
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut queued = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: i64 = row.get("id");
+            match serde_json::from_str(&row.get::<String, _>("payload")) {
+                Ok(event) => queued.push(QueuedNotification {
+                    id,
+                    event,
+                    created_at: row.get("created_at"),
+                    attempts: row.get::<i64, _>("attempts") as u32,
+                }),
+                Err(e) => {
+                    tracing::error!("Dropping unreadable notification id={}: {}", id, e);
+                    self.complete_notifications(&[id]).await?;
+                }
+            }
+        }
+        Ok(queued)
+    }
+
+    /// Remove delivered notifications from the queue.
+    pub async fn complete_notifications(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        for id in ids {
+            sqlx::query("DELETE FROM notification_queue WHERE id = ?")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Record a failed delivery. With `retry_at` the rows are retried then,
+    /// without it they are marked as permanently failed.
+    pub async fn fail_notifications(
+        &self,
+        ids: &[i64],
+        retry_at: Option<i64>,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now().timestamp();
+        for id in ids {
+            sqlx::query(
+                "UPDATE notification_queue
+                 SET attempts = attempts + 1,
+                     next_attempt_at = COALESCE(?, next_attempt_at),
+                     failed_at = CASE WHEN ? IS NULL THEN ? ELSE NULL END,
+                     last_error = ?
+                 WHERE id = ?",
+            )
+            .bind(retry_at)
+            .bind(retry_at)
+            .bind(now)
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Password hashing functions
+    fn hash_password(&self, password: &str) -> String {
+        use argon2::password_hash::rand_core::OsRng;
+        use argon2::password_hash::SaltString;
+        use argon2::{Argon2, PasswordHasher};
+
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2 = Argon2::default();
+        argon2
+            .hash_password(password.as_bytes(), &salt)
+            .expect("password hashing failed")
+            .to_string()
+    }
+
+    fn verify_password(&self, password: &str, stored_hash: &str) -> bool {
+        if stored_hash.starts_with("$argon2") {
+            use argon2::password_hash::PasswordHash;
+            use argon2::{Argon2, PasswordVerifier};
+            let parsed = PasswordHash::new(stored_hash).ok();
+            parsed
+                .map(|h| {
+                    Argon2::default()
+                        .verify_password(password.as_bytes(), &h)
+                        .is_ok()
+                })
+                .unwrap_or(false)
+        } else {
+            // Legacy DefaultHasher format: verify for transparent migration
+            use std::collections::hash_map::DefaultHasher;
+            use std::hash::{Hash, Hasher};
+            let mut hasher = DefaultHasher::new();
+            password.hash(&mut hasher);
+            format!("{:x}", hasher.finish()) == stored_hash
+        }
+    }
+
+    async fn rehash_if_legacy(
+        &self,
+        table: &str,
+        id_column: &str,
+        id: &str,
+        password: &str,
+        stored_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        if !stored_hash.starts_with("$argon2") {
+            let new_hash = self.hash_password(password);
+            let query = format!(
+                "UPDATE {} SET password_hash = ? WHERE {} = ?",
+                table, id_column
+            );
+            sqlx::query(&query)
+                .bind(&new_hash)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// URL of a fresh in-memory database for tests. It lives as long as some
+/// connection to it stays open, so nothing is left behind on disk.
+#[cfg(test)]
+pub(crate) fn test_db_url() -> String {
+    format!(
+        "sqlite:file:blog-test-{}?mode=memory&cache=shared",
+        Uuid::new_v4()
+    )
+}
+
+/// A fresh, migrated in-memory database for tests.
+#[cfg(test)]
+pub(crate) async fn test_db() -> Database {
+    Database::new(&test_db_url()).await.unwrap()
 }
@@ -0,0 +1,660 @@
+//! Format-agnostic feed model built from posts, with Atom, RSS 2.0 and
+//! JSON Feed 1.1 serializers. Entries carry both the post description and
+//! its full HTML; [`Feed::full_content`] decides which one readers get.
+
+use crate::{
+    i18n::Lang,
+    post::{dedup_refs_by_translation, Post, PostType},
+    SITE_DESCRIPTION, SITE_URL,
+};
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use regex::{Captures, Regex};
+use serde_json::json;
+use std::sync::OnceLock;
+
+/// Number of most recent posts in a feed.
+pub const FEED_SIZE: usize = 20;
+const FEED_AUTHOR: &str = "Han Damin";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Rss => "rss",
+            FeedFormat::Json => "json",
+        }
+    }
+
+    /// File name of the feed under its base path.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "feed.xml",
+            FeedFormat::Rss => "rss.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+
+    pub fn render(self, feed: &Feed) -> String {
+        match self {
+            FeedFormat::Atom => render_atom(feed),
+            FeedFormat::Rss => render_rss(feed),
+            FeedFormat::Json => render_json(feed),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub url: String,
+    pub title: String,
+    pub summary: String,
+    /// Post HTML with relative `src`/`href` made absolute.
+    pub content_html: String,
+    pub author: String,
+    pub published: DateTime<FixedOffset>,
+    pub updated: DateTime<FixedOffset>,
+    /// Post type followed by the post's tags.
+    pub categories: Vec<String>,
+    pub lang: Lang,
+}
+
+impl FeedEntry {
+    pub fn from_post(post: &Post) -> Self {
+        let mut categories = vec![post.post_type.to_string().to_lowercase()];
+        categories.extend(post.metadata.tags.iter().cloned());
+        let url = format!("{}/post/{}", SITE_URL, post.slug);
+        FeedEntry {
+            content_html: absolutize_urls(&post.content, &url),
+            url,
+            title: post.metadata.title.clone(),
+            summary: post.metadata.description.clone(),
+            author: post.metadata.author.clone(),
+            published: post.metadata.created_at,
+            updated: post.metadata.updated_at,
+            categories,
+            lang: post.lang,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    /// HTML page the feed mirrors.
+    pub home_url: String,
+    /// Path the feed files live under, without a trailing slash (`""` for the site root).
+    pub base_path: String,
+    /// Language filter, carried into self links and the ETag.
+    pub lang: Option<Lang>,
+    /// Include each post's full HTML rather than only its description.
+    pub full_content: bool,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    /// The most recent posts, one per translation, optionally limited to `lang`.
+    pub fn new<'a>(
+        title: &str,
+        description: &str,
+        home_url: &str,
+        base_path: &str,
+        posts: impl Iterator<Item = &'a Post>,
+        lang: Option<Lang>,
+    ) -> Self {
+        let mut posts = dedup_refs_by_translation(
+            posts.filter(|p| lang.map(|l| p.lang == l).unwrap_or(true)),
+            lang.unwrap_or(Lang::En),
+        );
+        posts.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
+
+        Feed {
+            title: title.to_string(),
+            description: description.to_string(),
+            home_url: home_url.to_string(),
+            base_path: base_path.to_string(),
+            lang,
+            full_content: false,
+            entries: posts
+                .into_iter()
+                .take(FEED_SIZE)
+                .map(FeedEntry::from_post)
+                .collect(),
+        }
+    }
+
+    /// The site-wide feed of every post.
+    pub fn site(posts: &[Post], lang: Option<Lang>) -> Self {
+        Self::new(
+            "miniex.blog",
+            SITE_DESCRIPTION,
+            SITE_URL,
+            "",
+            posts.iter(),
+            lang,
+        )
+    }
+
+    /// Posts of one type, e.g. `/blog/feed.xml`.
+    pub fn for_type(posts: &[Post], post_type: &PostType, lang: Option<Lang>) -> Self {
+        let name = post_type.to_string().to_lowercase();
+        Self::new(
+            &format!("miniex::{}", name),
+            &format!("{} posts on miniex.blog", post_type),
+            &format!("{}/{}", SITE_URL, name),
+            &type_feed_path(post_type),
+            posts.iter().filter(|p| &p.post_type == post_type),
+            lang,
+        )
+    }
+
+    /// Posts carrying `tag`, across all post types. `None` if there are none.
+    pub fn for_tag(posts: &[Post], tag: &str, lang: Option<Lang>) -> Option<Self> {
+        let tagged = || {
+            posts
+                .iter()
+                .filter(|p| p.metadata.tags.iter().any(|t| t == tag))
+        };
+        tagged().next()?;
+        Some(Self::new(
+            &format!("miniex.blog #{}", tag),
+            &format!("Posts tagged {} on miniex.blog", tag),
+            &format!("{}/blog?category={}", SITE_URL, path_segment(tag)),
+            &tag_feed_path(tag),
+            tagged(),
+            lang,
+        ))
+    }
+
+    /// Posts in one series. `None` if the series has no posts.
+    pub fn for_series(
+        posts: &[Post],
+        name: &str,
+        description: Option<&str>,
+        lang: Option<Lang>,
+    ) -> Option<Self> {
+        let in_series = || {
+            posts
+                .iter()
+                .filter(|p| p.metadata.series.as_deref() == Some(name))
+        };
+        in_series().next()?;
+        Some(Self::new(
+            &format!("miniex::series::{}", name),
+            description.unwrap_or(&format!("{} series on miniex.blog", name)),
+            &format!("{}/series/{}", SITE_URL, path_segment(name)),
+            &series_feed_path(name),
+            in_series(),
+            lang,
+        ))
+    }
+
+    pub fn with_full_content(mut self, full_content: bool) -> Self {
+        self.full_content = full_content;
+        self
+    }
+
+    pub fn updated(&self) -> Option<DateTime<FixedOffset>> {
+        self.entries.iter().map(|e| e.updated).max()
+    }
+
+    pub fn self_url(&self, format: FeedFormat) -> String {
+        let url = format!("{}{}/{}", SITE_URL, self.base_path, format.file_name());
+        match self.lang {
+            Some(lang) => format!("{}?lang={}", url, lang.as_str()),
+            None => url,
+        }
+    }
+
+    /// Changes whenever an entry is added or updated, and differs between
+    /// formats, content modes, scopes and language filters.
+    pub fn etag(&self, format: FeedFormat) -> String {
+        format!(
+            "\"{}{}{}-{}-{}-{}\"",
+            format.as_str(),
+            if self.full_content { "-full" } else { "" },
+            self.base_path.replace('/', "-"),
+            self.lang.map(Lang::as_str).unwrap_or("all"),
+            self.entries.len(),
+            self.updated().map(|u| u.timestamp()).unwrap_or(0)
+        )
+    }
+}
+
+/// Percent-encode one URL path segment.
+fn path_segment(s: &str) -> String {
+    let mut url = url::Url::parse("http://localhost/").unwrap();
+    url.path_segments_mut().unwrap().pop_if_empty().push(s);
+    url.path()[1..].to_string()
+}
+
+/// Base path of the feed for a post type, e.g. `/blog`.
+pub fn type_feed_path(post_type: &PostType) -> String {
+    format!("/{}", post_type.to_string().to_lowercase())
+}
+
+pub fn tag_feed_path(tag: &str) -> String {
+    format!("/tags/{}", path_segment(tag))
+}
+
+pub fn series_feed_path(name: &str) -> String {
+    format!("/series/{}", path_segment(name))
+}
+
+/// Resolve relative `src` and `href` attribute values against `base_url`, so
+/// images and links keep working when the HTML is read outside the site.
+pub fn absolutize_urls(html: &str, base_url: &str) -> String {
+    static ATTR_RE: OnceLock<Regex> = OnceLock::new();
+    let re = ATTR_RE.get_or_init(|| Regex::new(r#"(\s(?:src|href)=)"([^"]*)""#).unwrap());
+    let Ok(base) = url::Url::parse(base_url) else {
+        return html.to_string();
+    };
+
+    re.replace_all(html, |caps: &Captures| {
+        let value = &caps[2];
+        if url::Url::parse(value).is_ok() {
+            return caps[0].to_string();
+        }
+        match base.join(value) {
+            Ok(absolute) => format!("{}\"{}\"", &caps[1], absolute),
+            Err(_) => caps[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+/// RFC 3339 with the post's own offset, e.g. `2024-01-01T09:00:00+09:00`.
+fn rfc3339(date: DateTime<FixedOffset>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn render_atom(feed: &Feed) -> String {
+    let updated = feed
+        .updated()
+        .map(rfc3339)
+        .unwrap_or_else(|| "2024-01-01T00:00:00Z".to_string());
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    match feed.lang {
+        Some(lang) => xml.push_str(&format!(
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"{}\">\n",
+            lang.as_str()
+        )),
+        None => xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n"),
+    }
+    xml.push_str(&format!("  <title>{}</title>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!(
+        "  <subtitle>{}</subtitle>\n",
+        xml_escape(&feed.description)
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n",
+        xml_escape(&feed.self_url(FeedFormat::Atom))
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"alternate\" type=\"text/html\"/>\n",
+        xml_escape(&feed.home_url)
+    ));
+    xml.push_str(&format!("  <id>{}/</id>\n", xml_escape(&feed.home_url)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str("  <author>\n");
+    xml.push_str(&format!("    <name>{}</name>\n", FEED_AUTHOR));
+    xml.push_str("  </author>\n");
+
+    for entry in &feed.entries {
+        xml.push_str(&format!("  <entry xml:lang=\"{}\">\n", entry.lang.as_str()));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            xml_escape(&entry.title)
+        ));
+        xml.push_str(&format!(
+            "    <link href=\"{}\" rel=\"alternate\" type=\"text/html\"/>\n",
+            xml_escape(&entry.url)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            rfc3339(entry.published)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            rfc3339(entry.updated)
+        ));
+        xml.push_str("    <author>\n");
+        xml.push_str(&format!(
+            "      <name>{}</name>\n",
+            xml_escape(&entry.author)
+        ));
+        xml.push_str("    </author>\n");
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            xml_escape(&entry.summary)
+        ));
+        if feed.full_content {
+            xml.push_str(&format!(
+                "    <content type=\"html\">{}</content>\n",
+                xml_escape(&entry.content_html)
+            ));
+        }
+        for category in &entry.categories {
+            xml.push_str(&format!(
+                "    <category term=\"{}\"/>\n",
+                xml_escape(category)
+            ));
+        }
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn render_rss(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!(
+        "    <link>{}</link>\n",
+        xml_escape(&feed.home_url)
+    ));
+    xml.push_str(&format!(
+        "    <description>{}</description>\n",
+        xml_escape(&feed.description)
+    ));
+    xml.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        xml_escape(&feed.self_url(FeedFormat::Rss))
+    ));
+    if let Some(lang) = feed.lang {
+        xml.push_str(&format!("    <language>{}</language>\n", lang.as_str()));
+    }
+    if let Some(updated) = feed.updated() {
+        xml.push_str(&format!(
+            "    <lastBuildDate>{}</lastBuildDate>\n",
+            updated.to_rfc2822()
+        ));
+    }
+
+    for entry in &feed.entries {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <title>{}</title>\n",
+            xml_escape(&entry.title)
+        ));
+        xml.push_str(&format!("      <link>{}</link>\n", xml_escape(&entry.url)));
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"true\">{}</guid>\n",
+            xml_escape(&entry.url)
+        ));
+        xml.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            entry.published.to_rfc2822()
+        ));
+        xml.push_str(&format!(
+            "      <dc:creator>{}</dc:creator>\n",
+            xml_escape(&entry.author)
+        ));
+        xml.push_str(&format!(
+            "      <description>{}</description>\n",
+            xml_escape(&entry.summary)
+        ));
+        if feed.full_content {
+            xml.push_str(&format!(
+                "      <content:encoded>{}</content:encoded>\n",
+                xml_escape(&entry.content_html)
+            ));
+        }
+        for category in &entry.categories {
+            xml.push_str(&format!(
+                "      <category>{}</category>\n",
+                xml_escape(category)
+            ));
+        }
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+fn render_json(feed: &Feed) -> String {
+    let items: Vec<_> = feed
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut item = json!({
+                "id": entry.url,
+                "url": entry.url,
+                "title": entry.title,
+                "summary": entry.summary,
+                "date_published": rfc3339(entry.published),
+                "date_modified": rfc3339(entry.updated),
+                "authors": [{ "name": entry.author }],
+                "tags": entry.categories,
+                "language": entry.lang.as_str(),
+            });
+            if feed.full_content {
+                item["content_html"] = entry.content_html.as_str().into();
+            } else {
+                item["content_text"] = entry.summary.as_str().into();
+            }
+            item
+        })
+        .collect();
+
+    let mut doc = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": feed.title,
+        "home_page_url": feed.home_url,
+        "feed_url": feed.self_url(FeedFormat::Json),
+        "description": feed.description,
+        "authors": [{ "name": FEED_AUTHOR }],
+        "items": items,
+    });
+    if let Some(lang) = feed.lang {
+        doc["language"] = lang.as_str().into();
+    }
+    serde_json::to_string_pretty(&doc).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::sample_post;
+
+    fn posts() -> Vec<Post> {
+        let mut older = sample_post("older");
+        older.metadata.created_at =
+            DateTime::parse_from_rfc3339("2023-06-01T00:00:00+09:00").unwrap();
+        older.metadata.title = "Fish & <Chips>".to_string();
+
+        let mut translated = sample_post("hello");
+        translated.lang = Lang::Ko;
+        translated.slug = "hello-ko".to_string();
+
+        vec![older, sample_post("hello"), translated]
+    }
+
+    #[test]
+    fn test_feed_model_dedups_and_orders() {
+        let feed = Feed::site(&posts(), None);
+        let urls: Vec<_> = feed.entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://miniex.blog/post/hello",
+                "https://miniex.blog/post/older"
+            ]
+        );
+        assert_eq!(feed.entries[0].categories, vec!["blog", "rust"]);
+
+        let ko = Feed::site(&posts(), Some(Lang::Ko));
+        assert_eq!(ko.entries.len(), 1);
+        assert_eq!(ko.entries[0].url, "https://miniex.blog/post/hello-ko");
+        assert_eq!(
+            ko.self_url(FeedFormat::Rss),
+            "https://miniex.blog/rss.xml?lang=ko"
+        );
+    }
+
+    #[test]
+    fn test_scoped_feeds() {
+        let mut posts = posts();
+        let mut diary = sample_post("diary");
+        diary.post_type = PostType::Diary;
+        diary.metadata.tags = vec!["life log".to_string()];
+        diary.metadata.series = Some("양자역학".to_string());
+        posts.push(diary);
+
+        let blog = Feed::for_type(&posts, &PostType::Blog, None);
+        assert_eq!(blog.entries.len(), 2);
+        assert_eq!(
+            blog.self_url(FeedFormat::Atom),
+            "https://miniex.blog/blog/feed.xml"
+        );
+
+        let tag = Feed::for_tag(&posts, "life log", None).unwrap();
+        assert_eq!(tag.entries.len(), 1);
+        assert_eq!(
+            tag.self_url(FeedFormat::Atom),
+            "https://miniex.blog/tags/life%20log/feed.xml"
+        );
+        assert!(Feed::for_tag(&posts, "missing", None).is_none());
+
+        let series = Feed::for_series(&posts, "양자역학", None, None).unwrap();
+        assert_eq!(series.entries[0].url, "https://miniex.blog/post/diary");
+        assert_eq!(
+            series.self_url(FeedFormat::Atom),
+            "https://miniex.blog/series/%EC%96%91%EC%9E%90%EC%97%AD%ED%95%99/feed.xml"
+        );
+
+        let etags: std::collections::HashSet<_> = [&blog, &tag, &series, &Feed::site(&posts, None)]
+            .iter()
+            .map(|f| f.etag(FeedFormat::Atom))
+            .collect();
+        assert_eq!(etags.len(), 4);
+    }
+
+    #[test]
+    fn test_absolutize_urls() {
+        let html = r##"<a href="/post/other">o</a><img src="img/a.png" alt=""><a href="#h1">h</a><a href="https://example.com/x">x</a><a href="mailto:me@example.com">m</a>"##;
+        let out = absolutize_urls(html, "https://miniex.blog/post/hello");
+        assert!(out.contains(r#"href="https://miniex.blog/post/other""#));
+        assert!(out.contains(r#"src="https://miniex.blog/post/img/a.png""#));
+        assert!(out.contains(r#"href="https://miniex.blog/post/hello#h1""#));
+        assert!(out.contains(r#"href="https://example.com/x""#));
+        assert!(out.contains(r#"href="mailto:me@example.com""#));
+    }
+
+    #[test]
+    fn test_full_content_and_timestamps() {
+        let mut post = sample_post("hello");
+        post.content = r#"<p><img src="/static/a.png"></p>"#.to_string();
+        post.lang = Lang::Ko;
+        let summary = Feed::site(&[post.clone()], None);
+        let full = Feed::site(&[post], None).with_full_content(true);
+        assert_ne!(summary.etag(FeedFormat::Atom), full.etag(FeedFormat::Atom));
+
+        let atom = FeedFormat::Atom.render(&summary);
+        assert!(atom.contains("<published>2024-01-01T00:00:00+09:00</published>"));
+        assert!(atom.contains("<entry xml:lang=\"ko\">"));
+        assert!(!atom.contains("<content"));
+
+        let atom = FeedFormat::Atom.render(&full);
+        assert!(atom.contains(
+            "<content type=\"html\">&lt;p&gt;&lt;img src=&quot;https://miniex.blog/static/a.png&quot;&gt;&lt;/p&gt;</content>"
+        ));
+        assert!(FeedFormat::Rss.render(&full).contains("<content:encoded>"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&FeedFormat::Json.render(&full)).unwrap();
+        let item = &json["items"][0];
+        assert_eq!(item["date_published"], "2024-01-01T00:00:00+09:00");
+        assert!(item["content_html"]
+            .as_str()
+            .unwrap()
+            .contains("https://miniex.blog/static/a.png"));
+        assert!(item.get("content_text").is_none());
+    }
+
+    #[test]
+    fn test_etag_differs_by_format_and_lang() {
+        let posts = posts();
+        let all = Feed::site(&posts, None);
+        let ko = Feed::site(&posts, Some(Lang::Ko));
+        assert_ne!(all.etag(FeedFormat::Atom), all.etag(FeedFormat::Rss));
+        assert_ne!(all.etag(FeedFormat::Atom), ko.etag(FeedFormat::Atom));
+        assert_eq!(
+            all.etag(FeedFormat::Json),
+            Feed::site(&posts, None).etag(FeedFormat::Json)
+        );
+    }
+
+    #[test]
+    fn test_atom_escapes_and_links() {
+        let atom = FeedFormat::Atom.render(&Feed::site(&posts(), None));
+        assert!(atom.contains("<title>Fish &amp; &lt;Chips&gt;</title>"));
+        assert!(atom.contains("<link href=\"https://miniex.blog/feed.xml\" rel=\"self\""));
+        assert!(atom.contains("<category term=\"rust\"/>"));
+
+        let mut posts = posts();
+        posts[0].slug = "fish&chips".to_string();
+        let atom = FeedFormat::Atom.render(&Feed::site(&posts, None));
+        assert!(atom.contains("<id>https://miniex.blog/post/fish&amp;chips</id>"));
+        let rss = FeedFormat::Rss.render(&Feed::site(&posts, None));
+        assert!(rss.contains("<link>https://miniex.blog/post/fish&amp;chips</link>"));
+    }
+
+    #[test]
+    fn test_rss_items() {
+        let rss = FeedFormat::Rss.render(&Feed::site(&posts(), None));
+        assert!(rss.starts_with("<?xml"));
+        assert!(rss.contains("<rss version=\"2.0\""));
+        assert!(rss.contains("<atom:link href=\"https://miniex.blog/rss.xml\" rel=\"self\""));
+        assert!(rss.contains("<guid isPermaLink=\"true\">https://miniex.blog/post/hello</guid>"));
+        assert!(rss.contains("<pubDate>Mon, 1 Jan 2024 00:00:00 +0900</pubDate>"));
+        assert_eq!(rss.matches("<item>").count(), 2);
+    }
+
+    #[test]
+    fn test_json_feed() {
+        let doc: serde_json::Value =
+            serde_json::from_str(&FeedFormat::Json.render(&Feed::site(&posts(), None))).unwrap();
+        assert_eq!(doc["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(doc["feed_url"], "https://miniex.blog/feed.json");
+        assert_eq!(doc["items"][0]["id"], "https://miniex.blog/post/hello");
+        assert_eq!(
+            doc["items"][0]["date_published"],
+            "2024-01-01T00:00:00+09:00"
+        );
+        assert_eq!(doc["items"][1]["title"], "Fish & <Chips>");
+        assert!(doc.get("language").is_none());
+    }
+}
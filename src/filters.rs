@@ -30,6 +30,11 @@ pub fn truncate<T: std::fmt::Display>(s: T, len: usize) -> ::askama::Result<Stri
     })
 }
 
+/// Content-hashed URL for a file under `assets/`, e.g. `"js/app.js"|asset_url`.
+pub fn asset_url<T: std::fmt::Display>(path: T) -> ::askama::Result<String> {
+    Ok(crate::assets::url(&path.to_string()))
+}
+
 pub fn lower<T: std::fmt::Display>(s: T) -> ::askama::Result<String> {
     Ok(s.to_string().to_lowercase())
 }
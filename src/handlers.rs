@@ -1,3 +1,6 @@
+pub mod activitypub;
+pub mod admin;
 pub mod api;
 pub mod feed;
+pub mod og;
 pub mod pages;
@@ -0,0 +1,114 @@
+use crate::{
+    activitypub::{Federation, InboxError, InboxRequest, ACTIVITYSTREAMS, ACTIVITY_JSON},
+    SharedState,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+fn federation(state: &SharedState) -> Result<&Arc<Federation>, StatusCode> {
+    state.federation.as_ref().ok_or(StatusCode::NOT_FOUND)
+}
+
+fn activity_json(value: Value) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], value.to_string()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct WebfingerQuery {
+    resource: String,
+}
+
+pub async fn handle_webfinger(
+    State(state): State<SharedState>,
+    Query(query): Query<WebfingerQuery>,
+) -> Result<Response, StatusCode> {
+    let jrd = federation(&state)?
+        .webfinger(&query.resource)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/jrd+json")],
+        jrd.to_string(),
+    )
+        .into_response())
+}
+
+pub async fn handle_actor(State(state): State<SharedState>) -> Result<Response, StatusCode> {
+    Ok(activity_json(federation(&state)?.actor_document()))
+}
+
+pub async fn handle_outbox(State(state): State<SharedState>) -> Result<Response, StatusCode> {
+    federation(&state)?;
+    let posts = state.posts.read().await;
+    Ok(activity_json(Federation::outbox(&posts)))
+}
+
+pub async fn handle_followers(State(state): State<SharedState>) -> Result<Response, StatusCode> {
+    federation(&state)?;
+    let count = state.db.count_followers().await.map_err(|e| {
+        tracing::error!("count_followers error: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(activity_json(Federation::followers_collection(count)))
+}
+
+pub async fn handle_article(
+    Path(slug): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Response, StatusCode> {
+    federation(&state)?;
+    let posts = state.posts.read().await;
+    let post = crate::activitypub::federated_posts(&posts)
+        .into_iter()
+        .find(|p| p.slug == slug)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut article = Federation::article(post);
+    article["@context"] = ACTIVITYSTREAMS.into();
+    Ok(activity_json(article))
+}
+
+pub async fn handle_inbox(
+    State(state): State<SharedState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    let federation = federation(&state).map_err(|status| (status, "not found"))?;
+    let known_slugs: HashSet<String> = state
+        .posts
+        .read()
+        .await
+        .iter()
+        .map(|p| p.slug.clone())
+        .collect();
+    let request = InboxRequest {
+        method: method.as_str(),
+        path_and_query: uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or(uri.path()),
+        headers: &headers,
+        body: &body,
+    };
+
+    federation
+        .receive(&state.db, state.http.as_ref(), &known_slugs, &request)
+        .await
+        .map_err(|e| match e {
+            InboxError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            InboxError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            InboxError::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "could not process activity",
+            ),
+        })?;
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,564 @@
+use crate::{
+    analytics::{self, DateRange},
+    auth,
+    db::{BotHits, DailyViews, Database, ItemKind, PostViews},
+    i18n::{LangExtractor, Translations},
+    spam,
+    templates::{
+        AdminAnalyticsTemplate, AdminCommentsTemplate, AdminDashboardTemplate,
+        AdminGuestbookTemplate, AdminLoginTemplate,
+    },
+    Blog, SharedState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+const ADMIN_PER_PAGE: u32 = 30;
+const TOP_POSTS_DEFAULT: u32 = 10;
+const TOP_POSTS_MAX: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminPageQuery {
+    page: Option<u32>,
+}
+
+fn page_bounds(page: Option<u32>, total: u32) -> (u32, u32, i32) {
+    let total_pages = std::cmp::max(1, total.div_ceil(ADMIN_PER_PAGE));
+    let page = page.unwrap_or(1).clamp(1, total_pages);
+    let offset = ((page - 1) * ADMIN_PER_PAGE) as i32;
+    (page, total_pages, offset)
+}
+
+pub async fn handle_login_page(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+    headers: HeaderMap,
+) -> Response {
+    if auth::is_admin(&state, &headers).await {
+        return Redirect::to("/admin").into_response();
+    }
+    AdminLoginTemplate {
+        blog: Blog::new().set_title("miniex::admin"),
+        t: Translations::for_lang(lang),
+        lang,
+        error: false,
+    }
+    .into_response()
+}
+
+pub async fn handle_login(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    if !auth::verify_admin_password(&state.config, &form.password) {
+        tracing::warn!("Failed admin login attempt");
+        return (
+            StatusCode::UNAUTHORIZED,
+            AdminLoginTemplate {
+                blog: Blog::new().set_title("miniex::admin"),
+                t: Translations::for_lang(lang),
+                lang,
+                error: true,
+            },
+        )
+            .into_response();
+    }
+
+    let token = auth::new_session_token();
+    let ttl = state.config.admin_session_ttl;
+    if let Err(e) = state
+        .db
+        .create_admin_session(&auth::hash_token(&token), Utc::now() + ttl)
+        .await
+    {
+        tracing::error!("create_admin_session error: {}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    (
+        [(header::SET_COOKIE, auth::session_cookie(&token, ttl))],
+        Redirect::to("/admin"),
+    )
+        .into_response()
+}
+
+pub async fn handle_logout(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    if let Some(token) = auth::cookie_value(&headers, auth::SESSION_COOKIE) {
+        if let Err(e) = state
+            .db
+            .delete_admin_session(&auth::hash_token(token))
+            .await
+        {
+            tracing::error!("delete_admin_session error: {}", e);
+        }
+    }
+    (
+        [(header::SET_COOKIE, auth::clear_session_cookie())],
+        Redirect::to("/admin/login"),
+    )
+        .into_response()
+}
+
+pub async fn handle_dashboard(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+) -> Result<AdminDashboardTemplate, StatusCode> {
+    let today = analytics::today_in(state.config.site_timezone)
+        .format(analytics::DATE_FORMAT)
+        .to_string();
+    let db = &state.db;
+    let err = |_| StatusCode::INTERNAL_SERVER_ERROR;
+
+    let comment_count = db.count_comments().await.map_err(err)?;
+    let guestbook_count = db.count_guestbook_entries().await.map_err(err)?;
+    let (visitors_today, visitors_total) = db.get_visitor_stats(&today).await.map_err(err)?;
+    let post_stats = db.get_post_stats().await.map_err(err)?;
+    let spam_held = db.count_held_spam().await.map_err(err)?;
+    let post_count = state.posts.read().await.len();
+
+    Ok(AdminDashboardTemplate {
+        blog: Blog::new().set_title("miniex::admin"),
+        t: Translations::for_lang(lang),
+        lang,
+        post_count,
+        comment_count,
+        guestbook_count,
+        visitors_today,
+        visitors_total,
+        total_views: post_stats.iter().map(|s| s.views).sum(),
+        total_reactions: post_stats.iter().map(|s| s.reactions).sum(),
+        spam_held,
+        post_stats,
+    })
+}
+
+pub async fn handle_comments(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+    Query(query): Query<AdminPageQuery>,
+) -> Result<AdminCommentsTemplate, StatusCode> {
+    let total = state
+        .db
+        .count_comments()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let (page, total_pages, offset) = page_bounds(query.page, total);
+    let comments = state
+        .db
+        .get_all_comments_paged(offset, ADMIN_PER_PAGE as i32)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(AdminCommentsTemplate {
+        blog: Blog::new().set_title("miniex::admin::comments"),
+        t: Translations::for_lang(lang),
+        lang,
+        comments,
+        total,
+        current_page: page,
+        total_pages,
+    })
+}
+
+pub async fn handle_delete_comment(
+    Path(comment_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    match state.db.admin_delete_comment(&comment_id).await {
+        Ok(_) => Ok(Redirect::to("/admin/comments")),
+        Err(e) => {
+            tracing::error!("admin_delete_comment error for id={}: {}", comment_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn handle_guestbook(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+    Query(query): Query<AdminPageQuery>,
+) -> Result<AdminGuestbookTemplate, StatusCode> {
+    let total = state
+        .db
+        .count_all_guestbook_entries()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let (page, total_pages, offset) = page_bounds(query.page, total);
+    let entries = state
+        .db
+        .get_all_guestbook_entries_paged(offset, ADMIN_PER_PAGE as i32)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(AdminGuestbookTemplate {
+        blog: Blog::new().set_title("miniex::admin::guestbook"),
+        t: Translations::for_lang(lang),
+        lang,
+        entries,
+        total,
+        current_page: page,
+        total_pages,
+    })
+}
+
+pub async fn handle_delete_guestbook_entry(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    match state.db.admin_delete_guestbook_entry(&entry_id).await {
+        Ok(_) => Ok(Redirect::to("/admin/guestbook")),
+        Err(e) => {
+            tracing::error!(
+                "admin_delete_guestbook_entry error for id={}: {}",
+                entry_id,
+                e
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn set_pinned(
+    state: &SharedState,
+    entry_id: &str,
+    pinned: bool,
+) -> Result<Redirect, StatusCode> {
+    match state.db.set_guestbook_pinned(entry_id, pinned).await {
+        Ok(true) => Ok(Redirect::to("/admin/guestbook")),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("set_guestbook_pinned error for id={}: {}", entry_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn handle_guestbook_pin(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    set_pinned(&state, &entry_id, true).await
+}
+
+pub async fn handle_guestbook_unpin(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    set_pinned(&state, &entry_id, false).await
+}
+
+#[derive(Deserialize)]
+pub struct ReplyForm {
+    reply: String,
+}
+
+/// Save the owner's reply to an entry; an empty reply removes it.
+pub async fn handle_guestbook_reply(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+    Form(form): Form<ReplyForm>,
+) -> Result<Redirect, StatusCode> {
+    let reply = Some(form.reply.trim()).filter(|r| !r.is_empty());
+    match state.db.set_guestbook_reply(&entry_id, reply).await {
+        Ok(true) => Ok(Redirect::to("/admin/guestbook")),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("set_guestbook_reply error for id={}: {}", entry_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn mark_spam(
+    state: &SharedState,
+    kind: ItemKind,
+    id: &str,
+    is_spam: bool,
+    redirect_to: &str,
+) -> Result<Redirect, StatusCode> {
+    match spam::record_decision(&state.db, kind, id, is_spam).await {
+        Ok(true) => Ok(Redirect::to(redirect_to)),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("record_decision error for {:?} id={}: {}", kind, id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn handle_comment_spam(
+    Path(comment_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    mark_spam(
+        &state,
+        ItemKind::Comment,
+        &comment_id,
+        true,
+        "/admin/comments",
+    )
+    .await
+}
+
+pub async fn handle_comment_ham(
+    Path(comment_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    mark_spam(
+        &state,
+        ItemKind::Comment,
+        &comment_id,
+        false,
+        "/admin/comments",
+    )
+    .await
+}
+
+pub async fn handle_guestbook_spam(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    mark_spam(
+        &state,
+        ItemKind::Guestbook,
+        &entry_id,
+        true,
+        "/admin/guestbook",
+    )
+    .await
+}
+
+pub async fn handle_guestbook_ham(
+    Path(entry_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    mark_spam(
+        &state,
+        ItemKind::Guestbook,
+        &entry_id,
+        false,
+        "/admin/guestbook",
+    )
+    .await
+}
+
+pub async fn handle_clear_reactions(
+    Path(slug): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Redirect, StatusCode> {
+    match state.db.clear_reactions(&slug).await {
+        Ok(_) => Ok(Redirect::to("/admin")),
+        Err(e) => {
+            tracing::error!("clear_reactions error for slug={}: {}", slug, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AnalyticsQuery {
+    from: Option<String>,
+    to: Option<String>,
+    slug: Option<String>,
+    limit: Option<u32>,
+}
+
+impl AnalyticsQuery {
+    fn range(&self) -> Result<DateRange, (StatusCode, String)> {
+        DateRange::parse(
+            self.from.as_deref(),
+            self.to.as_deref(),
+            Utc::now().date_naive(),
+        )
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+    }
+
+    fn slug(&self) -> Option<&str> {
+        self.slug.as_deref().filter(|s| !s.is_empty())
+    }
+
+    fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(TOP_POSTS_DEFAULT)
+            .clamp(1, TOP_POSTS_MAX)
+    }
+}
+
+/// Daily views for one post, or the whole site when `slug` is `None`.
+#[derive(Serialize)]
+pub struct ViewSeries {
+    pub from: String,
+    pub to: String,
+    pub slug: Option<String>,
+    pub views: u32,
+    pub visitors: u32,
+    pub days: Vec<DailyViews>,
+    /// Requests not counted as views, by reason.
+    pub bot_hits: Vec<BotHits>,
+}
+
+async fn view_series(
+    db: &Database,
+    range: &DateRange,
+    slug: Option<&str>,
+) -> Result<ViewSeries, sqlx::Error> {
+    let rows = db
+        .get_daily_views(slug, &range.start(), &range.end())
+        .await?;
+    let days = analytics::fill_days(range, rows);
+    let bot_hits = db.get_bot_hits(slug, &range.start(), &range.end()).await?;
+    Ok(ViewSeries {
+        from: range.start(),
+        to: range.end(),
+        slug: slug.map(str::to_string),
+        views: days.iter().map(|d| d.views).sum(),
+        visitors: days.iter().map(|d| d.visitors).sum(),
+        days,
+        bot_hits,
+    })
+}
+
+pub async fn handle_analytics(
+    State(state): State<SharedState>,
+    LangExtractor(lang): LangExtractor,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<AdminAnalyticsTemplate, (StatusCode, String)> {
+    let range = query.range()?;
+    let err = |e: sqlx::Error| {
+        tracing::error!("analytics query error: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+    };
+    let series = view_series(&state.db, &range, query.slug())
+        .await
+        .map_err(err)?;
+    let top_posts = state
+        .db
+        .get_top_posts(&range.start(), &range.end(), query.limit())
+        .await
+        .map_err(err)?;
+
+    Ok(AdminAnalyticsTemplate {
+        blog: Blog::new().set_title("miniex::admin::analytics"),
+        t: Translations::for_lang(lang),
+        lang,
+        max_views: series.days.iter().map(|d| d.views).max().unwrap_or(0),
+        bot_total: series.bot_hits.iter().map(|b| b.hits).sum(),
+        series,
+        top_posts,
+    })
+}
+
+pub async fn handle_views_api(
+    State(state): State<SharedState>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<ViewSeries>, (StatusCode, String)> {
+    let range = query.range()?;
+    match view_series(&state.db, &range, query.slug()).await {
+        Ok(series) => Ok(Json(series)),
+        Err(e) => {
+            tracing::error!("get_daily_views error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, String::new()))
+        }
+    }
+}
+
+pub async fn handle_top_posts_api(
+    State(state): State<SharedState>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<Vec<PostViews>>, (StatusCode, String)> {
+    let range = query.range()?;
+    match state
+        .db
+        .get_top_posts(&range.start(), &range.end(), query.limit())
+        .await
+    {
+        Ok(top) => Ok(Json(top)),
+        Err(e) => {
+            tracing::error!("get_top_posts error: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, String::new()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_bounds_empty() {
+        assert_eq!(page_bounds(None, 0), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_page_bounds_clamps_past_end() {
+        assert_eq!(page_bounds(Some(9), 61), (3, 3, 60));
+    }
+
+    #[test]
+    fn test_page_bounds_clamps_zero() {
+        assert_eq!(page_bounds(Some(0), 10), (1, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_guestbook_entries_lead_both_orders() {
+        use crate::spam::SpamVerdict;
+
+        let db = crate::db::test_db().await;
+        let mut ids = Vec::new();
+        for author in ["first", "second", "third"] {
+            let website = (author == "second").then_some("https://example.com/");
+            let entry = db
+                .create_guestbook_entry(author, "hi", website, None, &SpamVerdict::default())
+                .await
+                .unwrap();
+            ids.push(entry.id);
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert!(db.set_guestbook_pinned(&ids[1], true).await.unwrap());
+        assert!(!db.set_guestbook_pinned("missing", true).await.unwrap());
+
+        for sort_asc in [true, false] {
+            let entries = db
+                .get_guestbook_entries_paged(0, 10, sort_asc)
+                .await
+                .unwrap();
+            let authors: Vec<&str> = entries.iter().map(|e| e.author.as_str()).collect();
+            let expected = if sort_asc {
+                ["second", "first", "third"]
+            } else {
+                ["second", "third", "first"]
+            };
+            assert_eq!(authors, expected);
+            assert_eq!(entries[0].website.as_deref(), Some("https://example.com/"));
+        }
+
+        assert!(db
+            .set_guestbook_reply(&ids[0], Some("*thanks*"))
+            .await
+            .unwrap());
+        let entries = db.get_guestbook_entries_paged(0, 10, true).await.unwrap();
+        let first = entries.iter().find(|e| e.id == ids[0]).unwrap();
+        assert_eq!(
+            first.reply_html.as_deref(),
+            Some("<p><em>thanks</em></p>\n")
+        );
+        assert!(first.replied_at.is_some());
+
+        assert!(db.set_guestbook_reply(&ids[0], None).await.unwrap());
+        let entries = db.get_guestbook_entries_paged(0, 10, true).await.unwrap();
+        let first = entries.iter().find(|e| e.id == ids[0]).unwrap();
+        assert!(first.reply.is_none() && first.replied_at.is_none());
+    }
+}
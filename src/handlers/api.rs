@@ -1,22 +1,111 @@
 use crate::{
-    db::{Comment, Guestbook},
+    analytics::{self, DateRange},
+    csp,
+    db::{Comment, DailyVisitors, Guestbook, ItemKind},
     i18n::Lang,
+    lockout::{self, Admission, Attempt},
+    metrics::Write,
     post::{dedup_by_translation, Post},
-    SharedState,
+    ratelimit::ClientIp,
+    sanitize::normalize_website,
+    spam::{SpamVerdict, Submission},
+    visitor::Visitor,
+    webmention, SharedState,
 };
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension, Form,
 };
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 
 // --- Health Check ---
 
+/// Longest a readiness check waits for the database.
+const READINESS_DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness: the process is up and answering requests.
 pub async fn health_check() -> StatusCode {
     StatusCode::OK
 }
 
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    ready: bool,
+    database: DatabaseCheck,
+    content: ContentCheck,
+}
+
+#[derive(Serialize)]
+pub struct DatabaseCheck {
+    ok: bool,
+    latency_ms: u128,
+    /// A fixed reason; the underlying error is only logged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct ContentCheck {
+    ok: bool,
+    /// Posts being served now; briefly zero while a reload is in progress.
+    posts: usize,
+    version: Option<String>,
+    commit: Option<String>,
+    loaded_at: Option<String>,
+}
+
+/// Readiness: the database answers and a non-empty content snapshot is
+/// loaded. `503` until both hold, so deploys can wait for a usable instance.
+pub async fn readiness_check(
+    State(state): State<SharedState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let start = Instant::now();
+    let error = match tokio::time::timeout(READINESS_DB_TIMEOUT, state.db.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => {
+            tracing::error!("Readiness check: database unavailable: {}", e);
+            Some("unavailable")
+        }
+        Err(_) => {
+            tracing::error!("Readiness check: database ping timed out");
+            Some("timed out")
+        }
+    };
+    let database = DatabaseCheck {
+        ok: error.is_none(),
+        latency_ms: start.elapsed().as_millis(),
+        error,
+    };
+
+    let posts = state.posts.read().await.len();
+    let snapshot = state.content.read().await.clone();
+    let content = ContentCheck {
+        ok: posts > 0 && snapshot.as_ref().is_some_and(|s| s.posts > 0),
+        posts,
+        version: snapshot.as_ref().map(|s| s.version.clone()),
+        commit: state.config.content_commit.clone(),
+        loaded_at: snapshot.map(|s| s.loaded_at.to_rfc3339()),
+    };
+
+    let ready = database.ok && content.ok;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            database,
+            content,
+        }),
+    )
+}
+
 // --- Search API ---
 
 #[derive(Deserialize)]
@@ -61,7 +150,7 @@ pub async fn handle_search(
 
     let deduped = dedup_by_translation(matching, search_lang);
     let mut sorted = deduped;
-    sorted.sort_by(|a, b| b.metadata.created_at.cmp(&a.metadata.created_at));
+    sorted.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
 
     let results: Vec<SearchResult> = sorted
         .into_iter()
@@ -114,13 +203,6 @@ pub async fn handle_set_lang(
 
 // --- Comments & Guestbook API ---
 
-#[derive(Deserialize)]
-pub struct CreateCommentRequest {
-    author: String,
-    content: String,
-    password: Option<String>,
-}
-
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
     data: T,
@@ -146,6 +228,8 @@ pub struct CreateCommentWithPostRequest {
     author: String,
     content: String,
     password: Option<String>,
+    email: Option<String>,
+    form_token: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -165,10 +249,124 @@ pub struct EditResponse {
     message: String,
 }
 
+/// Body of a `423 Locked` reply to a password-checked change.
+#[derive(Serialize)]
+pub struct LockedResponse {
+    success: bool,
+    error: &'static str,
+    /// `item` when the comment or entry is locked, `client` when the caller is.
+    scope: &'static str,
+    retry_after: u64,
+    message: String,
+}
+
+/// Run a password-checked change unless the item or the client is locked
+/// out, and count wrong passwords towards a lockout. Unknown items are a
+/// `404` and count for nothing.
+async fn password_checked(
+    state: &SharedState,
+    attempt: Attempt<'_>,
+    change: impl std::future::Future<Output = Result<bool, sqlx::Error>>,
+    done: &str,
+) -> Result<Response, StatusCode> {
+    let now = chrono::Utc::now();
+    let db = &state.db;
+    let internal = |_| StatusCode::INTERNAL_SERVER_ERROR;
+
+    if !db
+        .item_exists(attempt.kind, attempt.id)
+        .await
+        .map_err(internal)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let counted = match lockout::admit(db, &state.config.password_lockout, &attempt, now)
+        .await
+        .map_err(internal)?
+    {
+        Admission::Locked(lock) => return Ok(locked_response(&lock, now)),
+        Admission::Counted(counted) => counted,
+    };
+    if change.await.map_err(internal)? {
+        lockout::record_success(db, &attempt, &counted)
+            .await
+            .map_err(internal)?;
+        return Ok(Json(EditResponse {
+            success: true,
+            message: done.to_string(),
+        })
+        .into_response());
+    }
+
+    Ok(match lockout::record_failure(&attempt, &counted) {
+        Some(lock) => locked_response(&lock, now),
+        None => Json(EditResponse {
+            success: false,
+            message: "Wrong password".to_string(),
+        })
+        .into_response(),
+    })
+}
+
+fn locked_response(lock: &lockout::Lock, now: chrono::DateTime<chrono::Utc>) -> Response {
+    let retry_after = lock.retry_after_secs(now);
+    (
+        StatusCode::LOCKED,
+        [(header::RETRY_AFTER, HeaderValue::from(retry_after))],
+        Json(LockedResponse {
+            success: false,
+            error: "locked",
+            scope: lock.scope.as_str(),
+            retry_after,
+            message: "Too many wrong passwords, try again later".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize)]
+pub struct FormTokenResponse {
+    token: String,
+}
+
+/// Token the comment and guestbook forms send back, used to measure time-to-submit.
+pub async fn get_form_token(State(state): State<SharedState>) -> Json<FormTokenResponse> {
+    Json(FormTokenResponse {
+        token: state.spam.issue_form_token(chrono::Utc::now().timestamp()),
+    })
+}
+
+fn log_held_submission(kind: &str, author: &str, verdict: &SpamVerdict) {
+    tracing::info!(
+        "Held {} from {:?} as spam (score {:.2}: {})",
+        kind,
+        author,
+        verdict.score,
+        verdict.reasons.join(", ")
+    );
+}
+
 pub async fn create_comment(
     State(state): State<SharedState>,
     Json(payload): Json<CreateCommentWithPostRequest>,
 ) -> Result<Json<ApiResponse<Comment>>, StatusCode> {
+    let verdict = state
+        .spam
+        .check(
+            &state.db,
+            &Submission {
+                author: &payload.author,
+                content: &payload.content,
+                website: None,
+                honeypot: payload.email.as_deref(),
+                form_token: payload.form_token.as_deref(),
+            },
+        )
+        .await;
+    if verdict.is_spam {
+        log_held_submission("comment", &payload.author, &verdict);
+    }
+
     match state
         .db
         .create_comment(
@@ -176,13 +374,21 @@ pub async fn create_comment(
             &payload.author,
             &payload.content,
             payload.password.as_deref(),
+            &verdict,
         )
         .await
     {
-        Ok(comment) => Ok(Json(ApiResponse {
-            data: comment,
-            message: "Comment created successfully".to_string(),
-        })),
+        Ok(comment) => {
+            state.metrics.record_write(Write::Comment);
+            Ok(Json(ApiResponse {
+                data: comment,
+                message: if verdict.is_spam {
+                    "Comment is awaiting moderation".to_string()
+                } else {
+                    "Comment created successfully".to_string()
+                },
+            }))
+        }
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
@@ -190,45 +396,34 @@ pub async fn create_comment(
 pub async fn edit_comment(
     Path(comment_id): Path<String>,
     State(state): State<SharedState>,
+    Extension(ClientIp(client)): Extension<ClientIp>,
     Json(payload): Json<EditCommentRequest>,
-) -> Result<Json<EditResponse>, StatusCode> {
-    match state
+) -> Result<Response, StatusCode> {
+    let attempt = Attempt {
+        kind: ItemKind::Comment,
+        id: &comment_id,
+        client,
+    };
+    let spam = state.spam.check_edit(&state.db, &payload.content).await;
+    let change = state
         .db
-        .update_comment(&comment_id, &payload.content, &payload.password)
-        .await
-    {
-        Ok(success) => Ok(Json(EditResponse {
-            success,
-            message: if success {
-                "Comment updated successfully".to_string()
-            } else {
-                "Wrong password or comment not found".to_string()
-            },
-        })),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
+        .update_comment(&comment_id, &payload.content, &payload.password, &spam);
+    password_checked(&state, attempt, change, "Comment updated successfully").await
 }
 
 pub async fn delete_comment(
     Path(comment_id): Path<String>,
     State(state): State<SharedState>,
+    Extension(ClientIp(client)): Extension<ClientIp>,
     Json(payload): Json<DeleteRequest>,
-) -> Result<Json<EditResponse>, StatusCode> {
-    match state
-        .db
-        .delete_comment(&comment_id, &payload.password)
-        .await
-    {
-        Ok(success) => Ok(Json(EditResponse {
-            success,
-            message: if success {
-                "Comment deleted successfully".to_string()
-            } else {
-                "Wrong password or comment not found".to_string()
-            },
-        })),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
+) -> Result<Response, StatusCode> {
+    let attempt = Attempt {
+        kind: ItemKind::Comment,
+        id: &comment_id,
+        client,
+    };
+    let change = state.db.delete_comment(&comment_id, &payload.password);
+    password_checked(&state, attempt, change, "Comment deleted successfully").await
 }
 
 pub async fn get_guestbook_entries(
@@ -243,23 +438,64 @@ pub async fn get_guestbook_entries(
     }
 }
 
+#[derive(Deserialize)]
+pub struct CreateGuestbookRequest {
+    author: String,
+    content: String,
+    website: Option<String>,
+    password: Option<String>,
+    /// Honeypot; hidden from people and expected to stay empty.
+    email: Option<String>,
+    form_token: Option<String>,
+}
+
 pub async fn create_guestbook_entry(
     State(state): State<SharedState>,
-    Json(payload): Json<CreateCommentRequest>,
+    Json(payload): Json<CreateGuestbookRequest>,
 ) -> Result<Json<ApiResponse<Guestbook>>, StatusCode> {
+    let website = match payload.website.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(raw) => Some(normalize_website(raw).ok_or(StatusCode::BAD_REQUEST)?),
+    };
+    let verdict = state
+        .spam
+        .check(
+            &state.db,
+            &Submission {
+                author: &payload.author,
+                content: &payload.content,
+                website: website.as_deref(),
+                honeypot: payload.email.as_deref(),
+                form_token: payload.form_token.as_deref(),
+            },
+        )
+        .await;
+    if verdict.is_spam {
+        log_held_submission("guestbook entry", &payload.author, &verdict);
+    }
+
     match state
         .db
         .create_guestbook_entry(
             &payload.author,
             &payload.content,
+            website.as_deref(),
             payload.password.as_deref(),
+            &verdict,
         )
         .await
     {
-        Ok(entry) => Ok(Json(ApiResponse {
-            data: entry,
-            message: "Guestbook entry created successfully".to_string(),
-        })),
+        Ok(entry) => {
+            state.metrics.record_write(Write::Guestbook);
+            Ok(Json(ApiResponse {
+                data: entry,
+                message: if verdict.is_spam {
+                    "Guestbook entry is awaiting moderation".to_string()
+                } else {
+                    "Guestbook entry created successfully".to_string()
+                },
+            }))
+        }
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
@@ -267,99 +503,168 @@ pub async fn create_guestbook_entry(
 pub async fn edit_guestbook_entry(
     Path(entry_id): Path<String>,
     State(state): State<SharedState>,
+    Extension(ClientIp(client)): Extension<ClientIp>,
     Json(payload): Json<EditCommentRequest>,
-) -> Result<Json<EditResponse>, StatusCode> {
-    match state
-        .db
-        .update_guestbook_entry(&entry_id, &payload.content, &payload.password)
-        .await
-    {
-        Ok(success) => Ok(Json(EditResponse {
-            success,
-            message: if success {
-                "Guestbook entry updated successfully".to_string()
-            } else {
-                "Wrong password or entry not found".to_string()
-            },
-        })),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
+) -> Result<Response, StatusCode> {
+    let attempt = Attempt {
+        kind: ItemKind::Guestbook,
+        id: &entry_id,
+        client,
+    };
+    let spam = state.spam.check_edit(&state.db, &payload.content).await;
+    let change =
+        state
+            .db
+            .update_guestbook_entry(&entry_id, &payload.content, &payload.password, &spam);
+    password_checked(
+        &state,
+        attempt,
+        change,
+        "Guestbook entry updated successfully",
+    )
+    .await
 }
 
 pub async fn delete_guestbook_entry(
     Path(entry_id): Path<String>,
     State(state): State<SharedState>,
+    Extension(ClientIp(client)): Extension<ClientIp>,
     Json(payload): Json<DeleteRequest>,
-) -> Result<Json<EditResponse>, StatusCode> {
-    match state
+) -> Result<Response, StatusCode> {
+    let attempt = Attempt {
+        kind: ItemKind::Guestbook,
+        id: &entry_id,
+        client,
+    };
+    let change = state
         .db
-        .delete_guestbook_entry(&entry_id, &payload.password)
-        .await
-    {
-        Ok(success) => Ok(Json(EditResponse {
-            success,
-            message: if success {
-                "Guestbook entry deleted successfully".to_string()
-            } else {
-                "Wrong password or entry not found".to_string()
-            },
-        })),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
+        .delete_guestbook_entry(&entry_id, &payload.password);
+    password_checked(
+        &state,
+        attempt,
+        change,
+        "Guestbook entry deleted successfully",
+    )
+    .await
 }
 
-// --- Like API ---
+// --- Webmention ---
 
 #[derive(Deserialize)]
-pub struct LikeRequest {
-    client_id: String,
+pub struct WebmentionRequest {
+    source: String,
+    target: String,
+}
+
+/// Accept a Webmention for one of our posts; the source is verified in the background.
+pub async fn receive_webmention(
+    State(state): State<SharedState>,
+    Form(payload): Form<WebmentionRequest>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    let slug = webmention::post_slug_from_target(&payload.target)
+        .ok_or((StatusCode::BAD_REQUEST, "target is not a post on this site"))?;
+    if !state.posts.read().await.iter().any(|p| p.slug == slug) {
+        return Err((StatusCode::BAD_REQUEST, "target post does not exist"));
+    }
+    webmention::validate_source(&payload.source, &payload.target)
+        .map_err(|msg| (StatusCode::BAD_REQUEST, msg))?;
+
+    let id = state
+        .db
+        .upsert_webmention(&payload.source, &payload.target, &slug)
+        .await
+        .map_err(|e| {
+            tracing::error!("upsert_webmention error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "could not store webmention",
+            )
+        })?;
+
+    tokio::spawn(async move {
+        webmention::verify(
+            &state.db,
+            state.http.as_ref(),
+            &id,
+            &payload.source,
+            &payload.target,
+        )
+        .await;
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// --- CSP reports ---
+
+/// Log Content Security Policy violations reported by browsers. Reports
+/// arrive as `application/csp-report` or `application/reports+json`, so the
+/// body is parsed by hand rather than through `Json`.
+pub async fn receive_csp_report(
+    Extension(ClientIp(client)): Extension<ClientIp>,
+    body: axum::body::Bytes,
+) -> StatusCode {
+    for violation in csp::parse_report(&body) {
+        tracing::warn!(
+            "CSP violation on {}: {} blocked {} (reported by {})",
+            violation.document,
+            violation.directive,
+            violation.blocked,
+            client
+        );
+    }
+    StatusCode::NO_CONTENT
 }
 
+// --- Reaction API ---
+
 #[derive(Serialize)]
-pub struct LikeResponse {
-    liked: bool,
-    like_count: u32,
+pub struct ReactionResponse {
+    kind: String,
+    reacted: bool,
+    count: u32,
 }
 
-pub async fn toggle_like(
-    Path(slug): Path<String>,
+pub async fn toggle_reaction(
+    Path((slug, kind)): Path<(String, String)>,
     State(state): State<SharedState>,
-    Json(payload): Json<LikeRequest>,
-) -> impl IntoResponse {
-    match state.db.toggle_like(&slug, &payload.client_id).await {
-        Ok((liked, like_count)) => Json(LikeResponse { liked, like_count }).into_response(),
+    Extension(visitor): Extension<Visitor>,
+) -> Result<Json<ReactionResponse>, StatusCode> {
+    if !state.config.reactions.iter().any(|k| k.name == kind)
+        || !state.posts.read().await.iter().any(|p| p.slug == slug)
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    match state.db.toggle_reaction(&slug, &kind, &visitor.key).await {
+        Ok((reacted, count)) => {
+            state.metrics.record_write(Write::Reaction);
+            Ok(Json(ReactionResponse {
+                kind,
+                reacted,
+                count,
+            }))
+        }
         Err(e) => {
-            tracing::error!("toggle_like error for slug={}: {}", slug, e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(LikeResponse {
-                    liked: false,
-                    like_count: 0,
-                }),
-            )
-                .into_response()
+            tracing::error!("toggle_reaction error for slug={}: {}", slug, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
 // --- Visit API ---
 
-#[derive(Deserialize)]
-pub struct VisitRequest {
-    client_id: String,
-    date: String,
-}
-
 pub async fn record_visit(
     State(state): State<SharedState>,
-    Json(payload): Json<VisitRequest>,
+    Extension(visitor): Extension<Visitor>,
 ) -> StatusCode {
-    match state
-        .db
-        .record_visit(&payload.client_id, &payload.date)
-        .await
-    {
-        Ok(_) => StatusCode::OK,
+    let today = analytics::today_in(state.config.site_timezone)
+        .format(analytics::DATE_FORMAT)
+        .to_string();
+    match state.db.record_visit(&visitor.key, &today).await {
+        Ok(_) => {
+            state.metrics.record_write(Write::Visit);
+            StatusCode::OK
+        }
         Err(e) => {
             tracing::error!("record_visit error: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
@@ -371,21 +676,50 @@ pub async fn record_visit(
 
 #[derive(Deserialize)]
 pub struct VisitorStatsQuery {
-    date: String,
+    from: Option<String>,
+    to: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct VisitorStatsResponse {
     today: u32,
     total: u32,
+    /// Daily visitors, only when `from` or `to` was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days: Option<Vec<DailyVisitors>>,
 }
 
 pub async fn get_visitor_stats(
     State(state): State<SharedState>,
     Query(query): Query<VisitorStatsQuery>,
-) -> Json<VisitorStatsResponse> {
-    match state.db.get_visitor_stats(&query.date).await {
-        Ok((today, total)) => Json(VisitorStatsResponse { today, total }),
-        Err(_) => Json(VisitorStatsResponse { today: 0, total: 0 }),
-    }
+) -> Result<Json<VisitorStatsResponse>, (StatusCode, String)> {
+    let today = analytics::today_in(state.config.site_timezone);
+    let range = match (query.from.as_deref(), query.to.as_deref()) {
+        (None, None) => None,
+        (from, to) => {
+            Some(DateRange::parse(from, to, today).map_err(|e| (StatusCode::BAD_REQUEST, e))?)
+        }
+    };
+    let err = |e: sqlx::Error| {
+        tracing::error!("get_visitor_stats error: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+    };
+
+    let (today, total) = state
+        .db
+        .get_visitor_stats(&today.format(analytics::DATE_FORMAT).to_string())
+        .await
+        .map_err(err)?;
+    let days = match range {
+        Some(range) => {
+            let rows = state
+                .db
+                .get_daily_visitors(&range.start(), &range.end())
+                .await
+                .map_err(err)?;
+            Some(analytics::fill_days(&range, rows))
+        }
+        None => None,
+    };
+    Ok(Json(VisitorStatsResponse { today, total, days }))
 }
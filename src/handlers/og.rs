@@ -0,0 +1,45 @@
+use super::feed::is_not_modified;
+use crate::{og::OgCard, SharedState};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+/// `/og/:slug.png` — the generated preview card for a post.
+pub async fn handle_og_image(
+    Path(file_name): Path<String>,
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(slug) = file_name.strip_suffix(".png") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let card = {
+        let posts = state.posts.read().await;
+        match posts.iter().find(|p| p.slug == slug) {
+            Some(post) => OgCard::from_post(post),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        }
+    };
+
+    let etag = format!("\"og-{}\"", card.hash());
+    if is_not_modified(&headers, &etag) {
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+    match state.og.png(&card).await {
+        Ok(png) => (
+            [
+                (header::CONTENT_TYPE, "image/png".to_string()),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            ],
+            png,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("OG image error for {}: {}", slug, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
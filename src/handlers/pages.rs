@@ -1,33 +1,65 @@
-use crate::db::Database;
 use crate::{
+    analytics,
+    feed::{series_feed_path, tag_feed_path, type_feed_path},
     i18n::{LangExtractor, Translations},
+    jsonld, og,
     post::{
         get_available_translations, get_posts_by_category, get_posts_by_series, get_recent_posts,
         get_series_nav_info, Post, PostType,
     },
+    reactions,
+    sanitize::normalize_website,
     templates::{
         BlogTemplate, DiaryTemplate, ErrorTemplate, GuestbookTemplate, IndexTemplate, PostTemplate,
         ResumeTemplate, ReviewTemplate, SeriesDetailTemplate, SeriesTemplate,
     },
+    visitor::Visitor,
     Blog, SharedState, SITE_DESCRIPTION, SITE_URL,
 };
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, Method, StatusCode},
     response::IntoResponse,
+    Extension,
 };
 use serde::Deserialize;
 
-async fn enrich_posts_with_counts(posts: &mut [Post], db: &Database) {
+async fn enrich_posts_with_counts(posts: &mut [Post], state: &SharedState) {
     let slugs: Vec<String> = posts.iter().map(|p| p.slug.clone()).collect();
     if slugs.is_empty() {
         return;
     }
-    let view_counts = db.get_view_counts(&slugs).await.unwrap_or_default();
-    let like_counts = db.get_like_counts(&slugs).await.unwrap_or_default();
+    let view_counts = state.db.get_view_counts(&slugs).await.unwrap_or_default();
+    let reaction_counts = state
+        .db
+        .get_reaction_counts(&slugs)
+        .await
+        .unwrap_or_default();
     for post in posts.iter_mut() {
         post.view_count = view_counts.get(&post.slug).copied().unwrap_or(0);
-        post.like_count = like_counts.get(&post.slug).copied().unwrap_or(0);
+        post.reaction_count =
+            reactions::total(&state.config.reactions, reaction_counts.get(&post.slug));
+    }
+}
+
+fn home_url() -> String {
+    format!("{}/", SITE_URL)
+}
+
+/// Feed links and breadcrumbs for a post-type listing: the type feed, plus
+/// the tag feed when the listing is filtered by a category.
+fn with_listing_meta(blog: Blog, post_type: &PostType, category: Option<&str>) -> Blog {
+    let name = post_type.to_string().to_lowercase();
+    let url = format!("{}/{}", SITE_URL, name);
+    let blog = blog
+        .add_feed(&format!("miniex::{}", name), &type_feed_path(post_type))
+        .set_json_ld(vec![jsonld::breadcrumbs(&[
+            ("miniex.blog", &home_url()),
+            (&name, &url),
+        ])]);
+    match category {
+        Some(tag) => blog.add_feed(&format!("miniex.blog #{}", tag), &tag_feed_path(tag)),
+        None => blog,
     }
 }
 
@@ -51,14 +83,15 @@ pub async fn handle_index(
     let posts = state.posts.read().await;
     let mut recent_posts = get_recent_posts(&posts, lang);
     drop(posts);
-    enrich_posts_with_counts(&mut recent_posts, &state.db).await;
+    enrich_posts_with_counts(&mut recent_posts, &state).await;
     let t = Translations::for_lang(lang);
 
     IndexTemplate {
         blog: Blog::new()
             .set_title("miniex")
             .set_description(SITE_DESCRIPTION)
-            .set_url(SITE_URL),
+            .set_url(SITE_URL)
+            .set_json_ld(vec![jsonld::website(), jsonld::owner()]),
         recent_posts,
         t,
         lang,
@@ -104,17 +137,21 @@ pub async fn handle_blog(
         .collect();
 
     drop(posts);
-    enrich_posts_with_counts(&mut current_posts, &state.db).await;
+    enrich_posts_with_counts(&mut current_posts, &state).await;
 
     let page_numbers = compute_page_numbers(page, total_pages);
 
     BlogTemplate {
-        blog: Blog::new()
-            .set_title("miniex::blog")
-            .set_description(
-                "Technical blog posts about Rust, JavaScript, web development, and more",
-            )
-            .set_url(&format!("{}/blog", SITE_URL)),
+        blog: with_listing_meta(
+            Blog::new()
+                .set_title("miniex::blog")
+                .set_description(
+                    "Technical blog posts about Rust, JavaScript, web development, and more",
+                )
+                .set_url(&format!("{}/blog", SITE_URL)),
+            &PostType::Blog,
+            query.category.as_deref(),
+        ),
         posts: current_posts,
         categories,
         current_category: query.category,
@@ -172,15 +209,19 @@ pub async fn handle_review(
         .collect();
 
     drop(posts);
-    enrich_posts_with_counts(&mut current_posts, &state.db).await;
+    enrich_posts_with_counts(&mut current_posts, &state).await;
 
     let page_numbers = compute_page_numbers(page, total_pages);
 
     ReviewTemplate {
-        blog: Blog::new()
-            .set_title("miniex::review")
-            .set_description("Tech reviews and software analysis")
-            .set_url(&format!("{}/review", SITE_URL)),
+        blog: with_listing_meta(
+            Blog::new()
+                .set_title("miniex::review")
+                .set_description("Tech reviews and software analysis")
+                .set_url(&format!("{}/review", SITE_URL)),
+            &PostType::Review,
+            query.category.as_deref(),
+        ),
         posts: current_posts,
         categories,
         current_category: query.category,
@@ -238,15 +279,19 @@ pub async fn handle_diary(
         .collect();
 
     drop(posts);
-    enrich_posts_with_counts(&mut current_posts, &state.db).await;
+    enrich_posts_with_counts(&mut current_posts, &state).await;
 
     let page_numbers = compute_page_numbers(page, total_pages);
 
     DiaryTemplate {
-        blog: Blog::new()
-            .set_title("miniex::diary")
-            .set_description("Development diary and personal notes")
-            .set_url(&format!("{}/diary", SITE_URL)),
+        blog: with_listing_meta(
+            Blog::new()
+                .set_title("miniex::diary")
+                .set_description("Development diary and personal notes")
+                .set_url(&format!("{}/diary", SITE_URL)),
+            &PostType::Diary,
+            query.category.as_deref(),
+        ),
         posts: current_posts,
         categories,
         current_category: query.category,
@@ -278,7 +323,7 @@ pub async fn handle_series(
     let sort_asc = query.sort.as_deref() == Some("asc");
     let mut series = state.series_cache.read().await.clone();
     if sort_asc {
-        series.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        series.sort_by_key(|s| s.updated_at);
     }
     let t = Translations::for_lang(lang);
 
@@ -286,7 +331,11 @@ pub async fn handle_series(
         blog: Blog::new()
             .set_title("miniex::series")
             .set_description("Development tutorial series and in-depth guides")
-            .set_url(&format!("{}/series", SITE_URL)),
+            .set_url(&format!("{}/series", SITE_URL))
+            .set_json_ld(vec![jsonld::breadcrumbs(&[
+                ("miniex.blog", &home_url()),
+                ("series", &format!("{}/series", SITE_URL)),
+            ])]),
         series,
         t,
         lang,
@@ -323,6 +372,19 @@ pub async fn handle_series_detail(
         Some(series) => {
             let posts = state.posts.read().await;
             let all_series_posts = get_posts_by_series(&posts, &series_name, lang, sort_asc);
+            let mut reading_order: Vec<&Post> = all_series_posts.iter().collect();
+            if !sort_asc {
+                reading_order.reverse();
+            }
+            let series_url = jsonld::series_url(&series_name);
+            let json_ld = vec![
+                jsonld::creative_work_series(&series, &reading_order),
+                jsonld::breadcrumbs(&[
+                    ("miniex.blog", &home_url()),
+                    ("series", &format!("{}/series", SITE_URL)),
+                    (&series_name, &series_url),
+                ]),
+            ];
             let total_posts = all_series_posts.len();
             let total_pages = (total_posts as f32 / posts_per_page as f32).ceil() as u32;
 
@@ -334,7 +396,7 @@ pub async fn handle_series_detail(
                 .collect();
 
             drop(posts);
-            enrich_posts_with_counts(&mut series_posts, &state.db).await;
+            enrich_posts_with_counts(&mut series_posts, &state).await;
 
             let page_numbers = compute_page_numbers(page, total_pages);
 
@@ -347,7 +409,12 @@ pub async fn handle_series_detail(
                             .clone()
                             .unwrap_or_else(|| format!("{} 시리즈", series_name)),
                     )
-                    .set_url(&format!("{}/series/{}", SITE_URL, series_name)),
+                    .set_url(&format!("{}/series/{}", SITE_URL, series_name))
+                    .set_json_ld(json_ld)
+                    .add_feed(
+                        &format!("miniex::series::{}", series_name),
+                        &series_feed_path(&series_name),
+                    ),
                 series_description: series.description,
                 series_status: series.status,
                 series_name,
@@ -386,6 +453,9 @@ pub async fn handle_post(
     Path(id): Path<String>,
     State(state): State<SharedState>,
     LangExtractor(lang): LangExtractor,
+    Extension(visitor): Extension<Visitor>,
+    method: Method,
+    headers: HeaderMap,
 ) -> PostTemplate {
     let posts = state.posts.read().await;
     let t = Translations::for_lang(lang);
@@ -407,26 +477,84 @@ pub async fn handle_post(
         .as_ref()
         .and_then(|p| get_series_nav_info(&posts, p));
 
-    // Increment view count and get like count
+    // Record the view and get reaction counts
+    let mut reaction_views = Vec::new();
     let current_post = if let Some(mut post) = current_post {
-        if let Ok(view_count) = state.db.increment_view(&post.slug).await {
-            post.view_count = view_count;
+        match analytics::record_post_view(&state, &post.slug, &method, &headers, &visitor).await {
+            Ok(view_count) => post.view_count = view_count,
+            Err(e) => tracing::error!("record_view error for slug={}: {}", post.slug, e),
         }
         let slugs = vec![post.slug.clone()];
-        if let Ok(like_counts) = state.db.get_like_counts(&slugs).await {
-            post.like_count = like_counts.get(&post.slug).copied().unwrap_or(0);
-        }
+        let counts = state
+            .db
+            .get_reaction_counts(&slugs)
+            .await
+            .unwrap_or_default();
+        let reacted = state
+            .db
+            .get_visitor_reactions(&post.slug, &visitor.key)
+            .await
+            .unwrap_or_default();
+        let kinds = &state.config.reactions;
+        post.reaction_count = reactions::total(kinds, counts.get(&post.slug));
+        reaction_views = reactions::views(kinds, counts.get(&post.slug), &reacted);
         Some(post)
     } else {
         None
     };
 
+    let webmentions = match &current_post {
+        Some(p) => state
+            .db
+            .get_webmentions_for_post(&p.slug)
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let show_replies = state.federation.as_ref().is_some_and(|f| f.show_replies);
+    let federated_replies = match &current_post {
+        Some(p) if show_replies => state
+            .db
+            .get_federated_replies_for_post(&p.slug)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            // Both are rendered as links; rows stored before they were
+            // vetted on receipt may hold other schemes
+            .filter(|r| {
+                normalize_website(&r.actor_id).is_some() && normalize_website(&r.url).is_some()
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
     let blog = if let Some(ref p) = current_post {
+        let url = format!("{}/post/{}", SITE_URL, p.slug);
+        let image = p
+            .metadata
+            .og_image
+            .clone()
+            .unwrap_or_else(|| og::image_url(&p.slug));
+        let listing = p.post_type.to_string().to_lowercase();
+        let listing_url = format!("{}/{}", SITE_URL, listing);
+        let mut trail = vec![("miniex.blog", home_url()), (listing.as_str(), listing_url)];
+        if let Some(series) = &p.metadata.series {
+            trail.push((series.as_str(), jsonld::series_url(series)));
+        }
+        trail.push((p.metadata.title.as_str(), url.clone()));
+        let trail: Vec<(&str, &str)> = trail.iter().map(|(n, u)| (*n, u.as_str())).collect();
+
         Blog::new()
             .set_title(&p.metadata.title)
             .set_description(&p.metadata.description)
-            .set_url(&format!("{}/post/{}", SITE_URL, p.slug))
+            .set_url(&url)
             .set_og_type("article")
+            .set_image(&image)
+            .set_json_ld(vec![
+                jsonld::blog_posting(p, &url, &image),
+                jsonld::breadcrumbs(&trail),
+            ])
     } else {
         Blog::new().set_title("Post Not Found")
     };
@@ -438,6 +566,9 @@ pub async fn handle_post(
         t,
         lang,
         available_langs,
+        webmentions,
+        federated_replies,
+        reactions: reaction_views,
     }
 }
 
@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::HeaderMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use reqwest::Method;
+
+/// Responses larger than this are truncated; we only ever need page heads
+/// and small JSON documents.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const USER_AGENT: &str = concat!("miniex.blog/", env!("CARGO_PKG_VERSION"));
+
+/// An outgoing request to another site.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl HttpRequest {
+    pub fn get(url: &str) -> Self {
+        HttpRequest {
+            method: Method::GET,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn post(url: &str, content_type: &str, body: Vec<u8>) -> Self {
+        HttpRequest {
+            method: Method::POST,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: Some(body),
+        }
+        .header("Content-Type", content_type)
+    }
+
+    pub fn post_form(url: &str, params: &[(&str, &str)]) -> Self {
+        let body = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        Self::post(url, "application/x-www-form-urlencoded", body.into_bytes())
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Final URL after redirects.
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Outgoing HTTP, behind a trait so features that talk to other sites can
+/// be pointed at a local stand-in server in tests.
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn execute(&self, request: HttpRequest) -> anyhow::Result<HttpResponse>;
+}
+
+pub struct ReqwestClient {
+    client: reqwest::Client,
+    allow_private: bool,
+}
+
+impl ReqwestClient {
+    /// `allow_private` permits loopback and private-network targets. Keep it
+    /// off in production so user-supplied URLs cannot reach internal services.
+    pub fn new(allow_private: bool) -> Self {
+        Self::with_builder(allow_private, reqwest::Client::builder())
+    }
+
+    fn with_builder(allow_private: bool, builder: reqwest::ClientBuilder) -> Self {
+        let mut builder = builder
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(10));
+        if allow_private {
+            builder = builder.redirect(reqwest::redirect::Policy::limited(5));
+        } else {
+            // Names are vetted by the resolver on every connection; IP
+            // literals never reach it, so redirects to them are checked here
+            builder = builder.dns_resolver(Arc::new(PublicResolver)).redirect(
+                reqwest::redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= 5 {
+                        attempt.error("too many redirects")
+                    } else if attempt.url().host_str().is_some_and(is_private_ip_literal) {
+                        attempt.error("redirect to a private address")
+                    } else {
+                        attempt.follow()
+                    }
+                }),
+            );
+        }
+        let client = builder.build().expect("failed to build HTTP client");
+        ReqwestClient {
+            client,
+            allow_private,
+        }
+    }
+
+    fn ensure_public(&self, url: &reqwest::Url) -> anyhow::Result<()> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("URL has no host"))?;
+        if !self.allow_private && is_private_ip_literal(host) {
+            anyhow::bail!("refusing to connect to private address {}", host);
+        }
+        Ok(())
+    }
+}
+
+/// Resolves names like the system resolver but drops non-public addresses,
+/// so the lookup that is checked is the one connected to, for the first
+/// request and every redirect alike.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| !is_private_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn execute(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let url = reqwest::Url::parse(&request.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("unsupported URL scheme {}", url.scheme());
+        }
+        self.ensure_public(&url)?;
+
+        let mut builder = self.client.request(request.method, url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let mut response = builder.send().await?;
+        let status = response.status().as_u16();
+        let final_url = response.url().to_string();
+        let headers = response.headers().clone();
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            let room = MAX_BODY_BYTES - body.len();
+            body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            if body.len() >= MAX_BODY_BYTES {
+                break;
+            }
+        }
+
+        Ok(HttpResponse {
+            status,
+            url: final_url,
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+}
+
+fn is_private_ip_literal(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<IpAddr>().is_ok_and(is_private_ip)
+}
+
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // 100.64.0.0/10 carrier-grade NAT
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6.is_unspecified()
+                // fc00::/7 unique local, fe80::/10 link local
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80
+                || v6.to_ipv4_mapped().is_some_and(|v4| is_private_ip(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// Serve `app` on a free loopback port as a stand-in for another site,
+/// returning its base URL. `app` is built from that URL, for documents that
+/// link back to the server itself.
+#[cfg(test)]
+pub(crate) async fn stand_in_server(app: impl FnOnce(&str) -> axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let app = app(&base);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_ips() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "169.254.1.1",
+            "::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_client_refuses_loopback() {
+        let client = ReqwestClient::new(false);
+        let err = client
+            .execute(HttpRequest::get("http://127.0.0.1:9/"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("private address"));
+    }
+
+    #[tokio::test]
+    async fn test_public_client_refuses_redirect_to_private_name() {
+        let base = stand_in_server(|base| {
+            let private = base.replace("127.0.0.1", "localhost");
+            axum::Router::new()
+                .route("/ok", axum::routing::get(|| async { "ok" }))
+                .route(
+                    "/redirect",
+                    axum::routing::get(move || async move {
+                        axum::response::Redirect::temporary(&format!("{}/ok", private))
+                    }),
+                )
+        })
+        .await;
+        // `public.test` stands in for a public name that reaches the server
+        let addr: SocketAddr = base.trim_start_matches("http://").parse().unwrap();
+        let client = ReqwestClient::with_builder(
+            false,
+            reqwest::Client::builder().resolve("public.test", addr),
+        );
+        let public = format!("http://public.test:{}", addr.port());
+
+        let ok = client
+            .execute(HttpRequest::get(&format!("{}/ok", public)))
+            .await
+            .unwrap();
+        assert_eq!(ok.body, "ok");
+
+        // `localhost` resolves to loopback, which the resolver drops
+        let err = client
+            .execute(HttpRequest::get(&format!("{}/redirect", public)))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:?}", err).contains("has no public address"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_post_form_encodes_params() {
+        let req = HttpRequest::post_form(
+            "https://example.com",
+            &[("source", "a b"), ("target", "x&y")],
+        );
+        assert_eq!(req.body.unwrap(), b"source=a+b&target=x%26y");
+    }
+}
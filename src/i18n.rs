@@ -200,6 +200,7 @@ pub struct Translations {
     pub comments_enter_password_edit: &'static str,
     pub comments_edit_prompt: &'static str,
     pub comments_wrong_password: &'static str,
+    pub comments_locked: &'static str,
     pub comments_failed_edit: &'static str,
     pub comments_enter_password_delete: &'static str,
     pub comments_confirm_delete: &'static str,
@@ -239,6 +240,12 @@ pub struct Translations {
     pub guestbook_no_entries_message: &'static str,
     pub guestbook_enter_both: &'static str,
     pub guestbook_failed: &'static str,
+    pub guestbook_website: &'static str,
+    pub guestbook_website_hint: &'static str,
+    pub guestbook_website_placeholder: &'static str,
+    pub guestbook_invalid_website: &'static str,
+    pub guestbook_owner_reply: &'static str,
+    pub guestbook_pinned: &'static str,
 
     // Error
     pub error_title: &'static str,
@@ -280,7 +287,7 @@ pub struct Translations {
 
     // Post stats
     pub post_views: &'static str,
-    pub post_likes: &'static str,
+    pub post_reactions: &'static str,
 
     // Visitor stats
     pub visitor_today: &'static str,
@@ -289,6 +296,13 @@ pub struct Translations {
 
     // Rate limit
     pub rate_limit: &'static str,
+
+    // Moderation
+    pub moderation_held: &'static str,
+
+    // Webmentions
+    pub webmentions_title: &'static str,
+    pub fediverse_replies_title: &'static str,
 }
 
 impl Translations {
@@ -364,6 +378,7 @@ impl Translations {
             comments_enter_password_edit: "Enter your password to edit:",
             comments_edit_prompt: "Edit your comment:",
             comments_wrong_password: "Wrong password or comment not found.",
+            comments_locked: "Too many wrong passwords. Please try again later.",
             comments_failed_edit: "Failed to edit comment.",
             comments_enter_password_delete: "Enter your password to delete:",
             comments_confirm_delete: "Are you sure you want to delete this comment?",
@@ -403,6 +418,12 @@ impl Translations {
             guestbook_no_entries_message: "Be the first to leave a message!",
             guestbook_enter_both: "Please enter both name and message.",
             guestbook_failed: "Failed to post. Please try again.",
+            guestbook_website: "Website",
+            guestbook_website_hint: "(optional)",
+            guestbook_website_placeholder: "https://example.com",
+            guestbook_invalid_website: "Please enter a website address starting with http:// or https://.",
+            guestbook_owner_reply: "Reply from the owner",
+            guestbook_pinned: "Pinned",
 
             // Error
             error_title: "404",
@@ -444,7 +465,7 @@ impl Translations {
 
             // Post stats
             post_views: "views",
-            post_likes: "likes",
+            post_reactions: "reactions",
 
             // Visitor stats
             visitor_today: "Today",
@@ -453,6 +474,9 @@ impl Translations {
 
             // Rate limit
             rate_limit: "Too many requests. Please wait a moment.",
+            moderation_held: "Thanks! Your message will appear once it has been reviewed.",
+            webmentions_title: "Mentions",
+            fediverse_replies_title: "Replies from the fediverse",
         }
     }
 
@@ -513,6 +537,7 @@ impl Translations {
             comments_enter_password_edit: "댓글을 수정하려면 비밀번호를 입력하세요:",
             comments_edit_prompt: "댓글을 수정하세요:",
             comments_wrong_password: "비밀번호가 틀렸거나 댓글을 찾을 수 없습니다.",
+            comments_locked: "비밀번호를 너무 많이 틀렸습니다. 잠시 후 다시 시도해 주세요.",
             comments_failed_edit: "댓글 수정에 실패했습니다.",
             comments_enter_password_delete: "댓글을 삭제하려면 비밀번호를 입력하세요:",
             comments_confirm_delete: "정말 이 댓글을 삭제하시겠습니까?",
@@ -553,6 +578,13 @@ impl Translations {
             guestbook_no_entries_message: "첫 번째 메시지를 남겨 보세요!",
             guestbook_enter_both: "이름과 메시지를 모두 입력해 주세요.",
             guestbook_failed: "작성에 실패했습니다. 다시 시도해 주세요.",
+            guestbook_website: "웹사이트",
+            guestbook_website_hint: "(선택)",
+            guestbook_website_placeholder: "https://example.com",
+            guestbook_invalid_website:
+                "http:// 또는 https://로 시작하는 웹사이트 주소를 입력해 주세요.",
+            guestbook_owner_reply: "주인장 답글",
+            guestbook_pinned: "고정됨",
 
             // Error
             error_title: "404",
@@ -595,7 +627,7 @@ impl Translations {
 
             // Post stats
             post_views: "조회",
-            post_likes: "좋아요",
+            post_reactions: "반응",
 
             // Visitor stats
             visitor_today: "오늘",
@@ -604,6 +636,9 @@ impl Translations {
 
             // Rate limit
             rate_limit: "요청이 너무 많습니다. 잠시 후 다시 시도해 주세요.",
+            moderation_held: "감사합니다! 메시지는 검토 후 게시됩니다.",
+            webmentions_title: "멘션",
+            fediverse_replies_title: "연합우주 답글",
         }
     }
 
@@ -663,6 +698,7 @@ impl Translations {
             comments_enter_password_edit: "コメントを編集するにはパスワードを入力してください：",
             comments_edit_prompt: "コメントを編集：",
             comments_wrong_password: "パスワードが正しくないか、コメントが見つかりません。",
+            comments_locked: "パスワードの誤りが多すぎます。しばらくしてからもう一度お試しください。",
             comments_failed_edit: "コメントの編集に失敗しました。",
             comments_enter_password_delete: "コメントを削除するにはパスワードを入力してください：",
             comments_confirm_delete: "このコメントを削除してもよろしいですか？",
@@ -702,6 +738,12 @@ impl Translations {
             guestbook_no_entries_message: "最初のメッセージを残してみましょう！",
             guestbook_enter_both: "名前とメッセージを入力してください。",
             guestbook_failed: "投稿に失敗しました。もう一度お試しください。",
+            guestbook_website: "ウェブサイト",
+            guestbook_website_hint: "(任意)",
+            guestbook_website_placeholder: "https://example.com",
+            guestbook_invalid_website: "http:// または https:// で始まるウェブサイトのアドレスを入力してください。",
+            guestbook_owner_reply: "管理人からの返信",
+            guestbook_pinned: "ピン留め",
 
             // Error
             error_title: "404",
@@ -743,7 +785,7 @@ impl Translations {
 
             // Post stats
             post_views: "閲覧",
-            post_likes: "いいね",
+            post_reactions: "リアクション",
 
             // Visitor stats
             visitor_today: "今日",
@@ -752,6 +794,9 @@ impl Translations {
 
             // Rate limit
             rate_limit: "リクエストが多すぎます。しばらくしてからもう一度お試しください。",
+            moderation_held: "ありがとうございます！メッセージは確認後に公開されます。",
+            webmentions_title: "メンション",
+            fediverse_replies_title: "Fediverseからの返信",
         }
     }
 }
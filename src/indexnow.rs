@@ -0,0 +1,225 @@
+//! IndexNow: tell search engines which post URLs are new or changed.
+//!
+//! Each post URL is submitted with its `updated_at` as the version. The last
+//! submitted version is kept in SQLite, so a reload only reports the posts
+//! that were added or edited since.
+
+use crate::{
+    config::IndexNowConfig,
+    db::Database,
+    http::{HttpClient, HttpRequest},
+    post::Post,
+    SITE_URL,
+};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Published at `/assets/indexnow-key.txt`; the key location sent with
+/// every submission must match.
+pub const KEY_FILE: &str = "assets/indexnow-key.txt";
+
+/// `(url, version)` for every post, translations included.
+pub fn post_versions(posts: &[Post]) -> Vec<(String, String)> {
+    posts
+        .iter()
+        .map(|p| {
+            (
+                format!("{}/post/{}", SITE_URL, p.slug),
+                p.metadata.updated_at.to_rfc3339(),
+            )
+        })
+        .collect()
+}
+
+/// URLs that were never submitted or whose version changed since.
+pub fn changed_urls(
+    current: Vec<(String, String)>,
+    submitted: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    current
+        .into_iter()
+        .filter(|(url, version)| submitted.get(url) != Some(version))
+        .collect()
+}
+
+/// Submit changed URLs in batches and record each batch once it is
+/// accepted. Returns how many URLs were submitted (or would have been, in
+/// dry-run mode). Stops at the first rejected batch; the rest are retried
+/// on the next content load.
+pub async fn submit_changes(
+    db: &Database,
+    http: &dyn HttpClient,
+    config: &IndexNowConfig,
+    key: &str,
+    current: Vec<(String, String)>,
+) -> anyhow::Result<usize> {
+    let changed = changed_urls(current, &db.get_indexnow_versions().await?);
+    if changed.is_empty() {
+        return Ok(0);
+    }
+    if config.dry_run {
+        for (url, _) in &changed {
+            tracing::info!("IndexNow dry run: would submit {}", url);
+        }
+        return Ok(changed.len());
+    }
+
+    let host = url::Url::parse(SITE_URL)?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+    let mut submitted = 0;
+    for batch in changed.chunks(config.batch_size.max(1)) {
+        let body = json!({
+            "host": host,
+            "key": key,
+            "keyLocation": format!("{}/{}", SITE_URL, KEY_FILE),
+            "urlList": batch.iter().map(|(url, _)| url).collect::<Vec<_>>(),
+        });
+        let request = HttpRequest::post(
+            &config.endpoint,
+            "application/json; charset=utf-8",
+            body.to_string().into_bytes(),
+        );
+        let response = http.execute(request).await?;
+        if !response.is_success() {
+            anyhow::bail!("IndexNow endpoint returned {}", response.status);
+        }
+        db.record_indexnow_submissions(batch).await?;
+        submitted += batch.len();
+    }
+    tracing::info!("Submitted {} URLs to IndexNow", submitted);
+    Ok(submitted)
+}
+
+/// Background entry point: read the key and submit whatever changed.
+pub async fn submit_for_posts(
+    db: &Database,
+    http: &dyn HttpClient,
+    config: &IndexNowConfig,
+    current: Vec<(String, String)>,
+) {
+    let key = match tokio::fs::read_to_string(KEY_FILE).await {
+        Ok(key) if !key.trim().is_empty() => key.trim().to_string(),
+        Ok(_) | Err(_) => {
+            tracing::warn!("IndexNow is enabled but {} is missing or empty", KEY_FILE);
+            return;
+        }
+    };
+    if let Err(e) = submit_changes(db, http, config, &key, current).await {
+        tracing::warn!("IndexNow submission failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use crate::http::{stand_in_server, ReqwestClient};
+    use crate::post::sample_post;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use chrono::DateTime;
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    /// A stand-in IndexNow endpoint that answers with `status`.
+    async fn serve(status: StatusCode) -> (String, Received) {
+        let received: Received = Arc::default();
+        let app =
+            Router::new()
+                .route(
+                    "/indexnow",
+                    post(
+                        move |State(received): State<Received>,
+                              Json(body): Json<serde_json::Value>| async move {
+                            received.lock().unwrap().push(body);
+                            status
+                        },
+                    ),
+                )
+                .with_state(received.clone());
+        let base = stand_in_server(|_| app).await;
+        (format!("{}/indexnow", base), received)
+    }
+
+    fn config(endpoint: &str) -> IndexNowConfig {
+        IndexNowConfig {
+            enabled: true,
+            endpoint: endpoint.to_string(),
+            batch_size: 2,
+            dry_run: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submits_only_changes_in_batches() {
+        let db = test_db().await;
+        let http = ReqwestClient::new(true);
+        let (endpoint, received) = serve(StatusCode::ACCEPTED).await;
+        let config = config(&endpoint);
+        let mut posts = vec![sample_post("a"), sample_post("b"), sample_post("c")];
+
+        let sent = submit_changes(&db, &http, &config, "k", post_versions(&posts))
+            .await
+            .unwrap();
+        assert_eq!(sent, 3);
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[0]["host"], "miniex.blog");
+            assert_eq!(received[0]["key"], "k");
+            assert_eq!(
+                received[0]["keyLocation"],
+                "https://miniex.blog/assets/indexnow-key.txt"
+            );
+            assert_eq!(
+                received[1]["urlList"],
+                json!(["https://miniex.blog/post/c"])
+            );
+        }
+
+        let sent = submit_changes(&db, &http, &config, "k", post_versions(&posts))
+            .await
+            .unwrap();
+        assert_eq!(sent, 0);
+
+        posts[1].metadata.updated_at =
+            DateTime::parse_from_rfc3339("2024-02-01T00:00:00+09:00").unwrap();
+        let sent = submit_changes(&db, &http, &config, "k", post_versions(&posts))
+            .await
+            .unwrap();
+        assert_eq!(sent, 1);
+        let received = received.lock().unwrap();
+        assert_eq!(
+            received[2]["urlList"],
+            json!(["https://miniex.blog/post/b"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_and_rejection_record_nothing() {
+        let db = test_db().await;
+        let http = ReqwestClient::new(true);
+        let posts = vec![sample_post("a")];
+
+        let (endpoint, received) = serve(StatusCode::ACCEPTED).await;
+        let dry_run = IndexNowConfig {
+            dry_run: true,
+            ..config(&endpoint)
+        };
+        let sent = submit_changes(&db, &http, &dry_run, "k", post_versions(&posts))
+            .await
+            .unwrap();
+        assert_eq!(sent, 1);
+        assert!(received.lock().unwrap().is_empty());
+
+        let (endpoint, _) = serve(StatusCode::UNPROCESSABLE_ENTITY).await;
+        assert!(
+            submit_changes(&db, &http, &config(&endpoint), "k", post_versions(&posts))
+                .await
+                .is_err()
+        );
+        assert!(db.get_indexnow_versions().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! schema.org structured data, built as JSON and embedded by `_base.html`
+//! as one `application/ld+json` graph per page.
+
+use crate::{
+    post::{author_name, Post, Series},
+    SITE_DESCRIPTION, SITE_URL,
+};
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use serde_json::{json, Value};
+
+const SITE_NAME: &str = "miniex.blog";
+const OWNER: &str = "Han Damin";
+
+fn date(d: DateTime<FixedOffset>) -> String {
+    d.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn person(name: &str) -> Value {
+    json!({ "@type": "Person", "name": name })
+}
+
+fn publisher() -> Value {
+    json!({ "@type": "Organization", "name": SITE_NAME, "url": SITE_URL })
+}
+
+/// `@context` plus a `@graph` of `nodes`, safe to place inside a `<script>`.
+pub fn graph(nodes: Vec<Value>) -> String {
+    json!({ "@context": "https://schema.org", "@graph": nodes })
+        .to_string()
+        .replace("</", "<\\/")
+}
+
+/// The index page: the site with a search box, and its owner.
+pub fn website() -> Value {
+    json!({
+        "@type": "WebSite",
+        "@id": format!("{}/#website", SITE_URL),
+        "name": SITE_NAME,
+        "description": SITE_DESCRIPTION,
+        "url": SITE_URL,
+        "author": { "@id": format!("{}/#owner", SITE_URL) },
+        "potentialAction": {
+            "@type": "SearchAction",
+            "target": {
+                "@type": "EntryPoint",
+                "urlTemplate": format!("{}/?q={{search_term_string}}", SITE_URL),
+            },
+            "query-input": "required name=search_term_string",
+        },
+    })
+}
+
+pub fn owner() -> Value {
+    json!({
+        "@type": "Person",
+        "@id": format!("{}/#owner", SITE_URL),
+        "name": OWNER,
+        "url": SITE_URL,
+    })
+}
+
+pub fn blog_posting(post: &Post, url: &str, image: &str) -> Value {
+    let mut posting = json!({
+        "@type": "BlogPosting",
+        "@id": format!("{}#article", url),
+        "headline": post.metadata.title,
+        "description": post.metadata.description,
+        "datePublished": date(post.metadata.created_at),
+        "dateModified": date(post.metadata.updated_at),
+        "author": person(post.metadata.author_name()),
+        "publisher": publisher(),
+        "keywords": post.metadata.tags.join(", "),
+        "inLanguage": post.lang.as_str(),
+        "image": image,
+        "wordCount": post.word_count,
+        "url": url,
+        "mainEntityOfPage": { "@type": "WebPage", "@id": url },
+    });
+    if let Some(series) = &post.metadata.series {
+        posting["isPartOf"] = json!({
+            "@type": "CreativeWorkSeries",
+            "name": series,
+            "url": series_url(series),
+        });
+    }
+    posting
+}
+
+pub fn series_url(name: &str) -> String {
+    let mut url = url::Url::parse(SITE_URL).unwrap();
+    url.path_segments_mut().unwrap().extend(["series", name]);
+    url.to_string()
+}
+
+/// A series with its posts in reading order.
+pub fn creative_work_series(series: &Series, posts: &[&Post]) -> Value {
+    let url = series_url(&series.name);
+    json!({
+        "@type": "CreativeWorkSeries",
+        "@id": format!("{}#series", url),
+        "name": series.name,
+        "description": series.description,
+        "url": url,
+        "dateModified": date(series.updated_at),
+        "author": series
+            .authors
+            .iter()
+            .map(|a| person(author_name(a)))
+            .collect::<Vec<_>>(),
+        "publisher": publisher(),
+        "hasPart": posts
+            .iter()
+            .map(|p| json!({
+                "@type": "BlogPosting",
+                "headline": p.metadata.title,
+                "url": format!("{}/post/{}", SITE_URL, p.slug),
+                "inLanguage": p.lang.as_str(),
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// `(name, url)` pairs from the home page down to the current page.
+pub fn breadcrumbs(items: &[(&str, &str)]) -> Value {
+    json!({
+        "@type": "BreadcrumbList",
+        "itemListElement": items
+            .iter()
+            .enumerate()
+            .map(|(i, (name, url))| json!({
+                "@type": "ListItem",
+                "position": i + 1,
+                "name": name,
+                "item": url,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::sample_post;
+
+    #[test]
+    fn test_blog_posting() {
+        let mut post = sample_post("hello");
+        post.metadata.author = "Han Damin <me@example.com>".to_string();
+        post.metadata.series = Some("양자역학".to_string());
+        let url = "https://miniex.blog/post/hello";
+        let posting = blog_posting(&post, url, "https://miniex.blog/og/hello.png");
+
+        assert_eq!(posting["datePublished"], "2024-01-01T00:00:00+09:00");
+        assert_eq!(posting["author"]["name"], "Han Damin");
+        assert_eq!(posting["inLanguage"], "en");
+        assert_eq!(posting["keywords"], "rust");
+        assert_eq!(posting["wordCount"], 1);
+        assert_eq!(
+            posting["isPartOf"]["url"],
+            "https://miniex.blog/series/%EC%96%91%EC%9E%90%EC%97%AD%ED%95%99"
+        );
+    }
+
+    #[test]
+    fn test_graph_is_script_safe() {
+        let mut post = sample_post("hello");
+        post.metadata.title = "</script><script>alert(1)</script>".to_string();
+        let out = graph(vec![blog_posting(
+            &post,
+            "https://miniex.blog/post/hello",
+            "",
+        )]);
+        assert!(!out.contains("</script>"));
+
+        let parsed: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(parsed["@context"], "https://schema.org");
+        assert_eq!(
+            parsed["@graph"][0]["headline"],
+            "</script><script>alert(1)</script>"
+        );
+    }
+
+    #[test]
+    fn test_breadcrumbs_positions() {
+        let list = breadcrumbs(&[
+            ("miniex.blog", "https://miniex.blog/"),
+            ("blog", "https://miniex.blog/blog"),
+        ]);
+        assert_eq!(list["itemListElement"][1]["position"], 2);
+        assert_eq!(
+            list["itemListElement"][1]["item"],
+            "https://miniex.blog/blog"
+        );
+    }
+}
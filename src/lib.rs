@@ -1,16 +1,47 @@
+pub mod activitypub;
+pub mod analytics;
+pub mod assets;
+pub mod auth;
+pub mod bots;
+pub mod config;
+pub mod csp;
 pub mod db;
 pub mod error;
+pub mod feed;
 pub mod filters;
 pub mod handlers;
+pub mod http;
 pub mod i18n;
+pub mod indexnow;
+pub mod jsonld;
+pub mod lockout;
+pub mod metrics;
+pub mod notify;
+pub mod og;
 pub mod post;
+pub mod ratelimit;
+pub mod reactions;
 pub mod router;
+pub mod sanitize;
+pub mod sitemap;
+pub mod spam;
 pub mod templates;
+pub mod vendor;
+pub mod visitor;
+pub mod webmention;
 
+use activitypub::Federation;
+use bots::BotFilter;
+use config::Config;
 use db::Database;
-use post::{Post, Series};
+use http::HttpClient;
+use metrics::Metrics;
+use og::OgRenderer;
+use post::{ContentSnapshot, Post, Series};
+use spam::SpamFilter;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use visitor::VisitorKeys;
 
 pub const SITE_URL: &str = "https://miniex.blog";
 pub const SITE_DESCRIPTION: &str =
@@ -23,6 +54,76 @@ pub struct SharedState {
     pub posts: AppState,
     pub db: Database,
     pub series_cache: Arc<RwLock<Vec<Series>>>,
+    pub config: Arc<Config>,
+    pub spam: Arc<SpamFilter>,
+    pub http: Arc<dyn HttpClient>,
+    /// Present when ActivityPub federation is enabled.
+    pub federation: Option<Arc<Federation>>,
+    pub og: Arc<OgRenderer>,
+    pub bots: Arc<BotFilter>,
+    pub visitor_keys: Arc<VisitorKeys>,
+    pub metrics: Arc<Metrics>,
+    /// The last successful content load, `None` until the first one.
+    pub content: Arc<RwLock<Option<ContentSnapshot>>>,
+}
+
+impl SharedState {
+    /// Remember the posts just loaded, for readiness checks.
+    pub async fn record_content_snapshot(&self) {
+        let snapshot = ContentSnapshot::new(&self.posts.read().await, chrono::Utc::now());
+        *self.content.write().await = Some(snapshot);
+    }
+
+    /// Background work that follows every content load (startup and reload).
+    pub fn spawn_content_tasks(&self) {
+        if self.config.webmention_send_enabled {
+            let state = self.clone();
+            tokio::spawn(async move {
+                let cutoff = chrono::Utc::now()
+                    - chrono::Duration::days(state.config.webmention_send_max_age_days);
+                let posts: Vec<webmention::OutgoingPost> = state
+                    .posts
+                    .read()
+                    .await
+                    .iter()
+                    .filter(|p| p.metadata.created_at >= cutoff)
+                    .map(|p| webmention::OutgoingPost {
+                        slug: p.slug.clone(),
+                        content: p.content.clone(),
+                    })
+                    .collect();
+                webmention::send_for_posts(&state.db, state.http.as_ref(), &posts).await;
+            });
+        }
+        if self.config.indexnow.enabled {
+            let state = self.clone();
+            tokio::spawn(async move {
+                let current = indexnow::post_versions(&state.posts.read().await);
+                indexnow::submit_for_posts(
+                    &state.db,
+                    state.http.as_ref(),
+                    &state.config.indexnow,
+                    current,
+                )
+                .await;
+            });
+        }
+        if let Some(federation) = self.federation.clone() {
+            let state = self.clone();
+            tokio::spawn(async move {
+                let posts = state.posts.read().await.clone();
+                federation
+                    .publish_new_posts(&state.db, state.http.as_ref(), &posts)
+                    .await;
+            });
+        }
+    }
+}
+
+/// An extra Atom feed advertised with `<link rel="alternate">` on a page.
+pub struct FeedLink {
+    pub title: String,
+    pub href: String,
 }
 
 #[derive(Default)]
@@ -31,32 +132,37 @@ pub struct Blog {
     pub description: String,
     pub url: String,
     pub og_type: String,
-    pub v: String,
+    /// Absolute `og:image` URL.
+    pub image: String,
+    pub feeds: Vec<FeedLink>,
+    /// Serialized schema.org graph, see [`jsonld::graph`].
+    pub json_ld: Option<String>,
+    /// Nonce for inline `<script>` and `<style>` tags, see [`csp`].
+    pub nonce: String,
 }
 
 impl Blog {
     pub fn new() -> Self {
-        use std::sync::OnceLock;
-        use std::time::{SystemTime, UNIX_EPOCH};
-
-        static VERSION: OnceLock<String> = OnceLock::new();
-        let v = VERSION
-            .get_or_init(|| {
-                let secs = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                format!("{:x}", secs)
-            })
-            .clone();
-
         Blog {
             og_type: "website".to_string(),
-            v,
+            image: format!("{}/assets/favicon/sakura-flower-512-238032.png", SITE_URL),
+            nonce: csp::current_nonce(),
             ..Blog::default()
         }
     }
 
+    /// A library from `vendor.json`, for `src`/`href` and `integrity`.
+    pub fn asset(&self, name: &str) -> vendor::VendorAsset {
+        vendor::assets().get(name)
+    }
+
+    /// Comma-separated vendored libraries as JSON, for scripts that load
+    /// them on demand.
+    pub fn assets_json(&self, names: &str) -> String {
+        let names: Vec<&str> = names.split(',').map(str::trim).collect();
+        vendor::assets().json(&names)
+    }
+
     pub fn set_title(mut self, title: &str) -> Self {
         self.title = title.to_string();
         self
@@ -76,4 +182,23 @@ impl Blog {
         self.og_type = og_type.to_string();
         self
     }
+
+    pub fn set_image(mut self, image: &str) -> Self {
+        self.image = image.to_string();
+        self
+    }
+
+    pub fn set_json_ld(mut self, nodes: Vec<serde_json::Value>) -> Self {
+        self.json_ld = Some(jsonld::graph(nodes));
+        self
+    }
+
+    /// Advertise an Atom feed rooted at `base_path` (e.g. `/tags/rust`).
+    pub fn add_feed(mut self, title: &str, base_path: &str) -> Self {
+        self.feeds.push(FeedLink {
+            title: title.to_string(),
+            href: format!("{}/feed.xml", base_path),
+        });
+        self
+    }
 }
@@ -0,0 +1,318 @@
+//! Lockouts after repeated wrong passwords.
+//!
+//! Comment and guestbook edits and deletes are guarded only by the password
+//! the author picked, which may be short. Wrong passwords are counted per
+//! item and per client address; past the threshold the subject is locked,
+//! and every further failure doubles the lock up to the configured maximum.
+//! A correct password clears the item's count but not the client's.
+//!
+//! Each attempt is counted as a failure before its password is checked, in
+//! the same transaction as the lock check, and taken back if the password
+//! was right; otherwise parallel guesses could all pass the check before
+//! any of them is counted.
+
+use crate::{
+    config::PasswordLockoutConfig,
+    db::{Database, ItemKind, PasswordAttempt},
+    ratelimit::client_key,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::net::IpAddr;
+
+/// What a lock applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// The comment or guestbook entry, whoever is guessing.
+    Item,
+    /// The client address, whatever it is guessing at.
+    Client,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Item => "item",
+            Scope::Client => "client",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lock {
+    pub scope: Scope,
+    pub until: DateTime<Utc>,
+}
+
+impl Lock {
+    /// Whole seconds until the lock lifts, at least 1.
+    pub fn retry_after_secs(&self, now: DateTime<Utc>) -> u64 {
+        let secs = (self.until - now).num_seconds() + 1;
+        secs.max(1) as u64
+    }
+}
+
+/// A password check on one item by one client.
+pub struct Attempt<'a> {
+    pub kind: ItemKind,
+    pub id: &'a str,
+    pub client: IpAddr,
+}
+
+impl Attempt<'_> {
+    fn item_subject(&self) -> String {
+        format!("{}:{}", self.kind.as_str(), self.id)
+    }
+
+    fn client_subject(&self) -> String {
+        format!("client:{}", client_key(self.client))
+    }
+
+    fn scope_of(&self, subject: &str) -> Scope {
+        if subject == self.client_subject() {
+            Scope::Client
+        } else {
+            Scope::Item
+        }
+    }
+}
+
+/// Lock length after `failures` wrong passwords, or `None` below `threshold`.
+pub fn lock_duration(
+    config: &PasswordLockoutConfig,
+    failures: u32,
+    threshold: u32,
+) -> Option<Duration> {
+    let doublings = failures.checked_sub(threshold)?;
+    let factor = 1i32.checked_shl(doublings.min(30)).unwrap_or(i32::MAX);
+    Some(
+        config
+            .base
+            .checked_mul(factor)
+            .map_or(config.max, |d| d.min(config.max)),
+    )
+}
+
+/// An attempt that has been counted as a failure ahead of its check.
+#[derive(Debug)]
+pub struct Counted {
+    /// Locks this attempt set by reaching a threshold, with failure counts.
+    locks: Vec<(Lock, u32)>,
+}
+
+impl Counted {
+    /// The longest lock this attempt set.
+    pub fn lock(&self) -> Option<&Lock> {
+        self.locks
+            .iter()
+            .map(|(lock, _)| lock)
+            .max_by_key(|lock| lock.until)
+    }
+}
+
+/// Whether `attempt` may check its password.
+#[derive(Debug)]
+pub enum Admission {
+    /// A lock is in force; the password must not be checked.
+    Locked(Lock),
+    Counted(Counted),
+}
+
+/// Count `attempt` as a failure unless the item or client is locked. Follow
+/// up with [`record_success`] or [`record_failure`] once the password has
+/// been checked.
+pub async fn admit(
+    db: &Database,
+    config: &PasswordLockoutConfig,
+    attempt: &Attempt<'_>,
+    now: DateTime<Utc>,
+) -> Result<Admission, sqlx::Error> {
+    let subjects = [
+        (attempt.item_subject(), config.item_threshold),
+        (attempt.client_subject(), config.client_threshold),
+    ];
+    let counted = db
+        .count_password_attempt(&subjects, now, now - config.max, |failures, threshold| {
+            lock_duration(config, failures, threshold)
+        })
+        .await?;
+    Ok(match counted {
+        PasswordAttempt::Locked(subject, until) => Admission::Locked(Lock {
+            scope: attempt.scope_of(&subject),
+            until,
+        }),
+        PasswordAttempt::Counted(locked) => Admission::Counted(Counted {
+            locks: locked
+                .into_iter()
+                .map(|(subject, failures, until)| {
+                    let scope = attempt.scope_of(&subject);
+                    (Lock { scope, until }, failures)
+                })
+                .collect(),
+        }),
+    })
+}
+
+/// Log a wrong password and the locks it set. The returned lock is the
+/// longest one.
+pub fn record_failure(attempt: &Attempt<'_>, counted: &Counted) -> Option<Lock> {
+    for (lock, failures) in &counted.locks {
+        let subject = match lock.scope {
+            Scope::Item => attempt.item_subject(),
+            Scope::Client => attempt.client_subject(),
+        };
+        tracing::warn!(
+            "Locked {} until {} after {} wrong passwords",
+            subject,
+            lock.until.to_rfc3339(),
+            failures
+        );
+    }
+    tracing::warn!(
+        "Wrong password for {} {} from {}",
+        attempt.kind.as_str(),
+        attempt.id,
+        attempt.client
+    );
+    counted.lock().cloned()
+}
+
+/// After a correct password, forget the item's failures and take back the
+/// client failure counted for this attempt.
+pub async fn record_success(
+    db: &Database,
+    attempt: &Attempt<'_>,
+    counted: &Counted,
+) -> Result<(), sqlx::Error> {
+    db.clear_password_failures(&attempt.item_subject()).await?;
+    let client_lock = counted
+        .locks
+        .iter()
+        .find(|(lock, _)| lock.scope == Scope::Client)
+        .map(|(lock, _)| lock.until);
+    db.uncount_password_failure(&attempt.client_subject(), client_lock)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_duration_doubles_up_to_max() {
+        let config = PasswordLockoutConfig::default();
+        assert_eq!(lock_duration(&config, 4, 5), None);
+        assert_eq!(lock_duration(&config, 5, 5), Some(Duration::minutes(1)));
+        assert_eq!(lock_duration(&config, 6, 5), Some(Duration::minutes(2)));
+        assert_eq!(lock_duration(&config, 8, 5), Some(Duration::minutes(8)));
+        assert_eq!(lock_duration(&config, 20, 5), Some(config.max));
+        assert_eq!(lock_duration(&config, u32::MAX, 5), Some(config.max));
+    }
+
+    /// Admit `attempt` and fail it, returning the lock in the way.
+    async fn fail(
+        db: &Database,
+        config: &PasswordLockoutConfig,
+        attempt: &Attempt<'_>,
+        now: DateTime<Utc>,
+    ) -> Option<Lock> {
+        match admit(db, config, attempt, now).await.unwrap() {
+            Admission::Locked(lock) => Some(lock),
+            Admission::Counted(counted) => record_failure(attempt, &counted),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failures_lock_item_then_client() {
+        let db = crate::db::test_db().await;
+        let config = PasswordLockoutConfig {
+            item_threshold: 2,
+            client_threshold: 3,
+            ..PasswordLockoutConfig::default()
+        };
+        let now = Utc::now();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let first = Attempt {
+            kind: ItemKind::Comment,
+            id: "a",
+            client,
+        };
+
+        assert_eq!(fail(&db, &config, &first, now).await, None);
+        let lock = fail(&db, &config, &first, now).await.unwrap();
+        assert_eq!(lock.scope, Scope::Item);
+        assert_eq!(lock.until, now + Duration::minutes(1));
+        // Locked attempts are turned away without counting
+        assert_eq!(fail(&db, &config, &first, now).await, Some(lock.clone()));
+
+        // A third wrong password anywhere locks the client out of every item
+        let second = Attempt {
+            kind: ItemKind::Guestbook,
+            id: "b",
+            client,
+        };
+        let lock = fail(&db, &config, &second, now).await.unwrap();
+        assert_eq!(lock.scope, Scope::Client);
+        let other = Attempt {
+            kind: ItemKind::Comment,
+            id: "c",
+            client,
+        };
+        assert!(matches!(
+            admit(&db, &config, &other, now).await.unwrap(),
+            Admission::Locked(Lock {
+                scope: Scope::Client,
+                ..
+            })
+        ));
+
+        // Someone else can still use the item once its lock has passed, and
+        // a correct password takes their attempt back
+        let elsewhere = Attempt {
+            kind: ItemKind::Comment,
+            id: "a",
+            client: "198.51.100.1".parse().unwrap(),
+        };
+        let later = now + Duration::minutes(2);
+        for _ in 0..5 {
+            let Admission::Counted(counted) = admit(&db, &config, &elsewhere, later).await.unwrap()
+            else {
+                panic!("a correct password was locked out");
+            };
+            record_success(&db, &elsewhere, &counted).await.unwrap();
+        }
+        assert_eq!(fail(&db, &config, &elsewhere, later).await, None);
+
+        // Failures older than the memory window start over
+        let much_later = later + config.max + Duration::minutes(1);
+        assert_eq!(fail(&db, &config, &first, much_later).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_guesses_stop_at_threshold() {
+        let db = crate::db::test_db().await;
+        let config = PasswordLockoutConfig {
+            item_threshold: 3,
+            ..PasswordLockoutConfig::default()
+        };
+        let now = Utc::now();
+        let guesses = (0..10).map(|i| {
+            let (db, config) = (db.clone(), config.clone());
+            tokio::spawn(async move {
+                let attempt = Attempt {
+                    kind: ItemKind::Comment,
+                    id: "a",
+                    client: IpAddr::from([192, 0, 2, i]),
+                };
+                matches!(
+                    admit(&db, &config, &attempt, now).await.unwrap(),
+                    Admission::Counted(_)
+                )
+            })
+        });
+        let mut admitted = 0;
+        for guess in guesses.collect::<Vec<_>>() {
+            admitted += guess.await.unwrap() as usize;
+        }
+        assert_eq!(admitted, 3);
+    }
+}
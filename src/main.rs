@@ -1,12 +1,26 @@
 use blog::{
+    activitypub::Federation,
+    analytics,
+    auth::hash_admin_password,
+    bots::BotFilter,
+    config::Config,
     db::Database,
+    http::ReqwestClient,
     i18n::Lang,
+    metrics::{self, Metrics},
+    notify::{self, NotificationWorker},
+    og::OgRenderer,
     post::{get_series, load_posts},
     router::create_router,
+    spam::SpamFilter,
+    vendor,
+    visitor::VisitorKeys,
     SharedState,
 };
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::info;
 
@@ -40,8 +54,60 @@ async fn shutdown_signal() {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
+    let port = if cfg!(debug_assertions) { 3000 } else { 80 };
+
+    // `blog hash-password <password>` prints a value for ADMIN_PASSWORD_HASH
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("hash-password") {
+        let password = args.get(2).ok_or("usage: blog hash-password <password>")?;
+        println!("{}", hash_admin_password(password));
+        return Ok(());
+    }
+
+    // `blog vendor` downloads the third-party libraries in vendor.json;
+    // `--pin` records hashes for entries that have none yet
+    if args.get(1).map(String::as_str) == Some("vendor") {
+        vendor::fetch(
+            Path::new(vendor::MANIFEST_PATH),
+            Path::new(vendor::VENDOR_DIR),
+            args.get(2).map(String::as_str) == Some("--pin"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // `blog healthcheck` exits non-zero unless the local instance is ready,
+    // for container health checks
+    if args.get(1).map(String::as_str) == Some("healthcheck") {
+        let response = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()?
+            .get(format!("http://127.0.0.1:{}/health/ready", port))
+            .send()
+            .await?;
+        let ready = response.status().is_success();
+        println!("{}", response.text().await?);
+        if !ready {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Release builds serve every library locally; a missing copy would fall
+    // back to its CDN and widen the Content Security Policy
+    if !cfg!(debug_assertions) {
+        vendor::assets().ensure_complete()?;
+    }
+
+    let config = Config::from_env();
+    let spam = SpamFilter::new(&config);
+    let bots = Arc::new(BotFilter::load(config.bot_user_agents_path.as_deref()));
+
+    let metrics = Arc::new(Metrics::new());
     let app_state = Arc::new(RwLock::new(Vec::new()));
-    load_posts(Arc::clone(&app_state)).await?;
+    let loaded = load_posts(Arc::clone(&app_state)).await;
+    metrics.record_content_load(loaded.is_ok());
+    loaded?;
 
     // Pre-compute series cache from loaded posts
     let series_cache = {
@@ -53,27 +119,68 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         std::fs::create_dir_all("./data").unwrap_or_default();
         "sqlite:./data/blog.db?mode=rwc".to_string()
     });
-    let db = Database::new(&database_url).await?;
+    let notifiers = notify::build_notifiers(&config.notify);
+    let db = Database::new(&database_url)
+        .await?
+        .with_notification_channels(notifiers.iter().map(|n| n.channel()).collect());
+    NotificationWorker::new(db.clone(), notifiers, &config.notify).spawn();
+    analytics::spawn_visitor_compaction(db.clone(), &config);
+
+    let federation = if config.activitypub.enabled {
+        Some(Arc::new(Federation::load(&db, &config.activitypub).await?))
+    } else {
+        None
+    };
+
+    let og = Arc::new(OgRenderer::new(&config.og_cache_dir));
+    let visitor_keys = Arc::new(VisitorKeys::load(&db, &config).await?);
 
     let shared_state = SharedState {
         posts: app_state,
         db,
         series_cache: Arc::new(RwLock::new(series_cache)),
+        config: Arc::new(config),
+        spam: Arc::new(spam),
+        http: Arc::new(ReqwestClient::new(false)),
+        federation,
+        og,
+        bots,
+        visitor_keys,
+        metrics,
+        content: Arc::new(RwLock::new(None)),
     };
+    shared_state.record_content_snapshot().await;
+    shared_state.spawn_content_tasks();
+    if let Some(federation) = shared_state.federation.clone() {
+        federation.spawn_delivery_worker(shared_state.db.clone(), shared_state.http.clone());
+    }
 
     let app = create_router(shared_state.clone());
 
+    // Metrics on their own address, kept off the public site
+    if let (true, Some(addr)) = (
+        shared_state.config.metrics.enabled,
+        shared_state.config.metrics.addr,
+    ) {
+        let metrics_app = axum::Router::new()
+            .route("/metrics", axum::routing::get(metrics::serve_metrics))
+            .with_state(shared_state.clone());
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("Serving metrics on {}", addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, metrics_app).await {
+                tracing::error!("Metrics server failed: {}", e);
+            }
+        });
+    }
+
     #[cfg(debug_assertions)]
     let app = blog::router::add_live_reload(app, shared_state);
 
-    let address = if cfg!(debug_assertions) {
-        "0.0.0.0:3000"
-    } else {
-        "0.0.0.0:80"
-    };
+    let address = format!("0.0.0.0:{}", port);
 
     info!("Starting server on {}", address);
-    let listener = tokio::net::TcpListener::bind(address).await?;
+    let listener = tokio::net::TcpListener::bind(&address).await?;
     axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
@@ -0,0 +1,382 @@
+//! Prometheus metrics, served at `/metrics` in the text exposition format.
+//!
+//! Request counts and latencies are recorded per matched route by
+//! [`track_requests`]; writes, content loads and rate-limit rejections by the
+//! code doing them. Database pool and post gauges are read at scrape time.
+
+use crate::{
+    assets,
+    i18n::Lang,
+    post::{Post, PostType},
+    SharedState,
+};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use std::{
+    collections::HashMap,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+/// Route label for requests answered by the fallback (404 pages).
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: &'static str,
+    route: String,
+    status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PoolLabels {
+    state: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PostLabels {
+    r#type: &'static str,
+    lang: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct WriteLabels {
+    kind: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PolicyLabels {
+    policy: &'static str,
+}
+
+/// Writes counted by [`Metrics::record_write`].
+#[derive(Clone, Copy, Debug)]
+pub enum Write {
+    Comment,
+    Guestbook,
+    Reaction,
+    Visit,
+}
+
+impl Write {
+    fn as_str(self) -> &'static str {
+        match self {
+            Write::Comment => "comment",
+            Write::Guestbook => "guestbook",
+            Write::Reaction => "reaction",
+            Write::Visit => "visit",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    request_duration: Family<RequestLabels, Histogram>,
+    pool_connections: Family<PoolLabels, Gauge>,
+    pool_max_connections: Gauge,
+    posts: Family<PostLabels, Gauge>,
+    content_loads: Family<OutcomeLabels, Counter>,
+    content_load_time: Family<OutcomeLabels, Gauge>,
+    writes: Family<WriteLabels, Counter>,
+    rate_limited: Family<PolicyLabels, Counter>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("blog");
+        let requests = Family::default();
+        registry.register(
+            "http_requests",
+            "HTTP requests by method, matched route and status",
+            requests.clone(),
+        );
+        let request_duration = Family::<RequestLabels, Histogram>::new_with_constructor(
+            duration_histogram as fn() -> _,
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "HTTP request latency by method, matched route and status",
+            request_duration.clone(),
+        );
+        let pool_connections = Family::default();
+        registry.register(
+            "db_pool_connections",
+            "SQLite pool connections by state (idle, in_use)",
+            pool_connections.clone(),
+        );
+        let pool_max_connections = Gauge::default();
+        registry.register(
+            "db_pool_max_connections",
+            "Largest number of SQLite pool connections",
+            pool_max_connections.clone(),
+        );
+        let posts = Family::default();
+        registry.register("posts", "Loaded posts by type and language", posts.clone());
+        let content_loads = Family::default();
+        registry.register(
+            "content_loads",
+            "Content loads (startup and reloads) by outcome",
+            content_loads.clone(),
+        );
+        let content_load_time = Family::default();
+        registry.register(
+            "content_last_load_timestamp_seconds",
+            "Unix time of the last content load by outcome",
+            content_load_time.clone(),
+        );
+        let writes = Family::default();
+        registry.register(
+            "writes",
+            "Comments, guestbook entries, reactions and visits written",
+            writes.clone(),
+        );
+        let rate_limited = Family::default();
+        registry.register(
+            "rate_limited_requests",
+            "Requests rejected by a rate limit policy",
+            rate_limited.clone(),
+        );
+
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            pool_connections,
+            pool_max_connections,
+            posts,
+            content_loads,
+            content_load_time,
+            writes,
+            rate_limited,
+        }
+    }
+
+    fn record_request(&self, method: &'static str, route: &str, status: StatusCode, seconds: f64) {
+        let labels = RequestLabels {
+            method,
+            route: route.to_string(),
+            status: status.as_u16(),
+        };
+        self.requests.get_or_create(&labels).inc();
+        self.request_duration
+            .get_or_create(&labels)
+            .observe(seconds);
+    }
+
+    pub fn record_write(&self, write: Write) {
+        self.writes
+            .get_or_create(&WriteLabels {
+                kind: write.as_str(),
+            })
+            .inc();
+    }
+
+    pub fn record_content_load(&self, ok: bool) {
+        let labels = OutcomeLabels {
+            outcome: if ok { "success" } else { "failure" },
+        };
+        self.content_loads.get_or_create(&labels).inc();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        self.content_load_time.get_or_create(&labels).set(now);
+    }
+
+    pub fn record_rate_limited(&self, policy: &'static str) {
+        self.rate_limited
+            .get_or_create(&PolicyLabels { policy })
+            .inc();
+    }
+
+    /// Counted locally and then set, so concurrent scrapes never see a
+    /// half-built family; pairs with no posts left drop to zero.
+    fn set_post_counts(&self, posts: &[Post]) {
+        let mut counts: HashMap<PostLabels, i64> = HashMap::new();
+        for post in posts {
+            *counts
+                .entry(PostLabels {
+                    r#type: post_type_label(&post.post_type),
+                    lang: post.lang.as_str(),
+                })
+                .or_default() += 1;
+        }
+        for post_type in [PostType::Blog, PostType::Review, PostType::Diary] {
+            for &lang in Lang::all() {
+                let labels = PostLabels {
+                    r#type: post_type_label(&post_type),
+                    lang: lang.as_str(),
+                };
+                let count = counts.get(&labels).copied().unwrap_or_default();
+                self.posts.get_or_create(&labels).set(count);
+            }
+        }
+    }
+
+    /// Refresh the scrape-time gauges and encode everything.
+    pub async fn render(&self, state: &SharedState) -> String {
+        let pool = &state.db.pool;
+        let idle = pool.num_idle() as i64;
+        self.pool_connections
+            .get_or_create(&PoolLabels { state: "idle" })
+            .set(idle);
+        self.pool_connections
+            .get_or_create(&PoolLabels { state: "in_use" })
+            .set((pool.size() as i64 - idle).max(0));
+        self.pool_max_connections
+            .set(pool.options().get_max_connections() as i64);
+
+        self.set_post_counts(&state.posts.read().await);
+
+        let mut out = String::new();
+        if let Err(e) = encode(&mut out, &self.registry) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        out
+    }
+}
+
+fn duration_histogram() -> Histogram {
+    // 5ms .. ~10s
+    Histogram::new(exponential_buckets(0.005, 2.0, 12))
+}
+
+fn post_type_label(post_type: &PostType) -> &'static str {
+    match post_type {
+        PostType::Blog => "blog",
+        PostType::Review => "review",
+        PostType::Diary => "diary",
+    }
+}
+
+/// The standard methods by name, anything else as "other", so clients
+/// cannot add series by sending made-up methods.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+/// Count and time each request under the route it matched.
+pub async fn track_requests(
+    State(state): State<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = method_label(req.method());
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None if req.uri().path().starts_with("/assets/") => assets::ASSETS_ROUTE.to_string(),
+        None => UNMATCHED_ROUTE.to_string(),
+    };
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+    state.metrics.record_request(
+        method,
+        &route,
+        response.status(),
+        start.elapsed().as_secs_f64(),
+    );
+    response
+}
+
+pub async fn serve_metrics(State(state): State<SharedState>) -> Response {
+    let body = state.metrics.render(&state).await;
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::sample_post;
+
+    #[test]
+    fn test_encoding() {
+        let metrics = Metrics::new();
+        metrics.record_request("GET", "/post/:slug", StatusCode::OK, 0.02);
+        metrics.record_request("GET", "/post/:slug", StatusCode::OK, 0.5);
+        metrics.record_write(Write::Comment);
+        metrics.record_content_load(true);
+        metrics.record_rate_limited("submit");
+
+        let mut out = String::new();
+        encode(&mut out, &metrics.registry).unwrap();
+        assert!(out.contains(
+            r#"blog_http_requests_total{method="GET",route="/post/:slug",status="200"} 2"#
+        ));
+        assert!(out.contains(
+            r#"blog_http_request_duration_seconds_count{method="GET",route="/post/:slug",status="200"} 2"#
+        ));
+        assert!(out.contains(r#"blog_writes_total{kind="comment"} 1"#));
+        assert!(out.contains(r#"blog_content_loads_total{outcome="success"} 1"#));
+        assert!(out.contains(r#"blog_content_last_load_timestamp_seconds{outcome="success"} "#));
+        assert!(out.contains(r#"blog_rate_limited_requests_total{policy="submit"} 1"#));
+        assert!(out.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::GET), "GET");
+        assert_eq!(method_label(&Method::DELETE), "DELETE");
+        let made_up = Method::from_bytes(b"X-RANDOM-1234").unwrap();
+        assert_eq!(method_label(&made_up), "other");
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PROPFIND").unwrap()),
+            "other"
+        );
+    }
+
+    #[test]
+    fn test_post_counts() {
+        let metrics = Metrics::new();
+        let posts = [sample_post("a"), sample_post("b")];
+        metrics.set_post_counts(&posts);
+        metrics.set_post_counts(&posts);
+
+        let mut out = String::new();
+        encode(&mut out, &metrics.registry).unwrap();
+        assert!(out.contains(r#"blog_posts{type="blog",lang="en"} 2"#));
+
+        // A pair whose posts are gone reads zero rather than its old count
+        metrics.set_post_counts(&[]);
+        let mut out = String::new();
+        encode(&mut out, &metrics.registry).unwrap();
+        assert!(out.contains(r#"blog_posts{type="blog",lang="en"} 0"#));
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{
+    config::NotifyConfig,
+    db::{Comment, Database, Guestbook, QueuedNotification},
+    SITE_URL,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Upper bound on queue rows folded into one delivery.
+const BATCH_LIMIT: i32 = 100;
+/// Retry backoff starts at one minute and doubles, capped at six hours.
+const RETRY_BASE_SECS: i64 = 60;
+const RETRY_MAX_SECS: i64 = 6 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Comment,
+    Guestbook,
+}
+
+/// Something the owner should hear about, stored as JSON in the queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationEvent {
+    pub kind: EventKind,
+    pub id: String,
+    pub post_id: Option<String>,
+    pub author: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub held_as_spam: bool,
+}
+
+impl From<&Comment> for NotificationEvent {
+    fn from(comment: &Comment) -> Self {
+        NotificationEvent {
+            kind: EventKind::Comment,
+            id: comment.id.clone(),
+            post_id: Some(comment.post_id.clone()),
+            author: comment.author.clone(),
+            content: comment.content.clone(),
+            created_at: comment.created_at,
+            held_as_spam: comment.is_spam,
+        }
+    }
+}
+
+impl From<&Guestbook> for NotificationEvent {
+    fn from(entry: &Guestbook) -> Self {
+        NotificationEvent {
+            kind: EventKind::Guestbook,
+            id: entry.id.clone(),
+            post_id: None,
+            author: entry.author.clone(),
+            content: entry.content.clone(),
+            created_at: entry.created_at,
+            held_as_spam: entry.is_spam,
+        }
+    }
+}
+
+impl NotificationEvent {
+    pub fn url(&self) -> String {
+        match (&self.kind, &self.post_id) {
+            (EventKind::Comment, Some(post_id)) => format!("{}/post/{}", SITE_URL, post_id),
+            _ => format!("{}/guestbook", SITE_URL),
+        }
+    }
+
+    fn headline(&self) -> String {
+        let what = match self.kind {
+            EventKind::Comment => format!(
+                "New comment on {}",
+                self.post_id.as_deref().unwrap_or("a post")
+            ),
+            EventKind::Guestbook => "New guestbook entry".to_string(),
+        };
+        let held = if self.held_as_spam {
+            " [held as spam]"
+        } else {
+            ""
+        };
+        format!("{} from {}{}", what, self.author, held)
+    }
+}
+
+pub fn digest_subject(events: &[NotificationEvent]) -> String {
+    match events {
+        [single] => single.headline(),
+        _ => format!("{} new comments and guestbook entries", events.len()),
+    }
+}
+
+pub fn digest_text(events: &[NotificationEvent]) -> String {
+    let mut text = String::new();
+    for event in events {
+        text.push_str(&format!(
+            "{}\n{}\n{}\n\n{}\n\n",
+            event.headline(),
+            event.created_at.format("%Y-%m-%d %H:%M UTC"),
+            event.url(),
+            event.content
+        ));
+    }
+    text.push_str(&format!("Moderate: {}/admin\n", SITE_URL));
+    text
+}
+
+/// A delivery channel. One call delivers a whole batch, so a digest is a
+/// single email or webhook request.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Queue key for this channel; must stay stable across restarts.
+    fn channel(&self) -> &'static str;
+    async fn send(&self, events: &[NotificationEvent]) -> anyhow::Result<()>;
+}
+
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl EmailNotifier {
+    pub fn from_config(config: &NotifyConfig) -> anyhow::Result<Self> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("SMTP_HOST is not set"))?;
+        let from = config
+            .email_from
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("NOTIFY_EMAIL_FROM is not set"))?
+            .parse()?;
+        let to = config
+            .email_to
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("NOTIFY_EMAIL_TO is not set"))?
+            .parse()?;
+
+        let mut builder = if config.smtp_starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        }
+        .port(config.smtp_port);
+        if let (Some(user), Some(pass)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(user.clone(), pass.clone()));
+        }
+
+        Ok(EmailNotifier {
+            transport: builder.build(),
+            from,
+            to,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, events: &[NotificationEvent]) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(digest_subject(events))
+            .body(digest_text(events))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    /// Human-readable summary, for chat integrations that display `text`.
+    text: String,
+    events: &'a [NotificationEvent],
+}
+
+impl WebhookNotifier {
+    pub fn new(client: reqwest::Client, url: impl Into<String>) -> Self {
+        WebhookNotifier {
+            client,
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, events: &[NotificationEvent]) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(&WebhookPayload {
+                text: digest_subject(events),
+                events,
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Build the channels that are enabled and fully configured.
+pub fn build_notifiers(config: &NotifyConfig) -> Vec<Arc<dyn Notifier>> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+
+    if config.email_enabled {
+        match EmailNotifier::from_config(config) {
+            Ok(email) => notifiers.push(Arc::new(email)),
+            Err(e) => tracing::warn!("Email notifications disabled: {}", e),
+        }
+    }
+
+    if config.webhook_enabled {
+        match &config.webhook_url {
+            Some(url) => {
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()
+                    .expect("failed to build HTTP client");
+                notifiers.push(Arc::new(WebhookNotifier::new(client, url)));
+            }
+            None => tracing::warn!("Webhook notifications disabled: NOTIFY_WEBHOOK_URL is not set"),
+        }
+    }
+
+    notifiers
+}
+
+pub(crate) fn retry_delay(attempts: u32) -> i64 {
+    let exp = attempts.saturating_sub(1).min(16);
+    (RETRY_BASE_SECS << exp).min(RETRY_MAX_SECS)
+}
+
+/// Drains the notification queue in the background.
+pub struct NotificationWorker {
+    db: Database,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    digest_window_secs: i64,
+    max_attempts: u32,
+    poll_interval: Duration,
+}
+
+impl NotificationWorker {
+    pub fn new(db: Database, notifiers: Vec<Arc<dyn Notifier>>, config: &NotifyConfig) -> Self {
+        NotificationWorker {
+            db,
+            notifiers,
+            digest_window_secs: config.digest_minutes.max(0) * 60,
+            max_attempts: config.max_attempts.max(1),
+            poll_interval: Duration::from_secs(config.poll_secs.max(1)),
+        }
+    }
+
+    pub fn spawn(self) {
+        if self.notifiers.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                self.run_once(Utc::now().timestamp()).await;
+            }
+        });
+    }
+
+    /// Deliver whatever is due on every channel.
+    pub async fn run_once(&self, now: i64) {
+        for notifier in &self.notifiers {
+            let channel = notifier.channel();
+            let due = match self
+                .db
+                .get_due_notifications(channel, now, BATCH_LIMIT)
+                .await
+            {
+                Ok(due) => due,
+                Err(e) => {
+                    tracing::error!("notification queue read failed for {}: {}", channel, e);
+                    continue;
+                }
+            };
+            if due.is_empty() || self.should_wait_for_digest(&due, now) {
+                continue;
+            }
+
+            let ids: Vec<i64> = due.iter().map(|q| q.id).collect();
+            let events: Vec<NotificationEvent> = due.iter().map(|q| q.event.clone()).collect();
+            let result = match notifier.send(&events).await {
+                Ok(()) => {
+                    tracing::info!("Sent {} notification(s) via {}", events.len(), channel);
+                    self.db.complete_notifications(&ids).await
+                }
+                Err(e) => {
+                    let attempts = due.iter().map(|q| q.attempts).max().unwrap_or(0) + 1;
+                    let retry_at =
+                        (attempts < self.max_attempts).then(|| now + retry_delay(attempts));
+                    tracing::warn!(
+                        "Notification delivery via {} failed (attempt {}/{}): {}",
+                        channel,
+                        attempts,
+                        self.max_attempts,
+                        e
+                    );
+                    self.db
+                        .fail_notifications(&ids, retry_at, &e.to_string())
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                tracing::error!("notification queue update failed for {}: {}", channel, e);
+            }
+        }
+    }
+
+    /// Fresh notifications are held until the oldest one has waited a full
+    /// digest window. Retries go out as soon as they are due.
+    fn should_wait_for_digest(&self, due: &[QueuedNotification], now: i64) -> bool {
+        if self.digest_window_secs == 0 || due.iter().any(|q| q.attempts > 0) {
+            return false;
+        }
+        let oldest = due.iter().map(|q| q.created_at).min().unwrap_or(now);
+        now - oldest < self.digest_window_secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::stand_in_server;
+    use crate::spam::SpamVerdict;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn event(author: &str) -> NotificationEvent {
+        NotificationEvent {
+            kind: EventKind::Comment,
+            id: "c1".to_string(),
+            post_id: Some("hello-world".to_string()),
+            author: author.to_string(),
+            content: "Nice post".to_string(),
+            created_at: Utc::now(),
+            held_as_spam: false,
+        }
+    }
+
+    async fn test_db(channels: Vec<&'static str>) -> Database {
+        crate::db::test_db()
+            .await
+            .with_notification_channels(channels)
+    }
+
+    /// Records every batch and fails while `fail` is set.
+    struct RecordingNotifier {
+        fail: Mutex<bool>,
+        sent: Mutex<Vec<Vec<NotificationEvent>>>,
+    }
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        fn channel(&self) -> &'static str {
+            "test"
+        }
+
+        async fn send(&self, events: &[NotificationEvent]) -> anyhow::Result<()> {
+            if *self.fail.lock().unwrap() {
+                anyhow::bail!("unreachable");
+            }
+            self.sent.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_digest_subject() {
+        assert_eq!(
+            digest_subject(&[event("alice")]),
+            "New comment on hello-world from alice"
+        );
+        assert_eq!(
+            digest_subject(&[event("alice"), event("bob")]),
+            "2 new comments and guestbook entries"
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_and_caps() {
+        assert_eq!(retry_delay(1), 60);
+        assert_eq!(retry_delay(2), 120);
+        assert_eq!(retry_delay(30), RETRY_MAX_SECS);
+    }
+
+    #[tokio::test]
+    async fn test_queue_survives_failure_and_retries() {
+        let db = test_db(vec!["test"]).await;
+        db.create_comment("post", "alice", "hi", None, &SpamVerdict::default())
+            .await
+            .unwrap();
+
+        let notifier = Arc::new(RecordingNotifier {
+            fail: Mutex::new(true),
+            sent: Mutex::new(Vec::new()),
+        });
+        let worker =
+            NotificationWorker::new(db.clone(), vec![notifier.clone()], &NotifyConfig::default());
+
+        let now = Utc::now().timestamp();
+        worker.run_once(now).await;
+        let queued = db
+            .get_due_notifications("test", now + 60, 10)
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].attempts, 1);
+        // Not due again until the backoff has elapsed
+        assert!(db
+            .get_due_notifications("test", now, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        *notifier.fail.lock().unwrap() = false;
+        worker.run_once(now + 60).await;
+        assert_eq!(notifier.sent.lock().unwrap().len(), 1);
+        assert!(db
+            .get_due_notifications("test", now + 3600, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_digest_batches_events() {
+        let db = test_db(vec!["test"]).await;
+        for author in ["alice", "bob"] {
+            db.create_guestbook_entry(author, "hello", None, None, &SpamVerdict::default())
+                .await
+                .unwrap();
+        }
+        let notifier = Arc::new(RecordingNotifier {
+            fail: Mutex::new(false),
+            sent: Mutex::new(Vec::new()),
+        });
+        let config = NotifyConfig {
+            digest_minutes: 10,
+            ..NotifyConfig::default()
+        };
+        let worker = NotificationWorker::new(db, vec![notifier.clone()], &config);
+
+        let now = Utc::now().timestamp();
+        worker.run_once(now).await;
+        assert!(notifier.sent.lock().unwrap().is_empty());
+
+        worker.run_once(now + 600).await;
+        let sent = notifier.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].len(), 2);
+    }
+
+    /// Minimal SMTP sink: accepts one message and returns its DATA section.
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    write.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let verb = line.get(..4).unwrap_or("").to_ascii_uppercase();
+            let reply: &[u8] = match verb.as_str() {
+                "EHLO" | "HELO" => b"250 sink\r\n",
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                }
+                _ => b"250 ok\r\n",
+            };
+            write.write_all(reply).await.unwrap();
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_email_notifier_against_local_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let notifier = EmailNotifier::from_config(&NotifyConfig {
+            email_enabled: true,
+            smtp_host: Some("127.0.0.1".to_string()),
+            smtp_port: port,
+            smtp_starttls: false,
+            email_from: Some("blog@example.com".to_string()),
+            email_to: Some("owner@example.com".to_string()),
+            ..NotifyConfig::default()
+        })
+        .unwrap();
+        notifier.send(&[event("alice")]).await.unwrap();
+        drop(notifier);
+
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: New comment on hello-world from alice"));
+        assert!(data.contains("To: owner@example.com"));
+        assert!(data.contains("Nice post"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifier_posts_json() {
+        use axum::{routing::post, Json, Router};
+
+        let received = Arc::new(Mutex::new(None::<serde_json::Value>));
+        let store = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let store = store.clone();
+                async move {
+                    *store.lock().unwrap() = Some(body);
+                }
+            }),
+        );
+        let url = format!("{}/hook", stand_in_server(|_| app).await);
+
+        let notifier = WebhookNotifier::new(reqwest::Client::new(), url);
+        notifier.send(&[event("alice")]).await.unwrap();
+
+        let body = received.lock().unwrap().take().unwrap();
+        assert_eq!(body["text"], "New comment on hello-world from alice");
+        assert_eq!(body["events"][0]["kind"], "comment");
+        assert_eq!(body["events"][0]["author"], "alice");
+    }
+}
@@ -0,0 +1,356 @@
+//! Open Graph preview images for posts without an `og_image`.
+//!
+//! A card is laid out as SVG, rasterized with resvg and cached on disk under
+//! a hash of everything drawn on it, so an edited title or tag list gets a
+//! fresh image while unchanged posts are rendered once.
+
+use crate::{feed::xml_escape, post::Post, SITE_URL};
+use resvg::{tiny_skia, usvg};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+
+/// Fonts compiled into the binary, so cards have text on hosts with no
+/// fonts installed: DejaVu Sans for Latin, Greek and Cyrillic, and
+/// NanumBarunGothic for Hangul. Their licenses sit next to them in
+/// `assets/fonts`. Other scripts fall back to system fonts.
+const BUNDLED_FONTS: [&[u8]; 3] = [
+    include_bytes!("../assets/fonts/DejaVuSans.ttf"),
+    include_bytes!("../assets/fonts/DejaVuSans-Bold.ttf"),
+    include_bytes!("../assets/fonts/NanumBarunGothic.ttf"),
+];
+const SANS_SERIF_FAMILY: &str = "DejaVu Sans";
+const FONT_FAMILY: &str = "'DejaVu Sans', 'NanumBarunGothic', sans-serif";
+
+/// Bump when the layout changes so cached PNGs are regenerated.
+const TEMPLATE_VERSION: u32 = 2;
+
+const TITLE_SIZE: f32 = 64.0;
+const TITLE_MAX_WIDTH: f32 = 1040.0;
+const TITLE_MAX_LINES: usize = 3;
+
+/// What gets drawn on a post's card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OgCard {
+    pub title: String,
+    pub series: Option<String>,
+    pub tags: Vec<String>,
+    pub author: String,
+    pub date: String,
+}
+
+impl OgCard {
+    pub fn from_post(post: &Post) -> Self {
+        OgCard {
+            title: post.metadata.title.clone(),
+            series: post.metadata.series.clone(),
+            tags: post.metadata.tags.clone(),
+            author: post.metadata.author_name().to_string(),
+            date: post.metadata.created_at.format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    /// Cache key covering the layout version and every drawn field.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(TEMPLATE_VERSION.to_le_bytes());
+        for field in [
+            &self.title,
+            self.series.as_deref().unwrap_or(""),
+            &self.author,
+            &self.date,
+        ] {
+            hasher.update(field.as_bytes());
+            hasher.update([0]);
+        }
+        for tag in &self.tags {
+            hasher.update(tag.as_bytes());
+            hasher.update([0]);
+        }
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    pub fn svg(&self) -> String {
+        let mut svg = format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="{font}">
+  <defs>
+    <linearGradient id="bg" x1="0" y1="0" x2="1" y2="1">
+      <stop offset="0" stop-color="#fdf6f3"/>
+      <stop offset="1" stop-color="#f7e1e6"/>
+    </linearGradient>
+  </defs>
+  <rect width="{w}" height="{h}" fill="url(#bg)"/>
+  <rect x="0" y="0" width="16" height="{h}" fill="#d6899b"/>
+"##,
+            w = WIDTH,
+            h = HEIGHT,
+            font = FONT_FAMILY,
+        );
+
+        if let Some(series) = &self.series {
+            svg.push_str(&format!(
+                "  <text x=\"80\" y=\"110\" font-size=\"30\" font-weight=\"600\" fill=\"#d6899b\">{}</text>\n",
+                xml_escape(series)
+            ));
+        }
+
+        let lines = wrap_title(&self.title, TITLE_SIZE, TITLE_MAX_WIDTH, TITLE_MAX_LINES);
+        svg.push_str(&format!(
+            "  <text x=\"80\" y=\"210\" font-size=\"{}\" font-weight=\"700\" fill=\"#3b2f33\">\n",
+            TITLE_SIZE
+        ));
+        for (i, line) in lines.iter().enumerate() {
+            svg.push_str(&format!(
+                "    <tspan x=\"80\" dy=\"{}\">{}</tspan>\n",
+                if i == 0 { 0.0 } else { TITLE_SIZE * 1.25 },
+                xml_escape(line)
+            ));
+        }
+        svg.push_str("  </text>\n");
+
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self
+                .tags
+                .iter()
+                .take(5)
+                .map(|t| format!("#{}", t))
+                .collect();
+            svg.push_str(&format!(
+                "  <text x=\"80\" y=\"480\" font-size=\"28\" fill=\"#8a7479\">{}</text>\n",
+                xml_escape(&tags.join("  "))
+            ));
+        }
+
+        svg.push_str(&format!(
+            "  <text x=\"80\" y=\"560\" font-size=\"28\" fill=\"#3b2f33\">{} · {}</text>\n",
+            xml_escape(&self.author),
+            xml_escape(&self.date)
+        ));
+        svg.push_str(&format!(
+            "  <text x=\"1120\" y=\"560\" font-size=\"32\" font-weight=\"700\" fill=\"#d6899b\" text-anchor=\"end\">{}</text>\n",
+            xml_escape(SITE_URL.trim_start_matches("https://"))
+        ));
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Rough advance width: full-width for CJK and other wide characters,
+/// a bit over half an em for Latin text.
+fn char_width(c: char, size: f32) -> f32 {
+    let wide = matches!(c as u32,
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6);
+    if wide {
+        size
+    } else if c == ' ' {
+        size * 0.3
+    } else {
+        size * 0.56
+    }
+}
+
+/// Break a title into lines no wider than `max_width`, preferring spaces
+/// and falling back to any character (CJK titles often have no spaces).
+/// The last line is ellipsized if the title does not fit.
+fn wrap_title(title: &str, size: f32, max_width: f32, max_lines: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut width = 0.0;
+
+    for word in title.split_inclusive(' ') {
+        let word_width: f32 = word.chars().map(|c| char_width(c, size)).sum();
+        if width + word_width > max_width && !line.is_empty() {
+            lines.push(std::mem::take(&mut line).trim_end().to_string());
+            width = 0.0;
+        }
+        for c in word.chars() {
+            let w = char_width(c, size);
+            if width + w > max_width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line).trim_end().to_string());
+                width = 0.0;
+            }
+            line.push(c);
+            width += w;
+        }
+    }
+    if !line.trim().is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = &mut lines[max_lines - 1];
+        let ellipsis = char_width('…', size);
+        while !last.is_empty()
+            && last.chars().map(|c| char_width(c, size)).sum::<f32>() + ellipsis > max_width
+        {
+            last.pop();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+/// Renders cards to PNG and keeps the results in `cache_dir`.
+pub struct OgRenderer {
+    cache_dir: PathBuf,
+    fontdb: OnceLock<Arc<usvg::fontdb::Database>>,
+}
+
+impl OgRenderer {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        OgRenderer {
+            cache_dir: cache_dir.into(),
+            fontdb: OnceLock::new(),
+        }
+    }
+
+    /// Bundled fonts plus whatever the system has, loaded on first use.
+    fn fontdb(&self) -> Arc<usvg::fontdb::Database> {
+        self.fontdb
+            .get_or_init(|| {
+                let mut db = usvg::fontdb::Database::new();
+                for font in BUNDLED_FONTS {
+                    db.load_font_data(font.to_vec());
+                }
+                db.load_system_fonts();
+                // `sans-serif` defaults to Arial, which is rarely installed on
+                // servers; point it at a bundled font.
+                db.set_sans_serif_family(SANS_SERIF_FAMILY);
+                Arc::new(db)
+            })
+            .clone()
+    }
+
+    fn render(&self, svg: &str) -> anyhow::Result<tiny_skia::Pixmap> {
+        let options = usvg::Options {
+            fontdb: self.fontdb(),
+            ..usvg::Options::default()
+        };
+        let tree = usvg::Tree::from_str(svg, &options)?;
+        let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT)
+            .ok_or_else(|| anyhow::anyhow!("invalid image size"))?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        Ok(pixmap)
+    }
+
+    pub fn render_png(&self, svg: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(self.render(svg)?.encode_png()?)
+    }
+
+    /// The card's PNG, from the disk cache or freshly rendered.
+    pub async fn png(self: &Arc<Self>, card: &OgCard) -> anyhow::Result<Vec<u8>> {
+        let path = self.cache_dir.join(format!("{}.png", card.hash()));
+        if let Ok(png) = tokio::fs::read(&path).await {
+            return Ok(png);
+        }
+
+        let renderer = Arc::clone(self);
+        let svg = card.svg();
+        let png = tokio::task::spawn_blocking(move || renderer.render_png(&svg)).await??;
+
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        // Write then rename so a concurrent request never reads a partial file.
+        let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&tmp, &png).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(png)
+    }
+}
+
+/// Public URL of a post's generated card.
+pub fn image_url(slug: &str) -> String {
+    format!("{}/og/{}.png", SITE_URL, slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::sample_post;
+
+    #[test]
+    fn test_wrap_title() {
+        assert_eq!(
+            wrap_title("Short title", 64.0, 1040.0, 3),
+            vec!["Short title"]
+        );
+
+        let korean = "양자역학 입문기: 개발자의 시선으로 풀어보는 선형대수와 힐베르트 공간 그리고 그 너머의 이야기";
+        let lines = wrap_title(korean, 64.0, 1040.0, 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].ends_with('…'));
+        for line in &lines {
+            assert!(line.chars().map(|c| char_width(c, 64.0)).sum::<f32>() <= 1040.0);
+        }
+    }
+
+    #[test]
+    fn test_card_hash_tracks_content() {
+        let post = sample_post("hello");
+        let card = OgCard::from_post(&post);
+        assert_eq!(card.hash(), OgCard::from_post(&post).hash());
+
+        let mut retitled = card.clone();
+        retitled.title.push('!');
+        assert_ne!(card.hash(), retitled.hash());
+
+        let mut retagged = card.clone();
+        retagged.tags.push("web".to_string());
+        assert_ne!(card.hash(), retagged.hash());
+    }
+
+    /// Pixels of the title area that differ between two renders.
+    fn title_pixels(renderer: &OgRenderer, a: &OgCard, b: &OgCard) -> usize {
+        let (a, b) = (
+            renderer.render(&a.svg()).unwrap(),
+            renderer.render(&b.svg()).unwrap(),
+        );
+        let mut differing = 0;
+        for y in 130..420 {
+            for x in 0..WIDTH {
+                if a.pixel(x, y) != b.pixel(x, y) {
+                    differing += 1;
+                }
+            }
+        }
+        differing
+    }
+
+    #[test]
+    fn test_titles_render_glyphs() {
+        let renderer = OgRenderer::new(std::env::temp_dir());
+        let mut blank = OgCard::from_post(&sample_post("hello"));
+        blank.title = String::new();
+
+        for title in ["Hello, world", "양자역학 입문기"] {
+            let card = OgCard {
+                title: title.to_string(),
+                ..blank.clone()
+            };
+            let drawn = title_pixels(&renderer, &blank, &card);
+            assert!(drawn > 2000, "{:?} drew only {} pixels", title, drawn);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renders_and_caches_png() {
+        let dir = std::env::temp_dir().join(format!("blog-og-{}", uuid::Uuid::new_v4()));
+        let renderer = Arc::new(OgRenderer::new(&dir));
+        let mut post = sample_post("hello");
+        post.metadata.title = "Fish & <Chips> 양자역학".to_string();
+        let card = OgCard::from_post(&post);
+
+        let png = renderer.png(&card).await.unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let cached = dir.join(format!("{}.png", card.hash()));
+        assert_eq!(std::fs::read(&cached).unwrap(), png);
+
+        // A cached file is served as-is.
+        std::fs::write(&cached, b"cached").unwrap();
+        assert_eq!(renderer.png(&card).await.unwrap(), b"cached");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,10 +3,11 @@ mod de;
 use crate::i18n::Lang;
 use crate::AppState;
 use anyhow::{Context, Result};
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Utc};
 use gray_matter::{engine::YAML, Matter};
 use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use slug::slugify;
 use std::{
     cmp::Ordering,
@@ -23,12 +24,14 @@ pub struct Post {
     pub slug: String,
     pub toc: Vec<TocEntry>,
     pub reading_time_min: u32,
+    /// Whitespace-separated words in the body, code blocks included.
+    pub word_count: u32,
     pub lang: Lang,
     pub translation_key: String,
     #[serde(skip)]
     pub view_count: u32,
     #[serde(skip)]
-    pub like_count: u32,
+    pub reaction_count: u32,
 }
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -127,6 +130,17 @@ pub struct PostMetadata {
     pub slug: Option<String>,
 }
 
+/// An author without a trailing `<email>`, as in `Han Damin <me@example.com>`.
+pub fn author_name(author: &str) -> &str {
+    author.split('<').next().unwrap_or_default().trim()
+}
+
+impl PostMetadata {
+    pub fn author_name(&self) -> &str {
+        author_name(&self.author)
+    }
+}
+
 impl Ord for Post {
     fn cmp(&self, other: &Self) -> Ordering {
         other.metadata.created_at.cmp(&self.metadata.created_at)
@@ -203,7 +217,7 @@ pub fn dedup_refs_by_translation<'a>(
 /// get recent posts with language fallback
 pub fn get_recent_posts(posts: &[Post], lang: Lang) -> Vec<Post> {
     let mut deduped = dedup_refs_by_translation(posts.iter(), lang);
-    deduped.sort_by(|a, b| b.metadata.created_at.cmp(&a.metadata.created_at));
+    deduped.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
     deduped.into_iter().take(5).cloned().collect()
 }
 
@@ -225,13 +239,47 @@ pub fn get_posts_by_category(
         lang,
     );
     if sort_asc {
-        deduped.sort_by(|a, b| a.metadata.created_at.cmp(&b.metadata.created_at));
+        deduped.sort_by_key(|p| p.metadata.created_at);
     } else {
-        deduped.sort_by(|a, b| b.metadata.created_at.cmp(&a.metadata.created_at));
+        deduped.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
     }
     deduped.into_iter().cloned().collect()
 }
 
+/// The posts of one successful content load, as reported by readiness checks.
+#[derive(Clone, Debug)]
+pub struct ContentSnapshot {
+    /// Hash of every post's slug, language, update time and content, so two
+    /// instances serving the same content report the same version.
+    pub version: String,
+    pub posts: usize,
+    pub loaded_at: DateTime<Utc>,
+}
+
+impl ContentSnapshot {
+    pub fn new(posts: &[Post], loaded_at: DateTime<Utc>) -> Self {
+        let mut keyed: Vec<&Post> = posts.iter().collect();
+        keyed.sort_by(|a, b| (&a.slug, a.lang.as_str()).cmp(&(&b.slug, b.lang.as_str())));
+        let mut hasher = Sha256::new();
+        for post in keyed {
+            for part in [
+                post.slug.as_str(),
+                post.lang.as_str(),
+                &post.metadata.updated_at.to_rfc3339(),
+                &post.content,
+            ] {
+                hasher.update(part.as_bytes());
+                hasher.update([0]);
+            }
+        }
+        ContentSnapshot {
+            version: hex::encode(hasher.finalize())[..12].to_string(),
+            posts: posts.len(),
+            loaded_at,
+        }
+    }
+}
+
 // load posts from mdx files
 pub async fn load_posts(state: AppState) -> Result<()> {
     let matter = Matter::<YAML>::new();
@@ -311,9 +359,9 @@ pub fn get_series(posts: &[Post], _lang: Lang, sort_asc: bool) -> Vec<Series> {
         .collect();
 
     if sort_asc {
-        series.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        series.sort_by_key(|s| s.updated_at);
     } else {
-        series.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        series.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
     }
     series
 }
@@ -336,9 +384,9 @@ pub fn get_posts_by_series(
         lang,
     );
     if sort_asc {
-        deduped.sort_by(|a, b| a.metadata.created_at.cmp(&b.metadata.created_at));
+        deduped.sort_by_key(|p| p.metadata.created_at);
     } else {
-        deduped.sort_by(|a, b| b.metadata.created_at.cmp(&a.metadata.created_at));
+        deduped.sort_by_key(|p| std::cmp::Reverse(p.metadata.created_at));
     }
     deduped.into_iter().cloned().collect()
 }
@@ -775,19 +823,76 @@ async fn process_mdx_file(
         slug,
         toc,
         reading_time_min,
+        word_count: word_count as u32,
         lang,
         translation_key,
         view_count: 0,
-        like_count: 0,
+        reaction_count: 0,
     };
 
     Ok(post)
 }
 
+/// A minimal English blog post for tests in other modules.
+#[cfg(test)]
+pub(crate) fn sample_post(slug: &str) -> Post {
+    let date = DateTime::parse_from_rfc3339("2024-01-01T00:00:00+09:00").unwrap();
+    Post {
+        post_type: PostType::Blog,
+        metadata: PostMetadata {
+            title: format!("Title {}", slug),
+            description: "desc".to_string(),
+            author: "miniex".to_string(),
+            tags: vec!["rust".to_string()],
+            created_at: date,
+            updated_at: date,
+            series: None,
+            series_order: None,
+            series_description: None,
+            series_status: None,
+            prev_post: None,
+            next_post: None,
+            og_image: None,
+            lang: None,
+            slug: None,
+        },
+        content: "<p>body</p>".to_string(),
+        slug: slug.to_string(),
+        toc: vec![],
+        reading_time_min: 1,
+        word_count: 1,
+        lang: Lang::En,
+        translation_key: slug.to_string(),
+        view_count: 0,
+        reaction_count: 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_content_snapshot_version() {
+        let now = Utc::now();
+        let a = sample_post("a");
+        let b = sample_post("b");
+        let snapshot = ContentSnapshot::new(&[a.clone(), b.clone()], now);
+        assert_eq!(snapshot.posts, 2);
+        assert_eq!(snapshot.version.len(), 12);
+        // Independent of load order, but not of content
+        assert_eq!(
+            ContentSnapshot::new(&[b.clone(), a.clone()], now).version,
+            snapshot.version
+        );
+        let mut edited = b;
+        edited.content = "<p>edited</p>".to_string();
+        assert_ne!(
+            ContentSnapshot::new(&[a, edited], now).version,
+            snapshot.version
+        );
+    }
+
     #[test]
     fn test_parse_file_lang_ko() {
         let (base, lang) = parse_file_lang("my-post.ko");
@@ -840,10 +945,11 @@ mod tests {
             slug: slug.to_string(),
             toc: vec![],
             reading_time_min: 1,
+            word_count: 0,
             lang,
             translation_key: translation_key.to_string(),
             view_count: 0,
-            like_count: 0,
+            reaction_count: 0,
         }
     }
 
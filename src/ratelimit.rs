@@ -0,0 +1,313 @@
+//! Client addresses, the block list and per-route rate limits.
+//!
+//! Behind a reverse proxy every request arrives from the proxy, so the real
+//! client is taken from `X-Forwarded-For`, but only when the peer is listed
+//! in `TRUSTED_PROXIES`. The header is read right to left and the first
+//! address that is not a trusted proxy wins; anything further left was
+//! written by the client and could be forged. Forwarded requests with no
+//! trusted proxies configured are logged once, as every client would then
+//! share the proxy's address.
+
+use crate::{metrics::Metrics, SharedState};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ipnet::{IpNet, Ipv6Net};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tower_governor::{key_extractor::KeyExtractor, GovernorError};
+
+/// The address a request is attributed to, after proxy headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// How often one client may hit a group of routes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitPolicy {
+    /// Reported in 429 responses and logs.
+    pub name: &'static str,
+    /// One request is allowed back after every period.
+    pub period: Duration,
+    /// Requests allowed in a row before the period applies.
+    pub burst: u32,
+}
+
+impl RateLimitPolicy {
+    pub fn new(name: &'static str, period: Duration, burst: u32) -> Self {
+        RateLimitPolicy {
+            name,
+            period,
+            burst,
+        }
+    }
+}
+
+/// Parse `<interval>,<burst>`, e.g. `10s,3` or `500ms,10`. Intervals take
+/// an `ms`, `s`, `m` or `h` suffix; both parts must be non-zero.
+pub fn parse_policy(name: &'static str, raw: &str) -> Option<RateLimitPolicy> {
+    let (interval, burst) = raw.split_once(',')?;
+    let period = parse_interval(interval.trim())?;
+    let burst: u32 = burst.trim().parse().ok()?;
+    (!period.is_zero() && burst > 0).then(|| RateLimitPolicy::new(name, period, burst))
+}
+
+fn parse_interval(raw: &str) -> Option<Duration> {
+    let split = raw.find(|c: char| !c.is_ascii_digit())?;
+    let value: u64 = raw[..split].parse().ok()?;
+    match &raw[split..] {
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        "m" => Some(Duration::from_secs(value.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(value.checked_mul(3600)?)),
+        _ => None,
+    }
+}
+
+/// Parse comma-separated addresses and CIDR ranges. A bare address is a
+/// single-host range. Returns the ranges and the entries that did not parse.
+pub fn parse_networks(raw: &str) -> (Vec<IpNet>, Vec<String>) {
+    let mut nets = Vec::new();
+    let mut invalid = Vec::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.parse::<IpNet>() {
+            Ok(net) => nets.push(net),
+            Err(_) => match entry.parse::<IpAddr>() {
+                Ok(ip) => nets.push(IpNet::from(ip)),
+                Err(_) => invalid.push(entry.to_string()),
+            },
+        }
+    }
+    (nets, invalid)
+}
+
+fn contains(nets: &[IpNet], ip: IpAddr) -> bool {
+    nets.iter().any(|net| net.contains(&ip))
+}
+
+/// The client behind `peer`, following `X-Forwarded-For` through trusted
+/// proxies only.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+
+    let mut client = peer;
+    for entry in forwarded.iter().rev() {
+        if !contains(trusted, client) {
+            break;
+        }
+        match entry.parse::<IpAddr>() {
+            Ok(ip) => client = ip,
+            // A garbled hop ends the chain at the last proxy we trust
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+fn warn_untrusted_forwarding(peer: IpAddr) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        tracing::warn!(
+            "Request from {} carries X-Forwarded-For but TRUSTED_PROXIES is empty; \
+             all clients behind the proxy share its address",
+            peer
+        );
+    }
+}
+
+/// Resolve [`ClientIp`] for the rest of the stack and turn away blocked
+/// clients. Requests without connection info (tests) count as `0.0.0.0`.
+pub async fn resolve_client_ip(
+    State(state): State<SharedState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let limits = &state.config.rate_limit;
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    if limits.trusted_proxies.is_empty() && req.headers().contains_key("x-forwarded-for") {
+        warn_untrusted_forwarding(peer);
+    }
+    let ip = client_ip(peer, req.headers(), &limits.trusted_proxies);
+
+    if contains(&limits.blocked, ip) {
+        tracing::info!("Refused request from blocked address {}", ip);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "forbidden" })),
+        )
+            .into_response();
+    }
+
+    req.extensions_mut().insert(ClientIp(ip));
+    next.run(req).await
+}
+
+/// Keys rate limits by [`ClientIp`]. IPv6 clients are grouped by /64, since
+/// a single host usually controls the whole prefix; see [`client_key`].
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIpKeyExtractor;
+
+impl KeyExtractor for ClientIpKeyExtractor {
+    type Key = IpAddr;
+
+    fn extract<T>(&self, req: &axum::http::Request<T>) -> Result<Self::Key, GovernorError> {
+        let ClientIp(ip) = req
+            .extensions()
+            .get::<ClientIp>()
+            .copied()
+            .ok_or(GovernorError::UnableToExtractKey)?;
+        Ok(client_key(ip))
+    }
+}
+
+/// The address limits and lockouts count against: IPv4 addresses as they
+/// are, IPv6 addresses by their /64 prefix.
+pub fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => Ipv6Net::new(v6, 64)
+            .map(|net| IpAddr::V6(net.network()))
+            .unwrap_or(ip),
+    }
+}
+
+/// Error handler for a policy's limiter: a JSON body and `Retry-After`.
+/// Rejections are counted in `metrics`.
+pub fn too_many_requests(
+    policy: &'static str,
+    metrics: Arc<Metrics>,
+) -> impl Fn(GovernorError) -> Response<Body> + Send + Sync + 'static {
+    move |error| match error {
+        GovernorError::TooManyRequests { wait_time, .. } => {
+            metrics.record_rate_limited(policy);
+            let retry_after = wait_time.max(1);
+            tracing::debug!("Rate limited by {} policy for {}s", policy, retry_after);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, HeaderValue::from(retry_after))],
+                Json(serde_json::json!({
+                    "error": "rate_limited",
+                    "policy": policy,
+                    "retry_after": retry_after,
+                })),
+            )
+                .into_response()
+        }
+        GovernorError::UnableToExtractKey => {
+            tracing::error!("Rate limiter ran without a resolved client address");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        GovernorError::Other { code, .. } => code.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = parse_policy("submit", " 10s , 3 ").unwrap();
+        assert_eq!(policy.period, Duration::from_secs(10));
+        assert_eq!(policy.burst, 3);
+        assert_eq!(
+            parse_policy("write", "500ms,10").unwrap().period,
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            parse_policy("write", "2m,1").unwrap().period,
+            Duration::from_secs(120)
+        );
+
+        for bad in ["10s", "10,3", "0s,3", "10s,0", "s,3", "10x,3", "-1s,3"] {
+            assert!(parse_policy("write", bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_networks() {
+        let (nets, invalid) = parse_networks("10.0.0.0/8, 203.0.113.7,,::1, nope");
+        assert_eq!(nets.len(), 3);
+        assert_eq!(invalid, vec!["nope".to_string()]);
+        assert!(contains(&nets, "10.1.2.3".parse().unwrap()));
+        assert!(contains(&nets, "203.0.113.7".parse().unwrap()));
+        assert!(!contains(&nets, "203.0.113.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_client_ip_follows_trusted_proxies_only() {
+        let (trusted, _) = parse_networks("10.0.0.0/8");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.1.1.1, 198.51.100.4, 10.0.0.2"),
+        );
+
+        // Untrusted peers cannot speak for anyone else
+        let peer: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(client_ip(peer, &headers, &trusted), peer);
+
+        // The forged leftmost entry is never reached
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        assert_eq!(
+            client_ip(proxy, &headers, &trusted),
+            "198.51.100.4".parse::<IpAddr>().unwrap()
+        );
+
+        // No header, or a garbled one, leaves the proxy itself
+        assert_eq!(client_ip(proxy, &HeaderMap::new(), &trusted), proxy);
+        headers.insert("x-forwarded-for", HeaderValue::from_static("unknown"));
+        assert_eq!(client_ip(proxy, &headers, &trusted), proxy);
+    }
+
+    #[test]
+    fn test_ipv6_clients_share_a_prefix_key() {
+        let key = |ip: &str| {
+            let mut req = axum::http::Request::new(());
+            req.extensions_mut().insert(ClientIp(ip.parse().unwrap()));
+            ClientIpKeyExtractor.extract(&req).unwrap()
+        };
+        assert_eq!(key("2001:db8::1"), key("2001:db8::ffff:2"));
+        assert_ne!(key("2001:db8::1"), key("2001:db8:0:1::1"));
+        assert_eq!(key("192.0.2.1"), "192.0.2.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_too_many_requests_response() {
+        let handler = too_many_requests("submit", Arc::new(Metrics::new()));
+        let response = handler(GovernorError::TooManyRequests {
+            wait_time: 0,
+            headers: None,
+        });
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "rate_limited");
+        assert_eq!(json["policy"], "submit");
+        assert_eq!(json["retry_after"], 1);
+    }
+}
@@ -0,0 +1,159 @@
+//! Emoji reactions on posts.
+//!
+//! The site owner picks the reaction kinds with `REACTIONS`, a comma-separated
+//! list of `name:emoji` pairs. Each visitor can toggle every kind once per
+//! post. Likes from before reactions existed were migrated into
+//! [`LEGACY_LIKE_KIND`].
+
+use std::collections::HashMap;
+
+/// Kind that pre-reaction likes were migrated into.
+pub const LEGACY_LIKE_KIND: &str = "heart";
+
+pub const DEFAULT_REACTIONS: &str = "heart:❤️,thumbsup:👍,tada:🎉,thinking:🤔";
+
+/// One configured reaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionKind {
+    /// Stable identifier used in URLs and the database.
+    pub name: String,
+    pub emoji: String,
+}
+
+/// A reaction button on a post page.
+pub struct ReactionView {
+    pub name: String,
+    pub emoji: String,
+    pub count: u32,
+    /// Whether the current visitor has this reaction on the post.
+    pub reacted: bool,
+}
+
+/// Parse `name:emoji` pairs. Names are lowercase ASCII letters, digits, `-`
+/// and `_`; malformed or repeated entries are skipped.
+pub fn parse_kinds(raw: &str) -> Vec<ReactionKind> {
+    let mut kinds: Vec<ReactionKind> = Vec::new();
+    for entry in raw.split(',') {
+        let Some((name, emoji)) = entry.split_once(':') else {
+            continue;
+        };
+        let (name, emoji) = (name.trim().to_ascii_lowercase(), emoji.trim());
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid || emoji.is_empty() || kinds.iter().any(|k| k.name == name) {
+            continue;
+        }
+        kinds.push(ReactionKind {
+            name,
+            emoji: emoji.to_string(),
+        });
+    }
+    kinds
+}
+
+/// Sum of the configured kinds' counts; reactions of kinds that are no
+/// longer configured are left out.
+pub fn total(kinds: &[ReactionKind], counts: Option<&HashMap<String, u32>>) -> u32 {
+    let Some(counts) = counts else {
+        return 0;
+    };
+    kinds.iter().filter_map(|k| counts.get(&k.name)).sum()
+}
+
+/// Buttons for every configured kind, in configured order.
+pub fn views(
+    kinds: &[ReactionKind],
+    counts: Option<&HashMap<String, u32>>,
+    reacted: &[String],
+) -> Vec<ReactionView> {
+    kinds
+        .iter()
+        .map(|k| ReactionView {
+            name: k.name.clone(),
+            emoji: k.emoji.clone(),
+            count: counts.and_then(|c| c.get(&k.name)).copied().unwrap_or(0),
+            reacted: reacted.contains(&k.name),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kinds() {
+        let kinds = parse_kinds(DEFAULT_REACTIONS);
+        assert_eq!(kinds.len(), 4);
+        assert_eq!(kinds[0].name, LEGACY_LIKE_KIND);
+        assert_eq!(kinds[1].emoji, "👍");
+
+        let kinds = parse_kinds(" Fire : 🔥 ,fire:🔥,bad name:x,empty:, nocolon ,rocket:🚀");
+        let names: Vec<&str> = kinds.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, vec!["fire", "rocket"]);
+    }
+
+    #[test]
+    fn test_total_ignores_unconfigured_kinds() {
+        let kinds = parse_kinds("heart:❤️,tada:🎉");
+        let counts = HashMap::from([
+            ("heart".to_string(), 3),
+            ("tada".to_string(), 1),
+            ("removed".to_string(), 5),
+        ]);
+        assert_eq!(total(&kinds, Some(&counts)), 4);
+        assert_eq!(total(&kinds, None), 0);
+
+        let views = views(&kinds, Some(&counts), &["tada".to_string()]);
+        assert_eq!(views[0].count, 3);
+        assert!(!views[0].reacted);
+        assert!(views[1].reacted);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_likes_migrate_and_toggle() {
+        let url = crate::db::test_db_url();
+
+        let legacy = sqlx::SqlitePool::connect(&url).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE post_likes (post_slug TEXT NOT NULL, client_id TEXT NOT NULL,
+             created_at TEXT NOT NULL, PRIMARY KEY (post_slug, client_id))",
+        )
+        .execute(&legacy)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO post_likes VALUES ('a', 'v1', '2024-01-01T00:00:00Z')")
+            .execute(&legacy)
+            .await
+            .unwrap();
+
+        // `legacy` keeps the in-memory database alive until the migration runs
+        let db = crate::db::Database::new(&url).await.unwrap();
+        legacy.close().await;
+        let slugs = vec!["a".to_string()];
+        let counts = db.get_reaction_counts(&slugs).await.unwrap();
+        assert_eq!(counts["a"][LEGACY_LIKE_KIND], 1);
+
+        assert_eq!(
+            db.toggle_reaction("a", "tada", "v1").await.unwrap(),
+            (true, 1)
+        );
+        assert_eq!(
+            db.toggle_reaction("a", "tada", "v2").await.unwrap(),
+            (true, 2)
+        );
+        assert_eq!(
+            db.toggle_reaction("a", "tada", "v1").await.unwrap(),
+            (false, 1)
+        );
+
+        let reacted = db.get_visitor_reactions("a", "v1").await.unwrap();
+        assert_eq!(reacted, vec![LEGACY_LIKE_KIND.to_string()]);
+
+        let kinds = parse_kinds(DEFAULT_REACTIONS);
+        let counts = db.get_reaction_counts(&slugs).await.unwrap();
+        assert_eq!(total(&kinds, counts.get("a")), 2);
+    }
+}
@@ -1,22 +1,29 @@
 use crate::{
-    handlers::{api, feed, pages},
-    SharedState,
+    activitypub, assets, auth, csp,
+    handlers::{self, admin, api, feed, og, pages},
+    metrics,
+    ratelimit::{self, ClientIpKeyExtractor, RateLimitPolicy},
+    visitor, webmention, SharedState,
 };
 use axum::{
-    extract::Request,
+    extract::{DefaultBodyLimit, Request},
     http::header,
     middleware::Next,
     response::{IntoResponse, Redirect},
     routing::{get, post},
     Router,
 };
+use std::sync::Arc;
 use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
 use tower_http::{
     compression::CompressionLayer,
     services::{ServeDir, ServeFile},
-    set_header::{SetResponseHeader, SetResponseHeaderLayer},
+    set_header::SetResponseHeaderLayer,
 };
 
+/// Violation reports are small; anything bigger is not one.
+const CSP_REPORT_MAX_BYTES: usize = 16 * 1024;
+
 pub fn create_router(state: SharedState) -> Router {
     let resume_route = if cfg!(debug_assertions) {
         "/resume/ytm".to_string()
@@ -26,22 +33,55 @@ pub fn create_router(state: SharedState) -> Router {
             .unwrap_or_else(|_| "/resume/ytm".to_string())
     };
 
-    // Static assets with long-lived cache headers
-    let assets_service = SetResponseHeader::overriding(
-        ServeDir::new("assets"),
-        header::CACHE_CONTROL,
-        header::HeaderValue::from_static("public, max-age=31536000, immutable"),
-    );
+    // Served here unless it has its own address, see `main`
+    let metrics_routes = if state.config.metrics.enabled && state.config.metrics.addr.is_none() {
+        Router::new().route("/metrics", get(metrics::serve_metrics))
+    } else {
+        Router::new()
+    };
 
-    // Rate limiting for write API routes
-    let governor_conf = GovernorConfigBuilder::default()
-        .per_second(2)
-        .burst_size(5)
-        .finish()
-        .unwrap();
+    // Static assets; content-hashed URLs are cached for a year
+    let assets_routes = Router::new()
+        .nest_service("/assets", ServeDir::new(assets::ASSETS_DIR))
+        .layer(axum::middleware::from_fn(assets::serve_hashed));
+
+    // Each group of write routes gets its own limiter, keyed by client address
+    let limit = |policy: &RateLimitPolicy| GovernorLayer {
+        config: Arc::new(
+            GovernorConfigBuilder::default()
+                .period(policy.period)
+                .burst_size(policy.burst)
+                .key_extractor(ClientIpKeyExtractor)
+                .error_handler(ratelimit::too_many_requests(
+                    policy.name,
+                    state.metrics.clone(),
+                ))
+                .finish()
+                .expect("rate limit policies have a non-zero period and burst"),
+        ),
+    };
+    let limits = &state.config.rate_limit;
 
     let api_write_routes = Router::new()
+        .route(
+            "/api/post/:slug/reactions/:kind",
+            post(api::toggle_reaction),
+        )
+        .route("/api/visit", post(api::record_visit))
+        .route(webmention::ENDPOINT_PATH, post(api::receive_webmention))
+        .route(
+            csp::REPORT_PATH,
+            post(api::receive_csp_report).layer(DefaultBodyLimit::max(CSP_REPORT_MAX_BYTES)),
+        )
+        .layer(limit(&limits.write));
+
+    let api_submit_routes = Router::new()
         .route("/api/comments", post(api::create_comment))
+        .route("/api/guestbook", post(api::create_guestbook_entry))
+        .layer(limit(&limits.submit));
+
+    // Everything that checks a password, so guesses are slow
+    let api_password_routes = Router::new()
         .route(
             "/api/comments/edit/:comment_id",
             axum::routing::put(api::edit_comment),
@@ -50,7 +90,6 @@ pub fn create_router(state: SharedState) -> Router {
             "/api/comments/delete/:comment_id",
             axum::routing::delete(api::delete_comment),
         )
-        .route("/api/guestbook", post(api::create_guestbook_entry))
         .route(
             "/api/guestbook/edit/:entry_id",
             axum::routing::put(api::edit_guestbook_entry),
@@ -59,11 +98,94 @@ pub fn create_router(state: SharedState) -> Router {
             "/api/guestbook/delete/:entry_id",
             axum::routing::delete(api::delete_guestbook_entry),
         )
-        .route("/api/post/:slug/like", post(api::toggle_like))
-        .route("/api/visit", post(api::record_visit))
-        .layer(GovernorLayer {
-            config: governor_conf.into(),
-        });
+        .route("/admin/login", post(admin::handle_login))
+        .layer(limit(&limits.password));
+
+    // Owner-only pages; every route here requires a live admin session
+    let admin_routes = Router::new()
+        .route("/admin", get(admin::handle_dashboard))
+        .route("/admin/analytics", get(admin::handle_analytics))
+        .route("/admin/api/views", get(admin::handle_views_api))
+        .route("/admin/api/views/top", get(admin::handle_top_posts_api))
+        .route("/admin/comments", get(admin::handle_comments))
+        .route(
+            "/admin/comments/:comment_id/delete",
+            post(admin::handle_delete_comment),
+        )
+        .route(
+            "/admin/comments/:comment_id/spam",
+            post(admin::handle_comment_spam),
+        )
+        .route(
+            "/admin/comments/:comment_id/ham",
+            post(admin::handle_comment_ham),
+        )
+        .route("/admin/guestbook", get(admin::handle_guestbook))
+        .route(
+            "/admin/guestbook/:entry_id/delete",
+            post(admin::handle_delete_guestbook_entry),
+        )
+        .route(
+            "/admin/guestbook/:entry_id/spam",
+            post(admin::handle_guestbook_spam),
+        )
+        .route(
+            "/admin/guestbook/:entry_id/ham",
+            post(admin::handle_guestbook_ham),
+        )
+        .route(
+            "/admin/guestbook/:entry_id/pin",
+            post(admin::handle_guestbook_pin),
+        )
+        .route(
+            "/admin/guestbook/:entry_id/unpin",
+            post(admin::handle_guestbook_unpin),
+        )
+        .route(
+            "/admin/guestbook/:entry_id/reply",
+            post(admin::handle_guestbook_reply),
+        )
+        .route(
+            "/admin/reactions/:slug/clear",
+            post(admin::handle_clear_reactions),
+        )
+        .route("/admin/logout", post(admin::handle_logout))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin,
+        ));
+
+    // Federation endpoints; remote servers deliver from few addresses, so
+    // the inbox has its own limiter with a generous burst
+    let activitypub_routes = if state.federation.is_some() {
+        Router::new()
+            .route(
+                "/.well-known/webfinger",
+                get(handlers::activitypub::handle_webfinger),
+            )
+            .route(
+                activitypub::ACTOR_PATH,
+                get(handlers::activitypub::handle_actor),
+            )
+            .route(
+                activitypub::OUTBOX_PATH,
+                get(handlers::activitypub::handle_outbox),
+            )
+            .route(
+                activitypub::FOLLOWERS_PATH,
+                get(handlers::activitypub::handle_followers),
+            )
+            .route(
+                activitypub::INBOX_PATH,
+                post(handlers::activitypub::handle_inbox).layer(limit(&limits.inbox)),
+            )
+            .route(
+                &format!("{}/:slug", activitypub::OBJECT_PATH),
+                get(handlers::activitypub::handle_article),
+            )
+    } else {
+        Router::new()
+    };
 
     Router::new()
         .route("/", get(pages::handle_index))
@@ -76,18 +198,45 @@ pub fn create_router(state: SharedState) -> Router {
         .route(&resume_route, get(pages::handle_resume))
         .route("/guestbook", get(pages::handle_guestbook))
         .route("/feed.xml", get(feed::handle_feed))
+        .route("/rss.xml", get(feed::handle_rss))
+        .route("/feed.json", get(feed::handle_json_feed))
+        .route("/blog/feed.xml", get(feed::handle_blog_feed))
+        .route("/review/feed.xml", get(feed::handle_review_feed))
+        .route("/diary/feed.xml", get(feed::handle_diary_feed))
+        .route("/tags/:tag/feed.xml", get(feed::handle_tag_feed))
+        .route("/series/:name/feed.xml", get(feed::handle_series_feed))
         .route("/sitemap.xml", get(feed::handle_sitemap))
+        .route("/sitemaps/:file", get(feed::handle_sitemap_section))
+        .route("/og/:file", get(og::handle_og_image))
         .route("/health", get(api::health_check))
+        .route("/health/live", get(api::health_check))
+        .route("/health/ready", get(api::readiness_check))
         .route("/api/search", get(api::handle_search))
         .route("/api/set-lang", get(api::handle_set_lang))
         .route("/api/comments/:post_id", get(api::get_comments))
         .route("/api/guestbook", get(api::get_guestbook_entries))
         .route("/api/visitor-stats", get(api::get_visitor_stats))
+        .route("/api/form-token", get(api::get_form_token))
+        .route("/admin/login", get(admin::handle_login_page))
         .merge(api_write_routes)
-        .nest_service("/assets", assets_service)
-        .nest_service("/favicon.ico", ServeFile::new("assets/favicon/favicon.ico"))
-        .nest_service("/robots.txt", ServeFile::new("assets/robots.txt"))
+        .merge(api_submit_routes)
+        .merge(api_password_routes)
+        .merge(admin_routes)
+        .merge(activitypub_routes)
+        .merge(metrics_routes)
+        .merge(assets_routes)
+        .route_service("/favicon.ico", ServeFile::new("assets/favicon/favicon.ico"))
+        .route_service("/robots.txt", ServeFile::new("assets/robots.txt"))
+        .fallback(pages::handle_error)
         .layer(axum::middleware::from_fn(trailing_slash_redirect))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            visitor::assign_visitor,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            ratelimit::resolve_client_ip,
+        ))
         .layer(SetResponseHeaderLayer::overriding(
             header::HeaderName::from_static("x-content-type-options"),
             header::HeaderValue::from_static("nosniff"),
@@ -104,21 +253,16 @@ pub fn create_router(state: SharedState) -> Router {
             header::HeaderName::from_static("strict-transport-security"),
             header::HeaderValue::from_static("max-age=63072000; includeSubDomains; preload"),
         ))
-        .layer(SetResponseHeaderLayer::overriding(
-            header::HeaderName::from_static("content-security-policy"),
-            header::HeaderValue::from_static(
-                "default-src 'self'; \
-                 script-src 'self' 'unsafe-inline' https://unpkg.com https://cdn.jsdelivr.net https://cdnjs.cloudflare.com https://www.googletagmanager.com; \
-                 style-src 'self' 'unsafe-inline' https://fonts.googleapis.com https://unpkg.com https://cdn.jsdelivr.net https://cdnjs.cloudflare.com; \
-                 font-src 'self' https://fonts.gstatic.com https://unpkg.com https://cdn.jsdelivr.net; \
-                 img-src 'self' data: https:; \
-                 connect-src 'self' https://www.google-analytics.com; \
-                 frame-ancestors 'none'"
-            ),
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            csp::content_security_policy,
         ))
         .layer(CompressionLayer::new())
         .layer(tower_http::trace::TraceLayer::new_for_http())
-        .fallback(pages::handle_error)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track_requests,
+        ))
         .with_state(state)
 }
 
@@ -153,6 +297,14 @@ pub fn add_live_reload(app: Router, state: SharedState) -> Router {
     let state_clone = state.clone();
     let mut watcher = notify::recommended_watcher(move |evt: Result<notify::Event, _>| {
         if let Ok(evt) = evt {
+            // Re-hash assets so pages link to the changed files
+            if evt
+                .paths
+                .iter()
+                .any(|p| p.to_string_lossy().contains("assets"))
+            {
+                assets::reload();
+            }
             let is_content_change = evt
                 .paths
                 .iter()
@@ -160,13 +312,16 @@ pub fn add_live_reload(app: Router, state: SharedState) -> Router {
             if is_content_change {
                 let posts_state = state_clone.posts.clone();
                 let series_cache = state_clone.series_cache.clone();
+                let state_for_tasks = state_clone.clone();
                 tokio::spawn(async move {
                     // Clear and reload posts
                     {
                         let mut posts = posts_state.write().await;
                         posts.clear();
                     }
-                    if let Err(e) = load_posts(posts_state.clone()).await {
+                    let loaded = load_posts(posts_state.clone()).await;
+                    state_for_tasks.metrics.record_content_load(loaded.is_ok());
+                    if let Err(e) = loaded {
                         tracing::error!("Failed to reload posts: {}", e);
                         return;
                     }
@@ -177,6 +332,8 @@ pub fn add_live_reload(app: Router, state: SharedState) -> Router {
                     };
                     *series_cache.write().await = new_series;
                     tracing::info!("Contents reloaded successfully");
+                    state_for_tasks.record_content_snapshot().await;
+                    state_for_tasks.spawn_content_tasks();
                 });
             }
         }
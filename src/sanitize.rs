@@ -0,0 +1,152 @@
+use pulldown_cmark::{html, Event, Options, Parser};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// Tags that survive sanitization. Anything else the Markdown parser emits
+/// (headings, lists, images, ...) is unwrapped to its text content.
+const ALLOWED_TAGS: &[&str] = &["p", "br", "em", "strong", "code", "pre", "a"];
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::empty();
+        builder
+            .tags(ALLOWED_TAGS.iter().copied().collect())
+            .clean_content_tags(HashSet::from(["script", "style"]))
+            .add_tag_attributes("a", ["href"])
+            .url_schemes(ALLOWED_URL_SCHEMES.iter().copied().collect())
+            .url_relative(ammonia::UrlRelative::Deny)
+            .link_rel(Some("nofollow ugc"));
+        builder
+    })
+}
+
+/// Render a comment or guestbook message from the restricted Markdown
+/// subset to HTML that is safe to embed as-is.
+///
+/// Raw HTML in the source is shown as text rather than interpreted, and
+/// single newlines are kept as line breaks since that is how plain-text
+/// messages have always been displayed.
+pub fn render_user_markdown(source: &str) -> String {
+    let events = Parser::new_ext(source, Options::empty()).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::SoftBreak => Event::HardBreak,
+        other => other,
+    });
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events);
+    sanitizer().clean(&unsafe_html).to_string()
+}
+
+/// Sanitize HTML received from another server (e.g. a federated reply)
+/// with the same allowlist as local comments.
+pub fn clean_remote_html(html: &str) -> String {
+    sanitizer().clean(html).to_string()
+}
+
+/// Longest author website accepted, in bytes.
+pub const MAX_WEBSITE_LEN: usize = 200;
+
+/// Check an author's website: an absolute `http` or `https` URL with a host.
+/// Returns it normalized, or `None` when it is not acceptable.
+pub fn normalize_website(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    if raw.len() > MAX_WEBSITE_LEN {
+        return None;
+    }
+    let url = url::Url::parse(raw).ok()?;
+    let acceptable = matches!(url.scheme(), "http" | "https")
+        && url.host_str().is_some()
+        && url.username().is_empty()
+        && url.password().is_none();
+    acceptable.then(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_basic_markdown() {
+        let html = render_user_markdown("Hello *world* and **bold** with `code`");
+        assert_eq!(
+            html,
+            "<p>Hello <em>world</em> and <strong>bold</strong> with <code>code</code></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_fenced_code_block() {
+        let html = render_user_markdown("```rust\nfn main() {}\n```");
+        assert!(html.contains("<pre><code>fn main() {}\n</code></pre>"));
+    }
+
+    #[test]
+    fn test_links_get_nofollow_ugc() {
+        let html = render_user_markdown("[site](https://example.com)");
+        assert!(html.contains("href=\"https://example.com\""));
+        assert!(html.contains("rel=\"nofollow ugc\""));
+    }
+
+    #[test]
+    fn test_javascript_links_are_dropped() {
+        let html = render_user_markdown("[x](javascript:alert(1))");
+        assert!(!html.contains("javascript"));
+    }
+
+    #[test]
+    fn test_raw_html_is_escaped() {
+        let html = render_user_markdown("<script>alert(1)</script> <b onclick=x>hi</b>");
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<b"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn test_disallowed_blocks_keep_text() {
+        let html = render_user_markdown("# Title\n\n![alt](https://example.com/a.png)");
+        assert!(!html.contains("<h1"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("Title"));
+    }
+
+    #[test]
+    fn test_single_newlines_become_breaks() {
+        assert_eq!(
+            render_user_markdown("line one\nline two"),
+            "<p>line one<br>\nline two</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_remote_html_keeps_allowlist_only() {
+        let html = clean_remote_html(
+            r#"<p><span class="h-card"><a href="https://a.example/@x" class="u-url mention">@x</a></span> hi<img src=x onerror=alert(1)></p>"#,
+        );
+        assert_eq!(
+            html,
+            r#"<p><a href="https://a.example/@x" rel="nofollow ugc">@x</a> hi</p>"#
+        );
+    }
+
+    #[test]
+    fn test_normalize_website() {
+        assert_eq!(
+            normalize_website(" https://Example.com "),
+            Some("https://example.com/".to_string())
+        );
+        assert_eq!(
+            normalize_website("http://example.com/about?x=1"),
+            Some("http://example.com/about?x=1".to_string())
+        );
+        assert_eq!(normalize_website("javascript:alert(1)"), None);
+        assert_eq!(normalize_website("example.com"), None);
+        assert_eq!(normalize_website("https://user:pw@example.com"), None);
+        assert_eq!(
+            normalize_website(&format!("https://example.com/{}", "a".repeat(200))),
+            None
+        );
+    }
+}
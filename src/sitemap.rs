@@ -0,0 +1,398 @@
+//! Sitemap index plus per-section and per-language sitemaps.
+//!
+//! `/sitemap.xml` is an index pointing at `/sitemaps/{section}.xml`: static
+//! pages, series, tag listings, and one post sitemap per language. Every
+//! translation of a post is listed with reciprocal hreflang alternates and
+//! an `x-default`, along with its images.
+
+use crate::{
+    feed::{absolutize_urls, xml_escape},
+    i18n::Lang,
+    post::{Post, PostType, Series},
+    SITE_URL,
+};
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapSection {
+    Pages,
+    Series,
+    Tags,
+    Posts(Lang),
+}
+
+impl SitemapSection {
+    pub fn file_name(self) -> String {
+        match self {
+            SitemapSection::Pages => "pages.xml".to_string(),
+            SitemapSection::Series => "series.xml".to_string(),
+            SitemapSection::Tags => "tags.xml".to_string(),
+            SitemapSection::Posts(lang) => format!("posts-{}.xml", lang.as_str()),
+        }
+    }
+
+    pub fn parse(file_name: &str) -> Option<Self> {
+        match file_name {
+            "pages.xml" => Some(SitemapSection::Pages),
+            "series.xml" => Some(SitemapSection::Series),
+            "tags.xml" => Some(SitemapSection::Tags),
+            _ => {
+                let code = file_name.strip_prefix("posts-")?.strip_suffix(".xml")?;
+                Lang::all()
+                    .iter()
+                    .find(|l| l.as_str() == code)
+                    .map(|&l| SitemapSection::Posts(l))
+            }
+        }
+    }
+
+    pub fn url(self) -> String {
+        format!("{}/sitemaps/{}", SITE_URL, self.file_name())
+    }
+
+    /// Sections that have at least one URL.
+    pub fn all(posts: &[Post], series: &[Series]) -> Vec<SitemapSection> {
+        let mut sections = vec![SitemapSection::Pages];
+        if !series.is_empty() {
+            sections.push(SitemapSection::Series);
+        }
+        if posts.iter().any(|p| !p.metadata.tags.is_empty()) {
+            sections.push(SitemapSection::Tags);
+        }
+        sections.extend(
+            Lang::all()
+                .iter()
+                .filter(|&&l| posts.iter().any(|p| p.lang == l))
+                .map(|&l| SitemapSection::Posts(l)),
+        );
+        sections
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SitemapUrl {
+    pub loc: String,
+    pub lastmod: Option<DateTime<FixedOffset>>,
+    pub changefreq: &'static str,
+    /// `(hreflang, href)` pairs, including this URL itself and `x-default`.
+    pub alternates: Vec<(String, String)>,
+    pub images: Vec<String>,
+}
+
+impl SitemapUrl {
+    fn new(loc: String, lastmod: Option<DateTime<FixedOffset>>, changefreq: &'static str) -> Self {
+        SitemapUrl {
+            loc,
+            lastmod,
+            changefreq,
+            alternates: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+}
+
+const LISTINGS: [(&str, PostType); 3] = [
+    ("/blog", PostType::Blog),
+    ("/review", PostType::Review),
+    ("/diary", PostType::Diary),
+];
+
+fn latest<'a>(posts: impl Iterator<Item = &'a Post>) -> Option<DateTime<FixedOffset>> {
+    posts.map(|p| p.metadata.updated_at).max()
+}
+
+/// The URLs listed in one section's sitemap.
+pub fn section_urls(section: SitemapSection, posts: &[Post], series: &[Series]) -> Vec<SitemapUrl> {
+    match section {
+        SitemapSection::Pages => {
+            let mut urls = vec![SitemapUrl::new(
+                format!("{}/", SITE_URL),
+                latest(posts.iter()),
+                "weekly",
+            )];
+            for (path, post_type) in &LISTINGS {
+                urls.push(SitemapUrl::new(
+                    format!("{}{}", SITE_URL, path),
+                    latest(posts.iter().filter(|p| &p.post_type == post_type)),
+                    "weekly",
+                ));
+            }
+            urls.push(SitemapUrl::new(
+                format!("{}/series", SITE_URL),
+                series.iter().map(|s| s.updated_at).max(),
+                "weekly",
+            ));
+            urls.push(SitemapUrl::new(
+                format!("{}/guestbook", SITE_URL),
+                None,
+                "weekly",
+            ));
+            urls
+        }
+        SitemapSection::Series => series
+            .iter()
+            .map(|s| {
+                let mut url = url::Url::parse(SITE_URL).unwrap();
+                url.path_segments_mut().unwrap().extend(["series", &s.name]);
+                SitemapUrl::new(url.to_string(), Some(s.updated_at), "weekly")
+            })
+            .collect(),
+        SitemapSection::Tags => {
+            let mut urls = Vec::new();
+            for (path, post_type) in &LISTINGS {
+                let mut tags: BTreeMap<&str, DateTime<FixedOffset>> = BTreeMap::new();
+                for post in posts.iter().filter(|p| &p.post_type == post_type) {
+                    for tag in &post.metadata.tags {
+                        let entry = tags.entry(tag).or_insert(post.metadata.updated_at);
+                        *entry = (*entry).max(post.metadata.updated_at);
+                    }
+                }
+                urls.extend(tags.into_iter().map(|(tag, lastmod)| {
+                    let query: String = url::form_urlencoded::Serializer::new(String::new())
+                        .append_pair("category", tag)
+                        .finish();
+                    SitemapUrl::new(
+                        format!("{}{}?{}", SITE_URL, path, query),
+                        Some(lastmod),
+                        "weekly",
+                    )
+                }));
+            }
+            urls
+        }
+        SitemapSection::Posts(lang) => {
+            let mut groups: HashMap<&str, Vec<&Post>> = HashMap::new();
+            for post in posts {
+                groups.entry(&post.translation_key).or_default().push(post);
+            }
+
+            let mut in_lang: Vec<&Post> = posts.iter().filter(|p| p.lang == lang).collect();
+            in_lang.sort_by_key(|p| std::cmp::Reverse(p.metadata.updated_at));
+            in_lang
+                .into_iter()
+                .map(|post| {
+                    let mut url =
+                        SitemapUrl::new(post_url(post), Some(post.metadata.updated_at), "monthly");
+                    url.alternates = alternates(&groups[post.translation_key.as_str()]);
+                    url.images = post_images(post);
+                    url
+                })
+                .collect()
+        }
+    }
+}
+
+fn post_url(post: &Post) -> String {
+    format!("{}/post/{}", SITE_URL, post.slug)
+}
+
+/// Reciprocal hreflang links for a translation group. `x-default` points at
+/// the English version, or the first available language otherwise.
+fn alternates(group: &[&Post]) -> Vec<(String, String)> {
+    if group.len() < 2 {
+        return Vec::new();
+    }
+    let mut links: Vec<(String, String)> = Lang::all()
+        .iter()
+        .filter_map(|&l| group.iter().find(|p| p.lang == l))
+        .map(|p| (p.lang.as_str().to_string(), post_url(p)))
+        .collect();
+    let default = links
+        .iter()
+        .find(|(lang, _)| lang == Lang::En.as_str())
+        .unwrap_or(&links[0])
+        .1
+        .clone();
+    links.push(("x-default".to_string(), default));
+    links
+}
+
+/// Absolute URLs of the post's `og_image` and inline `<img>`s, deduplicated.
+fn post_images(post: &Post) -> Vec<String> {
+    static IMG_RE: OnceLock<Regex> = OnceLock::new();
+    let re = IMG_RE.get_or_init(|| Regex::new(r#"<img\s[^>]*?src="([^"]+)""#).unwrap());
+
+    let base = post_url(post);
+    let content = absolutize_urls(&post.content, &base);
+    let og_image = post
+        .metadata
+        .og_image
+        .as_deref()
+        .and_then(|src| url::Url::parse(&base).ok()?.join(src).ok())
+        .map(|u| u.to_string());
+
+    let mut seen = BTreeSet::new();
+    og_image
+        .into_iter()
+        .chain(re.captures_iter(&content).map(|c| c[1].to_string()))
+        .filter(|src| src.starts_with("http://") || src.starts_with("https://"))
+        .filter(|src| seen.insert(src.clone()))
+        .collect()
+}
+
+pub fn render_index(entries: &[(SitemapSection, Option<DateTime<FixedOffset>>)]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (section, lastmod) in entries {
+        xml.push_str("  <sitemap>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", xml_escape(&section.url())));
+        if let Some(lastmod) = lastmod {
+            xml.push_str(&format!(
+                "    <lastmod>{}</lastmod>\n",
+                lastmod.format("%Y-%m-%d")
+            ));
+        }
+        xml.push_str("  </sitemap>\n");
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+pub fn render_urlset(urls: &[SitemapUrl]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\"\n");
+    xml.push_str("        xmlns:xhtml=\"http://www.w3.org/1999/xhtml\"\n");
+    xml.push_str("        xmlns:image=\"http://www.google.com/schemas/sitemap-image/1.1\">\n");
+    for url in urls {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", xml_escape(&url.loc)));
+        if let Some(lastmod) = url.lastmod {
+            xml.push_str(&format!(
+                "    <lastmod>{}</lastmod>\n",
+                lastmod.format("%Y-%m-%d")
+            ));
+        }
+        xml.push_str(&format!(
+            "    <changefreq>{}</changefreq>\n",
+            url.changefreq
+        ));
+        for (hreflang, href) in &url.alternates {
+            xml.push_str(&format!(
+                "    <xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                hreflang,
+                xml_escape(href)
+            ));
+        }
+        for image in &url.images {
+            xml.push_str(&format!(
+                "    <image:image><image:loc>{}</image:loc></image:image>\n",
+                xml_escape(image)
+            ));
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Shared by the index and every section: changes when any post or series
+/// is added, removed or updated.
+pub fn etag(name: &str, posts: &[Post], series: &[Series]) -> String {
+    let latest_ts = posts
+        .iter()
+        .map(|p| p.metadata.updated_at)
+        .chain(series.iter().map(|s| s.updated_at))
+        .max()
+        .map(|u| u.timestamp())
+        .unwrap_or(0);
+    format!(
+        "\"sitemap-{}-{}-{}-{}\"",
+        name,
+        posts.len(),
+        series.len(),
+        latest_ts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::sample_post;
+
+    fn posts() -> Vec<Post> {
+        let mut en = sample_post("hello");
+        en.content = r#"<p><img src="/assets/a.png" alt=""><img src="data:x"></p>"#.to_string();
+        en.metadata.og_image = Some("/assets/og.png".to_string());
+
+        let mut ko = sample_post("hello-ko");
+        ko.lang = Lang::Ko;
+        ko.translation_key = "hello".to_string();
+        ko.metadata.tags = vec!["러스트 & c".to_string()];
+
+        vec![en, ko, sample_post("solo")]
+    }
+
+    #[test]
+    fn test_sections_and_parse() {
+        let posts = posts();
+        let sections = SitemapSection::all(&posts, &[]);
+        assert_eq!(
+            sections,
+            vec![
+                SitemapSection::Pages,
+                SitemapSection::Tags,
+                SitemapSection::Posts(Lang::Ko),
+                SitemapSection::Posts(Lang::En),
+            ]
+        );
+        for section in sections {
+            assert_eq!(SitemapSection::parse(&section.file_name()), Some(section));
+        }
+        assert_eq!(SitemapSection::parse("posts-fr.xml"), None);
+    }
+
+    #[test]
+    fn test_translations_have_reciprocal_hreflang() {
+        let posts = posts();
+        let en = section_urls(SitemapSection::Posts(Lang::En), &posts, &[]);
+        let ko = section_urls(SitemapSection::Posts(Lang::Ko), &posts, &[]);
+        assert_eq!(en.len(), 2);
+        assert_eq!(ko.len(), 1);
+
+        let hello = en.iter().find(|u| u.loc.ends_with("/hello")).unwrap();
+        let expected = vec![
+            (
+                "ko".to_string(),
+                "https://miniex.blog/post/hello-ko".to_string(),
+            ),
+            (
+                "en".to_string(),
+                "https://miniex.blog/post/hello".to_string(),
+            ),
+            (
+                "x-default".to_string(),
+                "https://miniex.blog/post/hello".to_string(),
+            ),
+        ];
+        assert_eq!(hello.alternates, expected);
+        assert_eq!(ko[0].alternates, expected);
+        assert_eq!(
+            hello.images,
+            vec![
+                "https://miniex.blog/assets/og.png",
+                "https://miniex.blog/assets/a.png"
+            ]
+        );
+
+        let solo = en.iter().find(|u| u.loc.ends_with("/solo")).unwrap();
+        assert!(solo.alternates.is_empty());
+    }
+
+    #[test]
+    fn test_render_escapes_tag_urls() {
+        let posts = posts();
+        let xml = render_urlset(&section_urls(SitemapSection::Tags, &posts, &[]));
+        assert!(xml.contains(
+            "<loc>https://miniex.blog/blog?category=%EB%9F%AC%EC%8A%A4%ED%8A%B8+%26+c</loc>"
+        ));
+        assert!(xml.contains("<loc>https://miniex.blog/blog?category=rust</loc>"));
+
+        let index = render_index(&[(SitemapSection::Posts(Lang::Ko), None)]);
+        assert!(index.contains("<loc>https://miniex.blog/sitemaps/posts-ko.xml</loc>"));
+    }
+}
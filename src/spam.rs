@@ -0,0 +1,488 @@
+use crate::{
+    config::Config,
+    db::{Database, ItemKind},
+};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Form tokens older than this no longer count as a human-paced submission.
+const FORM_TOKEN_MAX_AGE_SECS: i64 = 60 * 60 * 24;
+/// Number of tokens with the strongest signal that feed the Bayes estimate.
+const BAYES_INTERESTING_TOKENS: usize = 15;
+/// Classifier output is ignored until both classes have this many documents.
+const BAYES_MIN_DOCS: u32 = 5;
+const LINK_TOKEN: &str = "__link__";
+
+/// Result of scoring a submission. Anything at or above the configured
+/// threshold is stored but held back from public listings.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpamVerdict {
+    pub score: f64,
+    pub is_spam: bool,
+    pub reasons: Vec<&'static str>,
+}
+
+/// Fields of a comment or guestbook submission that the filter looks at.
+pub struct Submission<'a> {
+    pub author: &'a str,
+    pub content: &'a str,
+    /// Author's website, counted as one more link.
+    pub website: Option<&'a str>,
+    /// Hidden form field that people never see; bots tend to fill it in.
+    pub honeypot: Option<&'a str>,
+    pub form_token: Option<&'a str>,
+}
+
+/// Word and regex blocklist, one pattern per line. Lines wrapped in `/.../`
+/// are regular expressions, `#` starts a comment, anything else is a
+/// case-insensitive substring.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    words: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Blocklist {
+    pub fn parse(source: &str) -> Self {
+        let mut list = Blocklist::default();
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(pattern) = line
+                .strip_prefix('/')
+                .and_then(|rest| rest.strip_suffix('/'))
+            {
+                match Regex::new(&format!("(?i){}", pattern)) {
+                    Ok(re) => list.patterns.push(re),
+                    Err(e) => tracing::warn!("Skipping invalid blocklist regex {:?}: {}", line, e),
+                }
+            } else {
+                list.words.push(line.to_lowercase());
+            }
+        }
+        list
+    }
+
+    pub fn load(path: &str) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let list = Self::parse(&source);
+                tracing::info!(
+                    "Loaded spam blocklist from {} ({} words, {} patterns)",
+                    path,
+                    list.words.len(),
+                    list.patterns.len()
+                );
+                list
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Blocklist::default(),
+            Err(e) => {
+                tracing::warn!("Could not read spam blocklist {}: {}", path, e);
+                Blocklist::default()
+            }
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let lower = text.to_lowercase();
+        self.words.iter().any(|w| lower.contains(w.as_str()))
+            || self.patterns.iter().any(|re| re.is_match(text))
+    }
+}
+
+pub struct SpamFilter {
+    threshold: f64,
+    min_submit_secs: i64,
+    max_links: usize,
+    blocklist: Blocklist,
+    secret: Vec<u8>,
+}
+
+impl SpamFilter {
+    pub fn new(config: &Config) -> Self {
+        let secret = match &config.form_token_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                use argon2::password_hash::rand_core::{OsRng, RngCore};
+                let mut bytes = vec![0u8; 32];
+                OsRng.fill_bytes(&mut bytes);
+                bytes
+            }
+        };
+        SpamFilter {
+            threshold: config.spam_threshold,
+            min_submit_secs: config.spam_min_submit_secs,
+            max_links: config.spam_max_links,
+            blocklist: Blocklist::load(&config.spam_blocklist_path),
+            secret,
+        }
+    }
+
+    /// Issue a form token stamped with the current time.
+    pub fn issue_form_token(&self, now: i64) -> String {
+        format!("{}.{}", now, self.sign(now))
+    }
+
+    fn sign(&self, issued_at: i64) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        mac.update(issued_at.to_string().as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Seconds since the token was issued, if its signature is valid.
+    fn token_age(&self, token: &str, now: i64) -> Option<i64> {
+        let (issued, signature) = token.split_once('.')?;
+        let issued: i64 = issued.parse().ok()?;
+        let signature = hex::decode(signature).ok()?;
+        let mut mac = HmacSha256::new_from_slice(&self.secret).ok()?;
+        mac.update(issued.to_string().as_bytes());
+        mac.verify_slice(&signature).ok()?;
+        Some(now - issued)
+    }
+
+    /// Heuristic part of the score; everything except the classifier.
+    fn heuristic_score(&self, submission: &Submission, now: i64) -> (f64, Vec<&'static str>) {
+        let mut score = 0.0;
+        let mut reasons = Vec::new();
+
+        if submission.honeypot.is_some_and(|v| !v.trim().is_empty()) {
+            score += 1.0;
+            reasons.push("honeypot");
+        }
+
+        match submission.form_token.and_then(|t| self.token_age(t, now)) {
+            None => {
+                score += 0.5;
+                reasons.push("missing_token");
+            }
+            Some(age) if age < self.min_submit_secs => {
+                score += 0.6;
+                reasons.push("too_fast");
+            }
+            Some(age) if age > FORM_TOKEN_MAX_AGE_SECS => {
+                score += 0.3;
+                reasons.push("stale_token");
+            }
+            Some(_) => {}
+        }
+
+        let (content_score, content_reasons) = self.content_score(submission);
+        score += content_score;
+        reasons.extend(content_reasons);
+        (score, reasons)
+    }
+
+    /// Links and the blocklist, the checks that also apply to edits.
+    fn content_score(&self, submission: &Submission) -> (f64, Vec<&'static str>) {
+        let mut score = 0.0;
+        let mut reasons = Vec::new();
+
+        let links = count_links(submission.content) + submission.website.is_some() as usize;
+        if links > self.max_links {
+            score += (0.2 * (links - self.max_links) as f64).min(0.6);
+            reasons.push("too_many_links");
+        }
+        if count_links(submission.author) > 0 {
+            score += 0.5;
+            reasons.push("link_in_author");
+        }
+
+        if self.blocklist.matches(submission.author)
+            || self.blocklist.matches(submission.content)
+            || submission
+                .website
+                .is_some_and(|w| self.blocklist.matches(w))
+        {
+            score += 0.5;
+            reasons.push("blocklist");
+        }
+
+        (score, reasons)
+    }
+
+    /// Score a submission against the heuristics and the trained classifier.
+    pub async fn check(&self, db: &Database, submission: &Submission<'_>) -> SpamVerdict {
+        let now = chrono::Utc::now().timestamp();
+        let (score, reasons) = self.heuristic_score(submission, now);
+        self.classify(db, submission, score, reasons).await
+    }
+
+    /// Score edited content. The form token and honeypot belonged to the
+    /// original submission, so only the content checks and the classifier
+    /// apply.
+    pub async fn check_edit(&self, db: &Database, content: &str) -> SpamVerdict {
+        let submission = Submission {
+            author: "",
+            content,
+            website: None,
+            honeypot: None,
+            form_token: None,
+        };
+        let (score, reasons) = self.content_score(&submission);
+        self.classify(db, &submission, score, reasons).await
+    }
+
+    /// Add the classifier's estimate to a heuristic score.
+    async fn classify(
+        &self,
+        db: &Database,
+        submission: &Submission<'_>,
+        mut score: f64,
+        mut reasons: Vec<&'static str>,
+    ) -> SpamVerdict {
+        let tokens = tokenize(&format!("{} {}", submission.author, submission.content));
+        match bayes_probability(db, &tokens).await {
+            Ok(Some(p)) if p > 0.5 => {
+                score += (p - 0.5) * 2.0;
+                reasons.push("bayes");
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("spam classifier lookup failed: {}", e),
+        }
+
+        SpamVerdict {
+            score,
+            is_spam: score >= self.threshold,
+            reasons,
+        }
+    }
+}
+
+/// Count links: explicit URLs, `www.` hosts and Markdown/HTML link syntax.
+pub fn count_links(text: &str) -> usize {
+    let lower = text.to_lowercase();
+    let urls = lower.matches("http://").count() + lower.matches("https://").count();
+    ["www.", "<a ", "]("]
+        .iter()
+        .map(|needle| lower.matches(needle).count())
+        .fold(urls, usize::max)
+}
+
+/// Lowercased, de-duplicated word tokens. Each document contributes a token
+/// at most once, and any link collapses into a single marker token.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut tokens = Vec::new();
+    if count_links(text) > 0 {
+        seen.insert(LINK_TOKEN.to_string());
+        tokens.push(LINK_TOKEN.to_string());
+    }
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+    {
+        let len = word.chars().count();
+        if (2..=30).contains(&len) && seen.insert(word.clone()) {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+/// Robinson-style smoothed spam probability of a single token.
+fn token_probability(spam: u32, ham: u32, spam_docs: u32, ham_docs: u32) -> f64 {
+    const STRENGTH: f64 = 1.0;
+    const PRIOR: f64 = 0.5;
+
+    let spam_freq = spam as f64 / spam_docs.max(1) as f64;
+    let ham_freq = ham as f64 / ham_docs.max(1) as f64;
+    let raw = if spam_freq + ham_freq == 0.0 {
+        PRIOR
+    } else {
+        spam_freq / (spam_freq + ham_freq)
+    };
+    let n = (spam + ham) as f64;
+    (STRENGTH * PRIOR + n * raw) / (STRENGTH + n)
+}
+
+/// Combine per-token probabilities, using the tokens furthest from neutral.
+pub fn combine_probabilities(mut probs: Vec<f64>) -> f64 {
+    probs.sort_by(|a, b| (b - 0.5).abs().total_cmp(&(a - 0.5).abs()));
+    let log_odds: f64 = probs
+        .iter()
+        .take(BAYES_INTERESTING_TOKENS)
+        .map(|p| p.clamp(0.01, 0.99))
+        .map(|p| (p / (1.0 - p)).ln())
+        .sum();
+    1.0 / (1.0 + (-log_odds).exp())
+}
+
+/// Classifier estimate for a token set, or `None` while under-trained.
+async fn bayes_probability(db: &Database, tokens: &[String]) -> Result<Option<f64>, sqlx::Error> {
+    let (spam_docs, ham_docs) = db.get_spam_corpus_sizes().await?;
+    if spam_docs < BAYES_MIN_DOCS || ham_docs < BAYES_MIN_DOCS {
+        return Ok(None);
+    }
+    let counts: HashMap<String, (u32, u32)> = db.get_spam_token_counts(tokens).await?;
+    let probs: Vec<f64> = counts
+        .values()
+        .map(|&(spam, ham)| token_probability(spam, ham, spam_docs, ham_docs))
+        .collect();
+    if probs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(combine_probabilities(probs)))
+}
+
+/// Record a moderator's spam/ham decision and train the classifier on it.
+/// Returns `false` if the item does not exist.
+pub async fn record_decision(
+    db: &Database,
+    kind: ItemKind,
+    id: &str,
+    is_spam: bool,
+) -> Result<bool, sqlx::Error> {
+    let Some((author, content, trained)) = db.get_spam_training_item(kind, id).await? else {
+        return Ok(false);
+    };
+    let label = if is_spam { "spam" } else { "ham" };
+    if trained.as_deref() != Some(label) {
+        let tokens = tokenize(&format!("{} {}", author, content));
+        // A reversed decision first takes the item out of the other class
+        if let Some(previous) = trained.as_deref() {
+            db.adjust_spam_tokens(&tokens, previous == "spam", -1)
+                .await?;
+        }
+        db.adjust_spam_tokens(&tokens, is_spam, 1).await?;
+    }
+    db.set_spam_decision(kind, id, is_spam).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> SpamFilter {
+        SpamFilter {
+            threshold: 1.0,
+            min_submit_secs: 3,
+            max_links: 2,
+            blocklist: Blocklist::parse("casino\n/cheap\\s+pills/\n# comment"),
+            secret: b"test-secret".to_vec(),
+        }
+    }
+
+    fn submission<'a>(content: &'a str, token: Option<&'a str>) -> Submission<'a> {
+        Submission {
+            author: "reader",
+            content,
+            website: None,
+            honeypot: None,
+            form_token: token,
+        }
+    }
+
+    #[test]
+    fn test_form_token_roundtrip() {
+        let f = filter();
+        let token = f.issue_form_token(1000);
+        assert_eq!(f.token_age(&token, 1010), Some(10));
+        assert_eq!(f.token_age("1000.deadbeef", 1010), None);
+        assert_eq!(f.token_age(&token.replace("1000.", "900."), 1010), None);
+    }
+
+    #[test]
+    fn test_clean_submission_scores_zero() {
+        let f = filter();
+        let token = f.issue_form_token(1000);
+        let (score, reasons) = f.heuristic_score(&submission("Nice post!", Some(&token)), 1030);
+        assert_eq!(score, 0.0);
+        assert!(reasons.is_empty());
+    }
+
+    #[test]
+    fn test_honeypot_alone_reaches_threshold() {
+        let f = filter();
+        let token = f.issue_form_token(1000);
+        let s = Submission {
+            honeypot: Some("bot@example.com"),
+            ..submission("hello", Some(&token))
+        };
+        let (score, _) = f.heuristic_score(&s, 1030);
+        assert!(score >= f.threshold);
+    }
+
+    #[test]
+    fn test_fast_submission_and_missing_token() {
+        let f = filter();
+        let token = f.issue_form_token(1000);
+        let (_, reasons) = f.heuristic_score(&submission("hi", Some(&token)), 1001);
+        assert_eq!(reasons, vec!["too_fast"]);
+        let (_, reasons) = f.heuristic_score(&submission("hi", None), 1001);
+        assert_eq!(reasons, vec!["missing_token"]);
+    }
+
+    #[test]
+    fn test_blocklist_words_and_regex() {
+        let list = Blocklist::parse("casino\n/cheap\\s+pills/\n# comment\n/[bad/");
+        assert!(list.matches("Best CASINO bonus"));
+        assert!(list.matches("buy Cheap   pills now"));
+        assert!(!list.matches("a comment about pills"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_adding_blocklisted_content_hides_comment() {
+        let f = SpamFilter {
+            threshold: 0.5,
+            ..filter()
+        };
+        let db = crate::db::test_db().await;
+        let comment = db
+            .create_comment(
+                "post",
+                "reader",
+                "Nice post!",
+                Some("pw"),
+                &SpamVerdict::default(),
+            )
+            .await
+            .unwrap();
+
+        let verdict = f.check_edit(&db, "Visit my casino").await;
+        assert!(verdict.is_spam);
+        assert_eq!(verdict.reasons, vec!["blocklist"]);
+        assert!(db
+            .update_comment(&comment.id, "Visit my casino", "pw", &verdict)
+            .await
+            .unwrap());
+        assert!(db.get_comments_by_post("post").await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_count_links() {
+        assert_eq!(count_links("no links here"), 0);
+        assert_eq!(count_links("see https://a.com and http://b.com"), 2);
+        assert_eq!(count_links("[x](https://a.com)"), 1);
+    }
+
+    #[test]
+    fn test_tokenize_dedups_and_marks_links() {
+        let tokens = tokenize("Buy buy NOW https://spam.example a");
+        assert_eq!(tokens[0], LINK_TOKEN);
+        assert_eq!(tokens.iter().filter(|t| *t == "buy").count(), 1);
+        assert!(!tokens.contains(&"a".to_string()));
+    }
+
+    #[test]
+    fn test_combine_probabilities() {
+        assert!(combine_probabilities(vec![0.99, 0.95, 0.9]) > 0.99);
+        assert!(combine_probabilities(vec![0.01, 0.05, 0.1]) < 0.01);
+        assert!((combine_probabilities(vec![0.5, 0.5]) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_token_probability_smoothing() {
+        // Unseen tokens stay neutral, a single sighting is pulled toward 0.5
+        assert_eq!(token_probability(0, 0, 10, 10), 0.5);
+        let once = token_probability(1, 0, 10, 10);
+        assert!(once > 0.5 && once < 1.0);
+        assert!(token_probability(20, 0, 10, 10) > once);
+    }
+}
@@ -1,8 +1,10 @@
 use crate::{
-    db::Guestbook,
+    db::{Comment, FederatedReply, Guestbook, PostStats, PostViews, Webmention},
     filters,
+    handlers::admin::ViewSeries,
     i18n::{Lang, Translations},
     post::{Post, Series, SeriesNavInfo, SeriesStatus},
+    reactions::ReactionView,
     Blog,
 };
 use askama::Template;
@@ -108,6 +110,9 @@ pub struct PostTemplate {
     pub t: Translations,
     pub lang: Lang,
     pub available_langs: Vec<Lang>,
+    pub webmentions: Vec<Webmention>,
+    pub federated_replies: Vec<FederatedReply>,
+    pub reactions: Vec<ReactionView>,
 }
 
 #[derive(Template)]
@@ -142,3 +147,65 @@ pub struct ErrorTemplate {
     pub t: Translations,
     pub lang: Lang,
 }
+
+#[derive(Template)]
+#[template(path = "admin_login.html")]
+pub struct AdminLoginTemplate {
+    pub blog: Blog,
+    pub t: Translations,
+    pub lang: Lang,
+    pub error: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin.html")]
+pub struct AdminDashboardTemplate {
+    pub blog: Blog,
+    pub t: Translations,
+    pub lang: Lang,
+    pub post_count: usize,
+    pub comment_count: u32,
+    pub guestbook_count: u32,
+    pub visitors_today: u32,
+    pub visitors_total: u32,
+    pub total_views: u32,
+    pub total_reactions: u32,
+    pub spam_held: u32,
+    pub post_stats: Vec<PostStats>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_analytics.html")]
+pub struct AdminAnalyticsTemplate {
+    pub blog: Blog,
+    pub t: Translations,
+    pub lang: Lang,
+    pub series: ViewSeries,
+    pub max_views: u32,
+    pub bot_total: u32,
+    pub top_posts: Vec<PostViews>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_comments.html")]
+pub struct AdminCommentsTemplate {
+    pub blog: Blog,
+    pub t: Translations,
+    pub lang: Lang,
+    pub comments: Vec<Comment>,
+    pub total: u32,
+    pub current_page: u32,
+    pub total_pages: u32,
+}
+
+#[derive(Template)]
+#[template(path = "admin_guestbook.html")]
+pub struct AdminGuestbookTemplate {
+    pub blog: Blog,
+    pub t: Translations,
+    pub lang: Lang,
+    pub entries: Vec<Guestbook>,
+    pub total: u32,
+    pub current_page: u32,
+    pub total_pages: u32,
+}
@@ -0,0 +1,336 @@
+//! Third-party browser libraries, served from `/assets/vendor`.
+//!
+//! `vendor.json` lists each library's upstream URL and where its copy lives
+//! under `assets/vendor`, pinned to a Subresource Integrity hash. `blog
+//! vendor` downloads the copies (run it when building an image, or commit
+//! the results), refusing downloads that do not match their pin and entries
+//! that have none. `blog vendor --pin` records the hash of new entries on
+//! their first download; commit the manifest afterwards so every later
+//! build checks against it. Pages reference libraries by name
+//! and get the local path and hash. Release builds refuse to start while a
+//! copy is missing; debug builds load it from its upstream URL instead, and
+//! that origin is added to the Content Security Policy so the page keeps
+//! working.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha384};
+use std::{
+    collections::HashMap,
+    path::{Component, Path},
+    sync::OnceLock,
+};
+
+pub const MANIFEST_PATH: &str = "vendor.json";
+pub const VENDOR_DIR: &str = "assets/vendor";
+
+/// One file in `vendor.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VendorEntry {
+    /// How pages refer to the file; supporting files such as fonts have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub url: String,
+    /// Path under `assets/vendor`.
+    pub file: String,
+    /// Pinned `sha384-…` hash, filled in by `blog vendor --pin`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+}
+
+impl VendorEntry {
+    /// Relative paths only, so a manifest entry cannot write outside the
+    /// vendor directory.
+    fn has_safe_path(&self) -> bool {
+        let path = Path::new(&self.file);
+        !self.file.is_empty() && path.components().all(|c| matches!(c, Component::Normal(_)))
+    }
+}
+
+/// Where a page loads a library from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VendorAsset {
+    pub src: String,
+    /// Empty when neither a local copy nor a pinned hash exists.
+    pub integrity: String,
+}
+
+#[derive(Debug, Default)]
+pub struct VendorAssets {
+    assets: HashMap<String, VendorAsset>,
+    /// Origins that libraries without a local copy are loaded from.
+    fallback_origins: Vec<String>,
+    /// Files listed in the manifest but not on disk.
+    missing: Vec<String>,
+    /// Why the manifest could not be read, if it could not.
+    manifest_error: Option<String>,
+}
+
+impl VendorAssets {
+    pub fn load(manifest: &Path, dir: &Path) -> Self {
+        match read_manifest(manifest) {
+            Ok(entries) => Self::from_entries(&entries, dir),
+            Err(e) => {
+                tracing::error!("Failed to read {}: {}", manifest.display(), e);
+                VendorAssets {
+                    manifest_error: Some(format!("{}: {}", manifest.display(), e)),
+                    ..VendorAssets::default()
+                }
+            }
+        }
+    }
+
+    pub fn from_entries(entries: &[VendorEntry], dir: &Path) -> Self {
+        let mut vendored = VendorAssets::default();
+        for entry in entries.iter().filter(|e| e.has_safe_path()) {
+            let asset = match std::fs::read(dir.join(&entry.file)) {
+                Ok(bytes) => VendorAsset {
+                    src: crate::assets::url(&format!("vendor/{}", entry.file)),
+                    integrity: entry.integrity.clone().unwrap_or_else(|| integrity(&bytes)),
+                },
+                Err(_) => {
+                    vendored.missing.push(entry.file.clone());
+                    if let Some(origin) = origin_of(&entry.url) {
+                        if !vendored.fallback_origins.contains(&origin) {
+                            vendored.fallback_origins.push(origin);
+                        }
+                    }
+                    VendorAsset {
+                        src: entry.url.clone(),
+                        integrity: entry.integrity.clone().unwrap_or_default(),
+                    }
+                }
+            };
+            if let Some(name) = &entry.name {
+                vendored.assets.insert(name.clone(), asset);
+            }
+        }
+        if !vendored.missing.is_empty() {
+            tracing::warn!(
+                "{} vendored files missing (run `blog vendor`), loading them upstream: {}",
+                vendored.missing.len(),
+                vendored.missing.join(", ")
+            );
+        }
+        vendored
+    }
+
+    /// Fail unless the manifest was read and every file in it is on disk,
+    /// so no library falls back to its CDN.
+    pub fn ensure_complete(&self) -> anyhow::Result<()> {
+        if let Some(e) = &self.manifest_error {
+            anyhow::bail!("failed to read vendor manifest {}", e);
+        }
+        if !self.missing.is_empty() {
+            anyhow::bail!(
+                "{} vendored files missing from {} (run `blog vendor`): {}",
+                self.missing.len(),
+                VENDOR_DIR,
+                self.missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> VendorAsset {
+        self.assets.get(name).cloned().unwrap_or_else(|| {
+            tracing::error!("Unknown vendored asset {:?}", name);
+            VendorAsset::default()
+        })
+    }
+
+    pub fn fallback_origins(&self) -> &[String] {
+        &self.fallback_origins
+    }
+
+    /// `{name: {src, integrity}}` for scripts that load libraries on demand,
+    /// safe to embed in a `<script type="application/json">` block.
+    pub fn json(&self, names: &[&str]) -> String {
+        let map: HashMap<&str, VendorAsset> =
+            names.iter().map(|name| (*name, self.get(name))).collect();
+        serde_json::to_string(&map)
+            .unwrap_or_default()
+            .replace("</", "<\\/")
+    }
+}
+
+/// The process-wide assets, read from the manifest on first use.
+pub fn assets() -> &'static VendorAssets {
+    static ASSETS: OnceLock<VendorAssets> = OnceLock::new();
+    ASSETS.get_or_init(|| VendorAssets::load(Path::new(MANIFEST_PATH), Path::new(VENDOR_DIR)))
+}
+
+/// Subresource Integrity value for `bytes`.
+pub fn integrity(bytes: &[u8]) -> String {
+    format!("sha384-{}", BASE64.encode(Sha384::digest(bytes)))
+}
+
+fn origin_of(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    Some(url.origin().ascii_serialization())
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<Vec<VendorEntry>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Download every file in the manifest into `dir`. Files already present
+/// with the pinned hash are kept, and a download that does not match its
+/// pin is an error. Entries without a pin are refused before anything is
+/// downloaded unless `pin_new` is set, in which case their hash is written
+/// to the manifest.
+pub async fn fetch(manifest: &Path, dir: &Path, pin_new: bool) -> anyhow::Result<()> {
+    let mut entries = read_manifest(manifest)?;
+    let unpinned: Vec<&str> = entries
+        .iter()
+        .filter(|e| e.integrity.is_none())
+        .map(|e| e.file.as_str())
+        .collect();
+    if !pin_new && !unpinned.is_empty() {
+        anyhow::bail!(
+            "{} entries in {} have no pinned hash (run `blog vendor --pin` and commit the manifest): {}",
+            unpinned.len(),
+            manifest.display(),
+            unpinned.join(", ")
+        );
+    }
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("miniex.blog/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let mut pinned = 0;
+
+    for entry in entries.iter_mut() {
+        if !entry.has_safe_path() {
+            anyhow::bail!(
+                "refusing to vendor {:?} outside {}",
+                entry.file,
+                dir.display()
+            );
+        }
+        let path = dir.join(&entry.file);
+        if let (Some(pin), Ok(bytes)) = (&entry.integrity, std::fs::read(&path)) {
+            if integrity(&bytes) == *pin {
+                continue;
+            }
+        }
+
+        let bytes = client
+            .get(&entry.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let hash = integrity(&bytes);
+        match &entry.integrity {
+            Some(pin) if *pin != hash => anyhow::bail!(
+                "{} does not match its pinned hash (expected {}, got {})",
+                entry.url,
+                pin,
+                hash
+            ),
+            Some(_) => {}
+            None => {
+                entry.integrity = Some(hash);
+                pinned += 1;
+            }
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &bytes)?;
+        tracing::info!("Vendored {} ({} bytes)", entry.file, bytes.len());
+    }
+
+    if pinned > 0 {
+        std::fs::write(manifest, serde_json::to_string_pretty(&entries)? + "\n")?;
+        tracing::info!("Pinned {} new hashes in {}", pinned, manifest.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, file: &str, integrity: Option<&str>) -> VendorEntry {
+        VendorEntry {
+            name: Some(name.to_string()),
+            url: format!("https://cdn.example.com/{}", file),
+            file: file.to_string(),
+            integrity: integrity.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_integrity() {
+        assert_eq!(
+            integrity(b""),
+            "sha384-OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb"
+        );
+    }
+
+    #[test]
+    fn test_local_copies_and_upstream_fallback() {
+        let dir = std::env::temp_dir().join(format!("blog-vendor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/a.js"), b"a").unwrap();
+        std::fs::write(dir.join("lib/b.js"), b"b").unwrap();
+
+        let assets = VendorAssets::from_entries(
+            &[
+                entry("a", "lib/a.js", None),
+                entry("b", "lib/b.js", Some("sha384-pinned")),
+                entry("missing", "lib/missing.js", None),
+                entry("escape", "../escape.js", None),
+            ],
+            &dir,
+        );
+
+        assert_eq!(
+            assets.get("a"),
+            VendorAsset {
+                src: "/assets/vendor/lib/a.js".to_string(),
+                integrity: integrity(b"a"),
+            }
+        );
+        // A pinned hash wins, so a tampered copy is refused by the browser
+        assert_eq!(assets.get("b").integrity, "sha384-pinned");
+        assert_eq!(
+            assets.get("missing").src,
+            "https://cdn.example.com/lib/missing.js"
+        );
+        assert_eq!(
+            assets.fallback_origins(),
+            &["https://cdn.example.com".to_string()]
+        );
+        assert_eq!(assets.get("escape"), VendorAsset::default());
+        assert!(assets.ensure_complete().is_err());
+        let complete = VendorAssets::from_entries(&[entry("a", "lib/a.js", None)], &dir);
+        assert!(complete.ensure_complete().is_ok());
+        let unreadable = VendorAssets::load(&dir.join("vendor.json"), &dir);
+        assert!(unreadable.ensure_complete().is_err());
+
+        let json = assets.json(&["a"]);
+        assert!(json.contains("/assets/vendor/lib/a.js"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_refuses_unpinned_entries() {
+        let dir = std::env::temp_dir().join(format!("blog-vendor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("vendor.json");
+        let entries = [entry("a", "lib/a.js", None)];
+        std::fs::write(&manifest, serde_json::to_string(&entries).unwrap()).unwrap();
+
+        // Refused before any download is attempted
+        let err = fetch(&manifest, &dir.join("vendor"), false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("lib/a.js"), "{}", err);
+        assert!(err.to_string().contains("--pin"), "{}", err);
+        assert!(!dir.join("vendor").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
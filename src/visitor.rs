@@ -0,0 +1,284 @@
+//! Server-issued visitor identity for reactions and visit counts.
+//!
+//! A reader's first page load gets a random ID in a signed, `HttpOnly`
+//! first-party cookie. Only a keyed hash of that ID is ever stored, so the
+//! database cannot be turned back into working cookies. Requests without a
+//! valid cookie (API clients, readers who block cookies, and the page load
+//! that issues the cookie) fall back to a keyed hash of the day, address and
+//! user agent, which changes every day.
+
+use crate::{
+    analytics::{today_in, DATE_FORMAT},
+    auth::cookie_value,
+    config::Config,
+    db::Database,
+    ratelimit::ClientIp,
+    SharedState,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const VISITOR_COOKIE: &str = "visitor";
+/// Name of the generated secret in the `secrets` table.
+pub const SECRET_NAME: &str = "visitor";
+const COOKIE_MAX_AGE_SECS: i64 = 60 * 60 * 24 * 365;
+
+/// Who made a request, as far as reactions and statistics are concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visitor {
+    /// Stable per browser with a cookie; per day, address and user agent without.
+    pub key: String,
+    /// Whether the key comes from the signed cookie.
+    pub from_cookie: bool,
+}
+
+/// Signs visitor cookies and derives stored keys.
+pub struct VisitorKeys {
+    secret: Vec<u8>,
+}
+
+impl VisitorKeys {
+    pub fn new(secret: &[u8]) -> Self {
+        VisitorKeys {
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// Keys from `VISITOR_SECRET`, or from a secret generated on first
+    /// start and kept in the database so cookies survive restarts.
+    pub async fn load(db: &Database, config: &Config) -> Result<Self, sqlx::Error> {
+        if let Some(secret) = &config.visitor_secret {
+            return Ok(VisitorKeys::new(secret.as_bytes()));
+        }
+        use argon2::password_hash::rand_core::{OsRng, RngCore};
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = db
+            .get_or_create_secret(SECRET_NAME, &hex::encode(bytes))
+            .await?;
+        Ok(VisitorKeys::new(secret.as_bytes()))
+    }
+
+    /// Random 128-bit visitor ID, hex encoded.
+    pub fn new_id() -> String {
+        use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    fn mac(&self, parts: &[&str]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        for part in parts {
+            mac.update(part.as_bytes());
+            mac.update(&[0]);
+        }
+        mac
+    }
+
+    /// Cookie value: the ID and its signature.
+    pub fn sign(&self, id: &str) -> String {
+        let signature = self.mac(&["cookie", id]).finalize().into_bytes();
+        format!("{}.{}", id, hex::encode(signature))
+    }
+
+    /// The ID inside a cookie value, if its signature is valid.
+    pub fn verify<'a>(&self, value: &'a str) -> Option<&'a str> {
+        let (id, signature) = value.split_once('.')?;
+        let signature = hex::decode(signature).ok()?;
+        self.mac(&["cookie", id]).verify_slice(&signature).ok()?;
+        Some(id)
+    }
+
+    /// What gets stored for a cookie visitor.
+    pub fn key_for_id(&self, id: &str) -> String {
+        hex::encode(&self.mac(&["key", id]).finalize().into_bytes()[..16])
+    }
+
+    /// What gets stored for a visitor without a cookie on `date`.
+    pub fn daily_key(&self, date: &str, ip: &str, user_agent: &str) -> String {
+        hex::encode(
+            &self
+                .mac(&["daily", date, ip, user_agent])
+                .finalize()
+                .into_bytes()[..16],
+        )
+    }
+
+    pub fn set_cookie(&self, id: &str) -> String {
+        format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+            VISITOR_COOKIE,
+            self.sign(id),
+            COOKIE_MAX_AGE_SECS
+        )
+    }
+
+    /// The visitor behind a request from `ip` on `date`, and the cookie to
+    /// issue if it is a page load without one. An ID is only trusted once it
+    /// comes back in a valid cookie, so the request that issues the cookie
+    /// is counted under the daily key like any other cookieless request.
+    pub fn identify(
+        &self,
+        method: &Method,
+        headers: &HeaderMap,
+        ip: &str,
+        date: &str,
+    ) -> (Visitor, Option<String>) {
+        if let Some(id) = cookie_value(headers, VISITOR_COOKIE).and_then(|v| self.verify(v)) {
+            let visitor = Visitor {
+                key: self.key_for_id(id),
+                from_cookie: true,
+            };
+            return (visitor, None);
+        }
+
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let visitor = Visitor {
+            key: self.daily_key(date, ip, user_agent),
+            from_cookie: false,
+        };
+        let new_cookie =
+            is_navigation(method, headers).then(|| self.set_cookie(&VisitorKeys::new_id()));
+        (visitor, new_cookie)
+    }
+}
+
+/// Page loads get a cookie; fetches and asset requests do not.
+fn is_navigation(method: &Method, headers: &HeaderMap) -> bool {
+    method == Method::GET
+        && headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/html"))
+}
+
+/// Identify the visitor, issuing a cookie on their first page load, and
+/// make the [`Visitor`] available to handlers as a request extension.
+pub async fn assign_visitor(
+    State(state): State<SharedState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let ip = req
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_default();
+    let today = today_in(state.config.site_timezone)
+        .format(DATE_FORMAT)
+        .to_string();
+    let (visitor, new_cookie) =
+        state
+            .visitor_keys
+            .identify(req.method(), req.headers(), &ip, &today);
+
+    req.extensions_mut().insert(visitor);
+    let mut response = next.run(req).await;
+    if let Some(cookie) = new_cookie.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_signature() {
+        let keys = VisitorKeys::new(b"secret");
+        let id = VisitorKeys::new_id();
+        let value = keys.sign(&id);
+        assert_eq!(keys.verify(&value), Some(id.as_str()));
+
+        // A forged or re-signed ID is rejected.
+        let other = VisitorKeys::new_id();
+        let (_, signature) = value.split_once('.').unwrap();
+        assert_eq!(keys.verify(&format!("{}.{}", other, signature)), None);
+        assert_eq!(VisitorKeys::new(b"other").verify(&value), None);
+        assert_eq!(keys.verify(&other), None);
+    }
+
+    #[test]
+    fn test_stored_keys_do_not_reveal_inputs() {
+        let keys = VisitorKeys::new(b"secret");
+        let id = VisitorKeys::new_id();
+        assert_ne!(keys.key_for_id(&id), id);
+        assert_eq!(keys.key_for_id(&id), keys.key_for_id(&id));
+
+        let today = keys.daily_key("2024-01-01", "203.0.113.7", "Firefox");
+        assert!(!today.contains("203.0.113.7"));
+        assert_ne!(
+            today,
+            keys.daily_key("2024-01-02", "203.0.113.7", "Firefox")
+        );
+        assert_ne!(
+            today,
+            VisitorKeys::new(b"other").daily_key("2024-01-01", "203.0.113.7", "Firefox")
+        );
+    }
+
+    #[test]
+    fn test_only_page_loads_get_a_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html,application/xhtml+xml,*/*;q=0.8"),
+        );
+        assert!(is_navigation(&Method::GET, &headers));
+        assert!(!is_navigation(&Method::POST, &headers));
+
+        headers.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        assert!(!is_navigation(&Method::GET, &headers));
+    }
+
+    #[test]
+    fn test_cookieless_page_loads_share_the_daily_key() {
+        let keys = VisitorKeys::new(b"secret");
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/html"));
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("Firefox"));
+
+        let (first, first_cookie) =
+            keys.identify(&Method::GET, &headers, "203.0.113.7", "2024-01-01");
+        let (second, second_cookie) =
+            keys.identify(&Method::GET, &headers, "203.0.113.7", "2024-01-01");
+        assert_eq!(first, second);
+        assert!(!first.from_cookie);
+        assert_eq!(
+            first.key,
+            keys.daily_key("2024-01-01", "203.0.113.7", "Firefox")
+        );
+        // Each still gets offered a cookie, with a fresh ID.
+        let (first_cookie, second_cookie) = (first_cookie.unwrap(), second_cookie.unwrap());
+        assert_ne!(first_cookie, second_cookie);
+
+        // The ID counts once the cookie comes back.
+        let value = first_cookie
+            .strip_prefix("visitor=")
+            .and_then(|c| c.split(';').next())
+            .unwrap();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("visitor={}", value)).unwrap(),
+        );
+        let (returning, cookie) =
+            keys.identify(&Method::GET, &headers, "203.0.113.7", "2024-01-01");
+        assert!(returning.from_cookie);
+        assert_eq!(returning.key, keys.key_for_id(keys.verify(value).unwrap()));
+        assert_eq!(cookie, None);
+    }
+}
@@ -0,0 +1,465 @@
+//! Webmention (<https://www.w3.org/TR/webmention/>) receiving and sending.
+
+use crate::{
+    db::Database,
+    http::{HttpClient, HttpRequest},
+    SITE_URL,
+};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use url::Url;
+
+pub const ENDPOINT_PATH: &str = "/webmention";
+
+fn href_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)\bhref\s*=\s*["']([^"']*)["']"#).unwrap())
+}
+
+fn rel_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)\brel\s*=\s*["']([^"']*)["']"#).unwrap())
+}
+
+/// Slug of the local post a Webmention target points at, if any.
+pub fn post_slug_from_target(target: &str) -> Option<String> {
+    let url = Url::parse(target).ok()?;
+    let site = Url::parse(SITE_URL).ok()?;
+    if url.host_str() != site.host_str() {
+        return None;
+    }
+    let slug = url.path().strip_prefix("/post/")?.trim_end_matches('/');
+    (!slug.is_empty() && !slug.contains('/')).then(|| slug.to_string())
+}
+
+/// Check a received `source` before queuing it for verification.
+pub fn validate_source(source: &str, target: &str) -> Result<(), &'static str> {
+    let url = Url::parse(source).map_err(|_| "source is not a valid URL")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("source must be an http(s) URL");
+    }
+    if normalize(source) == normalize(target) {
+        return Err("source and target must differ");
+    }
+    Ok(())
+}
+
+fn normalize(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or(url);
+    url.trim_end_matches('/').to_string()
+}
+
+/// Whether an HTML document contains a link to `target`.
+pub fn links_to(html: &str, base: &str, target: &str) -> bool {
+    let base = Url::parse(base).ok();
+    let target = normalize(target);
+    href_re().captures_iter(html).any(|cap| {
+        let href = decode_entities(&cap[1]);
+        let absolute = match &base {
+            Some(base) => base.join(&href).map(|u| u.to_string()).unwrap_or(href),
+            None => href,
+        };
+        normalize(&absolute) == target
+    })
+}
+
+/// Absolute `http(s)` links in a post body that point away from this site.
+pub fn external_links(html: &str) -> Vec<String> {
+    let site_host = Url::parse(SITE_URL)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    let mut seen = HashSet::new();
+    href_re()
+        .captures_iter(html)
+        .filter_map(|cap| Url::parse(&decode_entities(&cap[1])).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|url| url.host_str().map(str::to_string) != site_host)
+        .map(|mut url| {
+            url.set_fragment(None);
+            url.to_string()
+        })
+        .filter(|url| seen.insert(url.clone()))
+        .collect()
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+}
+
+fn has_rel(tag: &str, rel: &str) -> bool {
+    rel_re().captures(tag).is_some_and(|cap| {
+        cap[1]
+            .split_ascii_whitespace()
+            .any(|r| r.eq_ignore_ascii_case(rel))
+    })
+}
+
+/// Webmention endpoint advertised by a fetched page: an HTTP `Link` header
+/// takes precedence over `<link>`/`<a>` elements, per the spec.
+pub fn find_endpoint(page_url: &str, link_headers: &[&str], html: &str) -> Option<String> {
+    let base = Url::parse(page_url).ok()?;
+
+    let from_header = link_headers
+        .iter()
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().split_once(';')?;
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            let rel_param = params.split(';').find_map(|p| {
+                let (k, v) = p.trim().split_once('=')?;
+                k.trim()
+                    .eq_ignore_ascii_case("rel")
+                    .then(|| v.trim().trim_matches('"').to_string())
+            })?;
+            rel_param
+                .split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("webmention"))
+                .then(|| target.to_string())
+        });
+
+    let endpoint = from_header.or_else(|| {
+        static TAG_RE: OnceLock<Regex> = OnceLock::new();
+        let tag_re = TAG_RE.get_or_init(|| Regex::new(r"(?is)<(?:link|a)\b[^>]*>").unwrap());
+        tag_re
+            .find_iter(html)
+            .map(|m| m.as_str())
+            .filter(|tag| has_rel(tag, "webmention"))
+            .find_map(|tag| href_re().captures(tag).map(|c| decode_entities(&c[1])))
+    })?;
+
+    // An empty href means the page itself is the endpoint
+    base.join(&endpoint).ok().map(|u| u.to_string())
+}
+
+fn first_capture(
+    html: &str,
+    pattern: &'static str,
+    cache: &'static OnceLock<Regex>,
+) -> Option<String> {
+    let re = cache.get_or_init(|| Regex::new(pattern).unwrap());
+    re.captures(html)
+        .map(|c| decode_entities(c[1].trim()))
+        .filter(|s| !s.is_empty())
+        .map(|s| s.chars().take(200).collect())
+}
+
+/// Title and author name of a mentioning page, from microformats or `<head>`.
+fn page_summary(html: &str) -> (Option<String>, Option<String>) {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static ENTRY_NAME: OnceLock<Regex> = OnceLock::new();
+    static AUTHOR_CARD: OnceLock<Regex> = OnceLock::new();
+    static AUTHOR_META: OnceLock<Regex> = OnceLock::new();
+
+    let title = first_capture(
+        html,
+        r#"(?is)<[a-z0-9]+\b[^>]*class\s*=\s*["'][^"']*\bp-name\b[^"']*["'][^>]*>([^<]+)<"#,
+        &ENTRY_NAME,
+    )
+    .or_else(|| first_capture(html, r"(?is)<title[^>]*>(.*?)</title>", &TITLE));
+
+    let author = first_capture(
+        html,
+        r#"(?is)<[a-z0-9]+\b[^>]*class\s*=\s*["'][^"']*\bp-author\b[^"']*["'][^>]*>([^<]+)<"#,
+        &AUTHOR_CARD,
+    )
+    .or_else(|| {
+        first_capture(
+            html,
+            r#"(?is)<meta\s+name\s*=\s*["']author["']\s+content\s*=\s*["']([^"']*)["']"#,
+            &AUTHOR_META,
+        )
+    });
+
+    (title, author)
+}
+
+/// Fetch `source` and check that it really links to `target`. Verified
+/// mentions become visible; anything else is removed.
+pub async fn verify(db: &Database, http: &dyn HttpClient, id: &str, source: &str, target: &str) {
+    let response = match http
+        .execute(HttpRequest::get(source).header("Accept", "text/html"))
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::info!("Webmention source {} unreachable: {}", source, e);
+            if let Err(e) = db.delete_webmention(id).await {
+                tracing::error!("delete_webmention error for id={}: {}", id, e);
+            }
+            return;
+        }
+    };
+
+    let result = if response.is_success() && links_to(&response.body, &response.url, target) {
+        let (title, author) = page_summary(&response.body);
+        tracing::info!("Verified Webmention {} -> {}", source, target);
+        db.mark_webmention_verified(id, author.as_deref(), title.as_deref())
+            .await
+    } else {
+        tracing::info!(
+            "Rejected Webmention {} -> {} (status {})",
+            source,
+            target,
+            response.status
+        );
+        db.delete_webmention(id).await
+    };
+    if let Err(e) = result {
+        tracing::error!("webmention update error for id={}: {}", id, e);
+    }
+}
+
+/// Discover the endpoint for `target` and notify it that `source` links there.
+/// Returns the endpoint used and its status code, or `None` if the target
+/// does not accept Webmentions.
+pub async fn send(
+    http: &dyn HttpClient,
+    source: &str,
+    target: &str,
+) -> anyhow::Result<Option<(String, u16)>> {
+    let page = http
+        .execute(HttpRequest::get(target).header("Accept", "text/html"))
+        .await?;
+    let link_headers: Vec<&str> = page
+        .headers
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    let Some(endpoint) = find_endpoint(&page.url, &link_headers, &page.body) else {
+        return Ok(None);
+    };
+
+    let response = http
+        .execute(HttpRequest::post_form(
+            &endpoint,
+            &[("source", source), ("target", target)],
+        ))
+        .await?;
+    Ok(Some((endpoint, response.status)))
+}
+
+/// A post whose outgoing links should be mentioned.
+pub struct OutgoingPost {
+    pub slug: String,
+    pub content: String,
+}
+
+/// Send Webmentions for every external link in `posts` that has not been
+/// attempted before.
+pub async fn send_for_posts(db: &Database, http: &dyn HttpClient, posts: &[OutgoingPost]) {
+    for post in posts {
+        let source = format!("{}/post/{}", SITE_URL, post.slug);
+        for target in external_links(&post.content) {
+            match db.has_sent_webmention(&post.slug, &target).await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!("has_sent_webmention error: {}", e);
+                    return;
+                }
+            }
+
+            let (endpoint, status) = match send(http, &source, &target).await {
+                Ok(Some((endpoint, status))) => {
+                    tracing::info!("Sent Webmention {} -> {} ({})", source, target, status);
+                    (Some(endpoint), Some(status))
+                }
+                Ok(None) => (None, None),
+                Err(e) => {
+                    tracing::info!("Webmention discovery failed for {}: {}", target, e);
+                    (None, None)
+                }
+            };
+            if let Err(e) = db
+                .record_sent_webmention(&post.slug, &target, endpoint.as_deref(), status)
+                .await
+            {
+                tracing::error!("record_sent_webmention error: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use crate::http::{stand_in_server, ReqwestClient};
+    use axum::{
+        http::header,
+        routing::{get, post},
+        Form, Router,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_post_slug_from_target() {
+        assert_eq!(
+            post_slug_from_target("https://miniex.blog/post/hello-world"),
+            Some("hello-world".to_string())
+        );
+        assert_eq!(
+            post_slug_from_target("https://miniex.blog/post/hello-world/"),
+            Some("hello-world".to_string())
+        );
+        assert_eq!(post_slug_from_target("https://evil.example/post/x"), None);
+        assert_eq!(post_slug_from_target("https://miniex.blog/guestbook"), None);
+    }
+
+    #[test]
+    fn test_validate_source() {
+        let target = "https://miniex.blog/post/a";
+        assert!(validate_source("https://example.com/reply", target).is_ok());
+        assert!(validate_source("ftp://example.com/reply", target).is_err());
+        assert!(validate_source(target, target).is_err());
+    }
+
+    #[test]
+    fn test_links_to_resolves_relative_and_entities() {
+        let html = r#"<a href="https://miniex.blog/post/a/">x</a>"#;
+        assert!(links_to(
+            html,
+            "https://example.com/",
+            "https://miniex.blog/post/a"
+        ));
+        let html = r#"<a href='/post/a?x=1&amp;y=2'>x</a>"#;
+        assert!(links_to(
+            html,
+            "https://miniex.blog/",
+            "https://miniex.blog/post/a?x=1&y=2"
+        ));
+        assert!(!links_to(
+            "<p>https://miniex.blog/post/a</p>",
+            "https://e.com/",
+            "https://miniex.blog/post/a"
+        ));
+    }
+
+    #[test]
+    fn test_external_links_skip_own_site() {
+        let html = r##"<a href="https://miniex.blog/post/x">own</a>
+            <a href="https://example.com/a#frag">ext</a>
+            <a href="https://example.com/a">dup</a>
+            <a href="/relative">rel</a>"##;
+        assert_eq!(
+            external_links(html),
+            vec!["https://example.com/a".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_find_endpoint_prefers_link_header() {
+        let html = r#"<link rel="webmention" href="/from-html">"#;
+        assert_eq!(
+            find_endpoint(
+                "https://example.com/post",
+                &[r#"<https://example.com/hook>; rel="webmention""#],
+                html
+            ),
+            Some("https://example.com/hook".to_string())
+        );
+        assert_eq!(
+            find_endpoint("https://example.com/post", &[], html),
+            Some("https://example.com/from-html".to_string())
+        );
+    }
+
+    #[test]
+    fn test_find_endpoint_rel_lists_and_empty_href() {
+        let html = r#"<a href="" rel="nofollow webmention">wm</a>"#;
+        assert_eq!(
+            find_endpoint("https://example.com/post?a=1", &[], html),
+            Some("https://example.com/post?a=1".to_string())
+        );
+        assert_eq!(
+            find_endpoint("https://example.com/", &[], "<a href='/x'>"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_page_summary() {
+        let html = r#"<title>Fallback</title><article class="h-entry"><h1 class="p-name">Great post</h1>
+            <a class="p-author h-card" href="/">Alice</a></article>"#;
+        let (title, author) = page_summary(html);
+        assert_eq!(title.as_deref(), Some("Great post"));
+        assert_eq!(author.as_deref(), Some("Alice"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_against_stand_in_source() {
+        let target = "https://miniex.blog/post/hello";
+        let app = Router::new()
+            .route(
+                "/links",
+                get(|| async {
+                    axum::response::Html(
+                        r#"<title>Reply</title><a href="https://miniex.blog/post/hello">re</a>"#,
+                    )
+                }),
+            )
+            .route("/nolink", get(|| async { "nothing here" }));
+        let base = stand_in_server(|_| app).await;
+        let db = test_db().await;
+        let http = ReqwestClient::new(true);
+
+        let good = format!("{}/links", base);
+        let id = db.upsert_webmention(&good, target, "hello").await.unwrap();
+        verify(&db, &http, &id, &good, target).await;
+
+        let bad = format!("{}/nolink", base);
+        let id = db.upsert_webmention(&bad, target, "hello").await.unwrap();
+        verify(&db, &http, &id, &bad, target).await;
+
+        let mentions = db.get_webmentions_for_post("hello").await.unwrap();
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].source, good);
+        assert_eq!(mentions[0].title.as_deref(), Some("Reply"));
+    }
+
+    #[tokio::test]
+    async fn test_send_discovers_endpoint_and_posts() {
+        let received = Arc::new(Mutex::new(Vec::<HashMap<String, String>>::new()));
+        let store = received.clone();
+        let app = Router::new()
+            .route(
+                "/article",
+                get(|| async { ([(header::LINK, "</endpoint>; rel=\"webmention\"")], "hi") }),
+            )
+            .route(
+                "/endpoint",
+                post(move |Form(form): Form<HashMap<String, String>>| {
+                    let store = store.clone();
+                    async move {
+                        store.lock().unwrap().push(form);
+                        axum::http::StatusCode::ACCEPTED
+                    }
+                }),
+            );
+        let base = stand_in_server(|_| app).await;
+        let db = test_db().await;
+        let http = ReqwestClient::new(true);
+        let target = format!("{}/article", base);
+        let posts = vec![OutgoingPost {
+            slug: "hello".to_string(),
+            content: format!(r#"<p>See <a href="{}">this</a></p>"#, target),
+        }];
+
+        send_for_posts(&db, &http, &posts).await;
+        // Already attempted pairs are not sent again
+        send_for_posts(&db, &http, &posts).await;
+
+        assert!(db.has_sent_webmention("hello", &target).await.unwrap());
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["source"], "https://miniex.blog/post/hello");
+        assert_eq!(received[0]["target"], target);
+    }
+}